@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::math::statistics;
+
+pub const UNKNOWN_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+/// Intervals failing the drill-hole filter when they are greyed out
+pub const FILTERED_COLOR: [f32; 4] = [0.2, 0.2, 0.2, 1.0];
+
+/// Named colour ramps, sampled between 0 and 1.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Colormap {
+    /// The green, yellow, blue, red ramp of [`super::mesh_handlers::color_scale`]
+    #[default]
+    Classic,
+    Viridis,
+    Jet,
+    Grey,
+    Diverging,
+}
+
+const VIRIDIS: [[f32; 3]; 9] = [
+    [0.267, 0.005, 0.329],
+    [0.278, 0.175, 0.483],
+    [0.229, 0.322, 0.546],
+    [0.173, 0.449, 0.558],
+    [0.128, 0.567, 0.551],
+    [0.153, 0.680, 0.504],
+    [0.360, 0.785, 0.388],
+    [0.678, 0.864, 0.190],
+    [0.993, 0.906, 0.144],
+];
+
+const JET: [[f32; 3]; 5] = [
+    [0.0, 0.0, 0.5],
+    [0.0, 0.5, 1.0],
+    [0.5, 1.0, 0.5],
+    [1.0, 0.5, 0.0],
+    [0.5, 0.0, 0.0],
+];
+
+const GREY: [[f32; 3]; 2] = [[0.0, 0.0, 0.0], [1.0, 1.0, 1.0]];
+
+const DIVERGING: [[f32; 3]; 5] = [
+    [0.230, 0.299, 0.754],
+    [0.552, 0.690, 0.996],
+    [0.865, 0.865, 0.865],
+    [0.958, 0.604, 0.483],
+    [0.706, 0.016, 0.150],
+];
+
+/// Linear interpolation between evenly spaced colour stops.
+fn sample_stops(stops: &[[f32; 3]], t: f32) -> [f32; 4] {
+    let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let i = (position.floor() as usize).min(stops.len() - 2);
+    let t = position - i as f32;
+    let (a, b) = (stops[i], stops[i + 1]);
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+        1.0,
+    ]
+}
+
+impl Colormap {
+    pub fn name(self) -> &'static str {
+        match self {
+            Colormap::Classic => "Classic",
+            Colormap::Viridis => "Viridis",
+            Colormap::Jet => "Jet",
+            Colormap::Grey => "Grey",
+            Colormap::Diverging => "Diverging",
+        }
+    }
+
+    pub fn all() -> [Colormap; 5] {
+        [
+            Colormap::Classic,
+            Colormap::Viridis,
+            Colormap::Jet,
+            Colormap::Grey,
+            Colormap::Diverging,
+        ]
+    }
+
+    pub fn sample(self, t: f32) -> [f32; 4] {
+        match self {
+            Colormap::Classic => super::mesh_handlers::color_scale(t.clamp(0.0, 1.0)),
+            Colormap::Viridis => sample_stops(&VIRIDIS, t),
+            Colormap::Jet => sample_stops(&JET, t),
+            Colormap::Grey => sample_stops(&GREY, t),
+            Colormap::Diverging => sample_stops(&DIVERGING, t),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum ColorScaling {
+    #[default]
+    Linear,
+    /// Logarithmic between min and max, for skewed grades
+    Log,
+}
+
+impl ColorScaling {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorScaling::Linear => "Linear",
+            ColorScaling::Log => "Log",
+        }
+    }
+
+    pub fn all() -> [ColorScaling; 2] {
+        [ColorScaling::Linear, ColorScaling::Log]
+    }
+}
+
+/// Values from `from` up to the next bin get `color`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorBin {
+    pub from: f32,
+    pub color: [f32; 3],
+}
+
+/// How the values of a numeric variable are coloured.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ColorLegend {
+    pub colormap: Colormap,
+    pub scaling: ColorScaling,
+    pub min: f32,
+    pub max: f32,
+    /// Values outside `min..max` get the colour of the closest end, otherwise they are grey
+    pub clamp: bool,
+    /// Cut-off bins sorted by `from`, used instead of the ramp when not empty
+    pub bins: Vec<ColorBin>,
+}
+
+impl Default for ColorLegend {
+    fn default() -> Self {
+        Self {
+            colormap: Colormap::default(),
+            scaling: ColorScaling::default(),
+            min: 0.0,
+            max: 1.0,
+            clamp: true,
+            bins: vec![],
+        }
+    }
+}
+
+impl ColorLegend {
+    /// Ramp between the P25 and P75 of the values.
+    pub fn from_values(values: &[f32]) -> Self {
+        Self {
+            min: statistics::quantile(values, 0.25).unwrap_or(0.0),
+            max: statistics::quantile(values, 0.75).unwrap_or(1.0),
+            ..Default::default()
+        }
+    }
+
+    /// Position of the value in the ramp, 0 at `min` and 1 at `max`.
+    pub fn normalize(&self, value: f32) -> f32 {
+        match self.scaling {
+            ColorScaling::Linear => (value - self.min) / (self.max - self.min),
+            ColorScaling::Log => {
+                let min = self.min.max(1e-6).ln();
+                let max = self.max.max(1e-6).ln();
+                (value.max(1e-6).ln() - min) / (max - min)
+            }
+        }
+    }
+
+    pub fn color(&self, value: f32) -> [f32; 4] {
+        if !value.is_finite() {
+            return UNKNOWN_COLOR;
+        }
+        if !self.clamp && (value < self.min || value > self.max) {
+            return UNKNOWN_COLOR;
+        }
+
+        if !self.bins.is_empty() {
+            let bin = self.bins.iter().rev()
+                .find(|bin| bin.from <= value)
+                .unwrap_or(&self.bins[0]);
+            return [bin.color[0], bin.color[1], bin.color[2], 1.0];
+        }
+
+        let t = self.normalize(value);
+        self.colormap.sample(if t.is_finite() { t } else { 0.0 })
+    }
+
+    /// Splits `min..max` in `count` bins coloured with the ramp.
+    pub fn equal_bins(&self, count: usize) -> Vec<ColorBin> {
+        let count = count.max(1);
+        (0..count)
+            .map(|i| {
+                let t = i as f32 / count as f32;
+                let color = self.colormap.sample(if count == 1 { 0.5 } else { i as f32 / (count - 1) as f32 });
+                ColorBin {
+                    from: self.min + (self.max - self.min) * t,
+                    color: [color[0], color[1], color[2]],
+                }
+            })
+            .collect()
+    }
+
+    pub fn sort_bins(&mut self) {
+        self.bins.sort_by(|a, b| a.from.partial_cmp(&b.from).unwrap_or(std::cmp::Ordering::Equal));
+    }
+}
+
+/// Colour legend of every numeric drill-hole variable, by variable name.
+/// Changing it recolours the layers showing the variable.
+#[derive(Resource, Default)]
+pub struct GradeLegends {
+    pub legends: HashMap<String, ColorLegend>,
+}
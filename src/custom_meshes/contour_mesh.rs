@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+use bevy::render::mesh::{PrimitiveTopology, VertexAttributeValues};
+
+use crate::custom_meshes::topography_mesh::TopographyMesh;
+use crate::math::contours::{contours, Contour};
+
+pub const MINOR_CONTOUR_COLOR: [f32; 4] = [0.45, 0.3, 0.15, 1.0];
+pub const MAJOR_CONTOUR_COLOR: [f32; 4] = [0.2, 0.1, 0.02, 1.0];
+
+/// Lines are raised over the surface so they are not hidden by its triangles.
+const CONTOUR_LIFT: f32 = 0.1;
+
+/// Contour lines of the topography the entity is a child of, drawn as a line mesh.
+#[derive(Component)]
+pub struct ContourLines {
+    pub contours: Vec<Contour>,
+    pub interval: f32,
+    pub major_every: usize,
+    /// Offsets of the topography, `[x, y, z]`, added back on export
+    pub offset: [f64; 3],
+    pub labels: bool,
+    /// Distance along the major contours between elevation labels
+    pub label_spacing: f32,
+    pub font_size: f32,
+    /// Positions of the labels and their elevation, in the space of the topography
+    pub label_points: Vec<(Vec3, f32)>,
+}
+
+impl ContourLines {
+    /// Contours of the topography mesh, `None` when it is not a triangle list.
+    pub fn from_topography(mesh: &Mesh, topography: &TopographyMesh, interval: f32, major_every: usize, label_spacing: f32) -> Option<Self> {
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            return None;
+        };
+        let positions = positions.iter().map(|p| Vec3::from(*p)).collect::<Vec<_>>();
+        let indices = mesh.indices()?.iter().collect::<Vec<_>>();
+        let triangles = indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect::<Vec<_>>();
+
+        let contours = contours(&positions, &triangles, topography.offset_z as f32, interval, major_every);
+        let label_points = contours.iter()
+            .filter(|contour| contour.major)
+            .flat_map(|contour| contour.points_every(label_spacing).into_iter().map(|point| (point, contour.elevation)))
+            .collect();
+
+        Some(Self {
+            contours,
+            interval,
+            major_every,
+            offset: [topography.offset_x, topography.offset_y, topography.offset_z],
+            labels: true,
+            label_spacing,
+            font_size: 11.0,
+            label_points,
+        })
+    }
+
+    /// Elevation as a label, with the decimals the interval needs, up to three.
+    pub fn label(&self, elevation: f32) -> String {
+        let decimals = (0..3)
+            .find(|decimals| {
+                let scaled = self.interval as f64 * 10f64.powi(*decimals);
+                (scaled - scaled.round()).abs() < 1e-4
+            })
+            .unwrap_or(3);
+        format!("{:.*}", decimals as usize, elevation)
+    }
+
+    /// Line list of every contour, the majors darker than the minors.
+    pub fn mesh(&self) -> Mesh {
+        let mut positions: Vec<[f32; 3]> = vec![];
+        let mut colors: Vec<[f32; 4]> = vec![];
+
+        for contour in self.contours.iter() {
+            let color = if contour.major { MAJOR_CONTOUR_COLOR } else { MINOR_CONTOUR_COLOR };
+            let mut points = contour.points.clone();
+            if contour.closed {
+                points.extend(contour.points.first().copied());
+            }
+            for pair in points.windows(2) {
+                positions.push((pair[0] + Vec3::Y * CONTOUR_LIFT).into());
+                positions.push((pair[1] + Vec3::Y * CONTOUR_LIFT).into());
+                colors.extend([color; 2]);
+            }
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        mesh
+    }
+}
+
+/// Spawns the contours as a child of the topography. Returns the contours entity.
+pub fn spawn_contours(world: &mut World, topography: Entity, contours: ContourLines, name: &str) -> Entity {
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(contours.mesh());
+    let material = world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial {
+        unlit: true,
+        ..Default::default()
+    });
+
+    let contours_id = world.spawn((
+        PbrBundle {
+            mesh,
+            material,
+            ..Default::default()
+        },
+        Name::new(name.to_string()),
+        contours,
+    )).id();
+    world.entity_mut(topography).add_child(contours_id);
+    contours_id
+}
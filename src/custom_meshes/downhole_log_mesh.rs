@@ -0,0 +1,182 @@
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::primitives::Aabb;
+
+use crate::custom_meshes::color_legend::ColorLegend;
+use crate::custom_meshes::drill_holes_mesh::DrillHolesData;
+use crate::files_manager::las_parser::LasLog;
+use crate::math::desurvey::DrillTrace;
+
+/// How the values of a downhole log curve are drawn along the hole.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum LogStyle {
+    /// Ribbon of constant width coloured by the legend
+    #[default]
+    Colored,
+    /// Ribbon coloured by the legend, as wide as the value
+    Width,
+}
+
+impl LogStyle {
+    pub fn name(self) -> &'static str {
+        match self {
+            LogStyle::Colored => "Coloured",
+            LogStyle::Width => "Width modulated",
+        }
+    }
+
+    pub fn all() -> [LogStyle; 2] {
+        [LogStyle::Colored, LogStyle::Width]
+    }
+}
+
+/// Geophysics log of a hole of the [`DrillHolesData`] of its closest ancestor holding one,
+/// drawn as a ribbon beside the trace. Changing it rebuilds the mesh of the entity.
+#[derive(Component)]
+pub struct DownholeLog {
+    pub hole: usize,
+    pub log: LasLog,
+    /// Index of the displayed curve in `log.curves`
+    pub curve: usize,
+    pub style: LogStyle,
+    /// Distance from the trace to the inner edge of the ribbon, clear of the interval prisms
+    pub offset: f32,
+    /// Width of the ribbon, the largest one when the width is modulated
+    pub width: f32,
+    pub legend: ColorLegend,
+}
+
+impl DownholeLog {
+    pub fn new(hole: usize, log: LasLog) -> Self {
+        let mut downhole_log = Self {
+            hole,
+            log,
+            curve: 0,
+            style: LogStyle::default(),
+            offset: 5.0,
+            width: 10.0,
+            legend: ColorLegend::default(),
+        };
+        downhole_log.reset_legend();
+        downhole_log
+    }
+
+    /// Ramp between the P25 and P75 of the displayed curve.
+    pub fn reset_legend(&mut self) {
+        let values = self.log.curves.get(self.curve)
+            .map(|curve| curve.values.iter().flatten().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        self.legend = ColorLegend::from_values(&values);
+    }
+
+    /// Ribbon of the displayed curve, samples outside of the trace or without value leave a gap.
+    pub fn mesh(&self, trace: &DrillTrace) -> Mesh {
+        let mut positions: Vec<[f32; 3]> = vec![];
+        let mut normals: Vec<[f32; 3]> = vec![];
+        let mut colors: Vec<[f32; 4]> = vec![];
+        let mut indices: Vec<u32> = vec![];
+
+        let values = self.log.curves.get(self.curve).map(|curve| curve.values.as_slice()).unwrap_or(&[]);
+        let samples = self.log.depths.iter().zip(values)
+            .map(|(depth, value)| (*depth, *value))
+            .collect::<Vec<_>>();
+
+        for pair in samples.windows(2) {
+            let ((from, Some(from_value)), (to, Some(to_value))) = (pair[0], pair[1]) else {
+                continue;
+            };
+            // Logs may be recorded going up the hole
+            if from.min(to) < 0.0 || from.max(to) > trace.length() || (to - from).abs() <= f32::EPSILON {
+                continue;
+            }
+
+            let start = positions.len() as u32;
+            for (depth, value) in [(from, from_value), (to, to_value)] {
+                let point = trace.point_at(depth);
+                let direction = (trace.point_at(depth + 0.5) - trace.point_at(depth - 0.5)).normalize_or_zero();
+                let horizontal = direction.cross(Vec3::Y);
+                let side = if horizontal.length() > 1e-3 { horizontal.normalize() } else { Vec3::X };
+
+                let width = match self.style {
+                    LogStyle::Colored => self.width,
+                    LogStyle::Width => self.width * self.legend.normalize(value).clamp(0.0, 1.0),
+                };
+                let color = self.legend.color(value);
+
+                positions.push((point + side * self.offset).into());
+                positions.push((point + side * (self.offset + width)).into());
+                let normal: [f32; 3] = side.cross(direction).normalize_or_zero().into();
+                normals.extend([normal; 2]);
+                colors.extend([color; 2]);
+            }
+
+            indices.extend([start, start + 1, start + 2, start + 1, start + 3, start + 2]);
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh
+    }
+}
+
+/// Spawns the log as a child of `parent`, its mesh is built by `update_downhole_logs` on the next frame.
+pub fn spawn_downhole_log(world: &mut World, parent: Entity, log: DownholeLog, name: &str) -> Entity {
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(Mesh::new(PrimitiveTopology::TriangleList));
+    // The ribbon is flat, both faces are drawn
+    let material = world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial {
+        cull_mode: None,
+        double_sided: true,
+        ..Default::default()
+    });
+
+    let log_id = world.spawn((
+        PbrBundle {
+            mesh,
+            material,
+            ..Default::default()
+        },
+        Name::new(name.to_string()),
+        log,
+    )).id();
+    world.entity_mut(parent).add_child(log_id);
+    log_id
+}
+
+/// Rebuilds the mesh of every log whose settings or data changed.
+pub fn update_downhole_logs(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    data: Query<Ref<DrillHolesData>>,
+    parents: Query<&Parent>,
+    logs: Query<(Entity, Ref<DownholeLog>, &Handle<Mesh>)>,
+) {
+    for (entity, log, handle) in logs.iter() {
+        let mut ancestor = entity;
+        let data = loop {
+            let Ok(parent) = parents.get(ancestor) else {
+                break None;
+            };
+            ancestor = parent.get();
+            if let Ok(data) = data.get(ancestor) {
+                break Some(data);
+            }
+        };
+        let Some(data) = data else {
+            continue;
+        };
+        if !(log.is_changed() || data.is_changed()) {
+            continue;
+        }
+        let Some(hole) = data.holes.get(log.hole) else {
+            continue;
+        };
+
+        if let Some(mesh) = meshes.get_mut(handle) {
+            *mesh = log.mesh(&hole.trace);
+            commands.entity(entity).remove::<Aabb>();
+        }
+    }
+}
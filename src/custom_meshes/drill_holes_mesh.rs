@@ -1,16 +1,28 @@
 
 
 
+use std::collections::HashMap;
+use std::error::Error;
 use std::ops::Sub;
 use bevy::prelude::*;
 use bevy::prelude::shape::Cylinder;
+use bevy::render::mesh::PrimitiveTopology;
+use bevy::render::primitives::Aabb;
 
 use polars::prelude::*;
-use crate::files_manager::csv_parser::CsvFile;
-use crate::math::analytic_geometry;
-
-
-/// Saves the files
+use crate::custom_meshes::color_legend::{ColorLegend, GradeLegends, FILTERED_COLOR, UNKNOWN_COLOR};
+use crate::custom_meshes::lithology_legend::LithologyLegend;
+use crate::files_manager::column_mapping::{ColumnMapping, DrillTable};
+use crate::files_manager::csv_parser::{f32_column, string_column, CsvFile};
+use crate::files_manager::drill_holes_validation::{drop_rows, ValidationReport};
+use crate::files_manager::filter_expression::FilterExpression;
+use crate::math::compositing::{self, CompositeMethod, CompositeSettings};
+use crate::math::desurvey::{DesurveyMethod, DrillTrace, SurveyStation};
+use crate::math::intercepts::{self, Intercept, InterceptSettings};
+use crate::math::surface::SurfaceGrid;
+
+
+/// Saves the files and the column mapping of each one
 /// 0: Assay
 /// 1: Header
 /// 2: Lithography
@@ -18,142 +30,359 @@ use crate::math::analytic_geometry;
 #[derive(Component)]
 pub struct DrillHolesMesh{
     pub files: [CsvFile;4],
+    pub mappings: [ColumnMapping;4],
     pub offset_x: Option<f32>,
     pub offset_y: Option<f32>,
     pub offset_z: Option<f32>,
+    pub desurvey_method: DesurveyMethod,
 }
 
-impl DrillHolesMesh {
-    pub fn from_csv(drill_holes: DrillHolesMesh) -> Vec<Mesh>{
-        let assay = &drill_holes.files[0];
-        let header = &drill_holes.files[1];
-        let lithography = &drill_holes.files[2];
-        let survey = &drill_holes.files[3];
-
-
-
-        let df_assay = assay.dataframe().unwrap();
-        let mut df_header = header.dataframe().unwrap();
-        let df_survey = survey.dataframe().unwrap();
-        let df_lithography = lithography.dataframe().unwrap();
-
-        let mut au_grades_meshes_result: Vec<Mesh> = Vec::new();
-        let mut cu_grades_meshes_result: Vec<Mesh> = Vec::new();
-        let mut transforms_result: Vec<Transform> = Vec::new();
-        let _material_au_result: Vec<[f32;3]> = Vec::new();
-        let _material_cu_result: Vec<[f32;3]> = Vec::new();
-        let _material_lithography: Vec<[f32;3]> = Vec::new();
+/// A desurveyed hole, the collar is relative to the linked topography offsets.
+#[derive(Clone)]
+pub struct DrillHole {
+    pub hole_id: String,
+    pub collar: [f32;3],
+    pub trace: DrillTrace,
+}
 
-        let p25_grade_au = df_assay.column("au").unwrap().f64().unwrap()
-            .quantile(0.25, QuantileInterpolOptions::Linear).unwrap().unwrap() as f32;
-        let p75_grade_au = df_assay.column("au").unwrap().f64().unwrap()
-            .quantile(0.75, QuantileInterpolOptions::Linear).unwrap().unwrap() as f32;
+/// A from/to row of the assay or lithology table placed on a hole.
+/// `row` indexes the dataframe the interval was read from.
+#[derive(Clone, Copy)]
+pub struct DrillInterval {
+    pub hole: usize,
+    pub row: usize,
+    pub from: f32,
+    pub to: f32,
+}
 
-        let p25_grade_cu = df_assay.column("cu").unwrap().f64().unwrap()
-            .quantile(0.25, QuantileInterpolOptions::Linear).unwrap().unwrap() as f32;
-        let p75_grade_cu = df_assay.column("cu").unwrap().f64().unwrap()
-            .quantile(0.75, QuantileInterpolOptions::Linear).unwrap().unwrap() as f32;
+/// The loaded drill-hole database, kept in memory so the layers can be rebuilt
+/// without reading the files again.
+#[derive(Component)]
+pub struct DrillHolesData {
+    pub holes: Vec<DrillHole>,
+    /// Collar table in mine grid coordinates
+    pub header: DataFrame,
+    pub assay: DataFrame,
+    pub assay_intervals: Vec<DrillInterval>,
+    pub lithology: DataFrame,
+    pub lithology_intervals: Vec<DrillInterval>,
+    /// Numeric assay columns that can be displayed
+    pub variables: Vec<String>,
+    /// Offsets subtracted from the collars, `[x, y, z]`
+    pub offset: [f32;3],
+}
 
-        let _p25_lithography = df_lithography.column("rock").unwrap().i64().unwrap()
-            .quantile(0.25, QuantileInterpolOptions::Linear).unwrap().unwrap() as f32;
-        let _p75_lithography = df_lithography.column("rock").unwrap().i64().unwrap()
-            .quantile(0.75, QuantileInterpolOptions::Linear).unwrap().unwrap() as f32;
+/// Result of [`DrillHolesMesh::from_csv`], `data` is `None` when the files have errors
+/// and bad records are not skipped.
+pub struct DrillHolesImport {
+    pub data: Option<DrillHolesData>,
+    pub report: ValidationReport,
+}
 
+/// Difference between the surveyed collar elevation and the topography under it.
+/// Elevations are in mine grid coordinates.
+#[derive(Clone)]
+pub struct CollarDelta {
+    pub hole: usize,
+    pub hole_id: String,
+    pub collar_z: f32,
+    /// `None` when the collar is outside the topography
+    pub surface_z: Option<f32>,
+}
 
-        let x_header_colum = df_header.column("x").unwrap().sub(drill_holes.offset_x.unwrap());
-        df_header = (*df_header.with_column(x_header_colum).unwrap()).clone();
+impl CollarDelta {
+    /// Positive when the collar floats above the surface.
+    pub fn delta(&self) -> Option<f32> {
+        self.surface_z.map(|surface_z| self.collar_z - surface_z)
+    }
+}
 
-        let y_header_colum = df_header.column("y").unwrap().sub(drill_holes.offset_y.unwrap());
-        df_header = (*df_header.with_column(y_header_colum).unwrap()).clone();
+/// Significant intercept of a hole of a [`DrillHolesData`].
+pub struct HoleIntercept {
+    pub hole: usize,
+    pub hole_id: String,
+    pub intercept: Intercept,
+}
 
-        let z_header_colum = df_header.column("z").unwrap().sub(drill_holes.offset_z.unwrap());
-        df_header = (*df_header.with_column(z_header_colum).unwrap()).clone();
+/// What a drill-hole layer is coloured by.
+#[derive(Clone, PartialEq, Debug)]
+pub enum DrillHolesVariable {
+    Assay(String),
+    Lithology,
+}
 
-        let df_drills_orientation = df_header.left_join(&df_survey, ["hole-id"], ["hole-id"]).unwrap();
+impl DrillHolesVariable {
+    pub fn name(&self) -> String {
+        match self {
+            DrillHolesVariable::Assay(variable) => variable.clone(),
+            DrillHolesVariable::Lithology => "Lithology".to_string(),
+        }
+    }
+}
 
-        let mut iters_drills = df_drills_orientation
-            .columns(["hole-id","x","y","z","from","to","azimuth","dip"]).unwrap()
-            .iter().map(|s| s.iter()).collect::<Vec<_>>();
+/// A drawable view of the [`DrillHolesData`] of its closest ancestor holding one.
+/// Changing it rebuilds the mesh of the entity.
+#[derive(Component)]
+pub struct DrillHolesLayer {
+    pub variable: DrillHolesVariable,
+    pub radius: f32,
+    /// Index of the only hole drawn, `None` merges every hole in one mesh
+    pub hole: Option<usize>,
+}
 
-        for _row_drills in 0..df_drills_orientation.height(){
-            let hole_id = iters_drills[0].next().unwrap().to_string().replace("\"", "");
-            let x = iters_drills[1].next().unwrap().try_extract::<f32>().unwrap();
-            let y = iters_drills[2].next().unwrap().try_extract::<f32>().unwrap();
-            let z = iters_drills[3].next().unwrap().try_extract::<f32>().unwrap();
-            let _survey_from = iters_drills[4].next().unwrap().try_extract::<f32>().unwrap();
-            let _survey_to = iters_drills[5].next().unwrap().try_extract::<f32>().unwrap();
-            let azimuth = iters_drills[6].next().unwrap().try_extract::<f32>().unwrap();
-            let dip = iters_drills[7].next().unwrap().try_extract::<f32>().unwrap();
+/// How the intervals failing a [`DrillHolesFilter`] are drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum FilterDisplay {
+    #[default]
+    Hide,
+    Grey,
+}
 
-            let df_filtered_assays = df_assay.filter(&df_assay
-                .column("hole-id").unwrap().utf8().unwrap()
-                .contains_literal(&hole_id).unwrap()).unwrap();
+impl FilterDisplay {
+    pub fn name(self) -> &'static str {
+        match self {
+            FilterDisplay::Hide => "Hide",
+            FilterDisplay::Grey => "Grey out",
+        }
+    }
 
-            let mut iters_assay = df_filtered_assays
-                .columns(["hole-id","from","to","au","cu"]).unwrap()
-                .iter().map(|s| s.iter()).collect::<Vec<_>>();
+    pub fn all() -> [FilterDisplay; 2] {
+        [FilterDisplay::Hide, FilterDisplay::Grey]
+    }
+}
 
-            for _row_assay in 0..df_filtered_assays.height(){
+/// Attribute filter of the intervals of a drill-hole entity, as `cu > 0.3 and rock == 4`.
+/// Changing it rebuilds the layers of the entity.
+#[derive(Component, Default)]
+pub struct DrillHolesFilter {
+    /// Text being edited, `expression` is only replaced once it parses
+    pub text: String,
+    pub expression: Option<FilterExpression>,
+    pub display: FilterDisplay,
+    pub error: Option<String>,
+    /// Rows of the assay table passing the expression, `None` when it is not filtered
+    pub assay_passed: Option<Vec<bool>>,
+    pub lithology_passed: Option<Vec<bool>>,
+}
 
-                let _hole_id = iters_assay[0].next().unwrap().to_string().replace("\"", "");
-                let from = iters_assay[1].next().unwrap().try_extract::<f32>().unwrap();
-                let to = iters_assay[2].next().unwrap().try_extract::<f32>().unwrap();
-                let au = iters_assay[3].next().unwrap().try_extract::<f32>().unwrap();
-                let cu = iters_assay[4].next().unwrap().try_extract::<f32>().unwrap();
+impl DrillHolesFilter {
+    pub fn passed(&self, variable: &DrillHolesVariable) -> Option<&[bool]> {
+        match variable {
+            DrillHolesVariable::Assay(_) => self.assay_passed.as_deref(),
+            DrillHolesVariable::Lithology => self.lithology_passed.as_deref(),
+        }
+    }
 
-                let grade_from_coord = analytic_geometry::interpolate_point_on_the_line(
-                    [x,y,z],
-                    azimuth,
-                    dip,
-                    from
-                );
+    /// Whether the row of the table of the variable is left out of the layers.
+    pub fn hides(&self, variable: &DrillHolesVariable, row: usize) -> bool {
+        self.display == FilterDisplay::Hide
+            && self.passed(variable).map_or(false, |passed| !passed.get(row).copied().unwrap_or(true))
+    }
 
-                let grade_to_coord = analytic_geometry::interpolate_point_on_the_line(
-                    [x,y,z],
-                    azimuth,
-                    dip,
-                    to
-                );
+    /// Evaluates the expression on both tables of the data.
+    /// A table missing a column of the expression is left unfiltered, `error` is only set
+    /// when no table could be filtered.
+    pub fn update(&mut self, data: &DrillHolesData) {
+        self.assay_passed = None;
+        self.lithology_passed = None;
+        self.error = None;
+        let Some(expression) = &self.expression else {
+            return;
+        };
 
-                let prisma_mesh = Self::generate_triangular_prisma(
-                    &grade_from_coord,
-                    &grade_to_coord,
-                    3.0);
+        let mut errors = vec![];
+        let mut skipped = vec![];
+        for (name, table, intervals) in [
+            ("Assay", &data.assay, &data.assay_intervals),
+            ("Lithology", &data.lithology, &data.lithology_intervals),
+        ] {
+            if intervals.is_empty() {
+                continue;
+            }
+            match data.filter_rows(table, intervals, expression) {
+                Ok(Some(passed)) if name == "Assay" => self.assay_passed = Some(passed),
+                Ok(Some(passed)) => self.lithology_passed = Some(passed),
+                Ok(None) => skipped.push(name),
+                Err(error) => errors.push(format!("{} not filtered: {}", name, error)),
+            }
+        }
+        if self.assay_passed.is_none() && self.lithology_passed.is_none() {
+            if errors.is_empty() && !skipped.is_empty() {
+                errors.push(format!("Unknown column in {}: {}", skipped.join(" and "), expression.columns().join(", ")));
+            }
+            if !errors.is_empty() {
+                self.error = Some(errors.join("\n"));
+            }
+        }
+    }
+}
 
+/// Entity of a single hole, parent of the layers drawing it.
+/// The collar is in mine grid coordinates.
+#[derive(Component)]
+pub struct DrillHoleCollar {
+    pub hole: usize,
+    pub hole_id: String,
+    pub collar: [f32;3],
+    pub length: f32,
+}
 
-                let material_au_grade = super::mesh_handlers::color_scale((au-p25_grade_au)/(p75_grade_au-p25_grade_au));
-                let material_cu_grade = super::mesh_handlers::color_scale((cu-p25_grade_cu)/(p75_grade_cu-p25_grade_cu));
+impl DrillHolesMesh {
+    /// Reads the four files and renames their columns with the mappings.
+    pub fn read_tables(&self) -> PolarsResult<[DataFrame;4]> {
+        Ok([
+            self.mappings[0].apply(self.files[0].dataframe()?)?,
+            self.mappings[1].apply(self.files[1].dataframe()?)?,
+            self.mappings[2].apply(self.files[2].dataframe()?)?,
+            self.mappings[3].apply(self.files[3].dataframe()?)?,
+        ])
+    }
 
-                let mut prisma_cu = prisma_mesh.clone();
-                let mut prisma_au = prisma_mesh;
+    /// Reads, validates and desurveys the files. When the report has errors nothing is built,
+    /// unless `skip_bad_records` is set, in which case the rows with errors are left out.
+    pub fn from_csv(drill_holes: DrillHolesMesh, skip_bad_records: bool) -> Result<DrillHolesImport, Box<dyn Error + Send + Sync>>{
+        let [mut df_assay, mut df_header, mut df_lithography, mut df_survey] = drill_holes.read_tables()?;
 
-                prisma_au.insert_attribute(Mesh::ATTRIBUTE_COLOR, vec![material_au_grade; prisma_au.count_vertices()]);
-                prisma_cu.insert_attribute(Mesh::ATTRIBUTE_COLOR, vec![material_cu_grade; prisma_cu.count_vertices()]);
+        let variables = drill_holes.mappings[0].variables.iter()
+            .map(|variable| variable.to_lowercase())
+            .collect::<Vec<_>>();
 
+        let report = ValidationReport::validate(&df_header, &df_survey, &df_assay, &df_lithography, &variables)?;
+        if report.errors() > 0 {
+            if !skip_bad_records {
+                return Ok(DrillHolesImport { data: None, report });
+            }
+            df_assay = drop_rows(&df_assay, &report.bad_rows(DrillTable::Assay))?;
+            df_header = drop_rows(&df_header, &report.bad_rows(DrillTable::Header))?;
+            df_lithography = drop_rows(&df_lithography, &report.bad_rows(DrillTable::Lithology))?;
+            df_survey = drop_rows(&df_survey, &report.bad_rows(DrillTable::Survey))?;
+        }
+        let header = df_header.clone();
+
+        let x_header_colum = df_header.column("x")?.cast(&DataType::Float64)?;
+        let x_header_colum = x_header_colum.sub(drill_holes.offset_x.unwrap_or(0.0) as f64);
+        df_header.with_column(x_header_colum)?;
+
+        let y_header_colum = df_header.column("y")?.cast(&DataType::Float64)?;
+        let y_header_colum = y_header_colum.sub(drill_holes.offset_y.unwrap_or(0.0) as f64);
+        df_header.with_column(y_header_colum)?;
+
+        let z_header_colum = df_header.column("z")?.cast(&DataType::Float64)?;
+        let z_header_colum = z_header_colum.sub(drill_holes.offset_z.unwrap_or(0.0) as f64);
+        df_header.with_column(z_header_colum)?;
+
+        let deepest = Self::deepest_samples(&[&df_assay, &df_lithography])?;
+        let holes = Self::desurvey(&df_header, &df_survey, &deepest, drill_holes.desurvey_method)?;
+
+        let assay_intervals = Self::place_intervals(&holes, &df_assay)?;
+        let lithology_intervals = Self::place_intervals(&holes, &df_lithography)?;
+
+        let data = DrillHolesData {
+            holes,
+            header,
+            assay: df_assay,
+            assay_intervals,
+            lithology: df_lithography,
+            lithology_intervals,
+            variables,
+            offset: [
+                drill_holes.offset_x.unwrap_or(0.0),
+                drill_holes.offset_y.unwrap_or(0.0),
+                drill_holes.offset_z.unwrap_or(0.0),
+            ],
+        };
 
-                au_grades_meshes_result.push(prisma_au);
-                cu_grades_meshes_result.push(prisma_cu);
-                let transform = (grade_from_coord + grade_to_coord)*0.5;
-                transforms_result.push(Transform::from_xyz(transform.x,transform.y,transform.z));
+        Ok(DrillHolesImport { data: Some(data), report })
+    }
 
+    /// Deepest `to` of the from/to tables for every hole.
+    fn deepest_samples(tables: &[&DataFrame]) -> PolarsResult<HashMap<String, f32>> {
+        let mut deepest: HashMap<String, f32> = HashMap::new();
+        for df in tables {
+            let ids = string_column(df, "hole-id")?;
+            let to = f32_column(df, "to")?;
+            for (hole_id, to) in ids.into_iter().zip(to) {
+                if let (Some(hole_id), Some(to)) = (hole_id, to) {
+                    let depth = deepest.entry(hole_id).or_insert(to);
+                    *depth = depth.max(to);
+                }
             }
+        }
+        Ok(deepest)
+    }
 
+    /// Groups the survey by hole and builds one trace per collar.
+    /// Collars without survey rows are treated as vertical holes. Holes whose collar has no
+    /// length are drawn down to their deepest sample of `deepest`, or to their last station.
+    pub fn desurvey(df_header: &DataFrame, df_survey: &DataFrame, deepest: &HashMap<String, f32>, method: DesurveyMethod) -> PolarsResult<Vec<DrillHole>> {
+        let mut stations: HashMap<String, Vec<SurveyStation>> = HashMap::new();
+
+        let survey_ids = string_column(df_survey, "hole-id")?;
+        let survey_from = f32_column(df_survey, "from")?;
+        let survey_azimuth = f32_column(df_survey, "azimuth")?;
+        let survey_dip = f32_column(df_survey, "dip")?;
+
+        for i in 0..df_survey.height() {
+            if let (Some(hole_id), Some(depth), Some(azimuth), Some(dip)) =
+                (&survey_ids[i], survey_from[i], survey_azimuth[i], survey_dip[i]) {
+                stations.entry(hole_id.clone()).or_default().push(SurveyStation { depth, azimuth, dip });
+            }
+        }
 
+        let header_ids = string_column(df_header, "hole-id")?;
+        let x = f32_column(df_header, "x")?;
+        let y = f32_column(df_header, "y")?;
+        let z = f32_column(df_header, "z")?;
+        let length = if df_header.get_column_names().contains(&"length") {
+            f32_column(df_header, "length")?
+        } else {
+            vec![None; df_header.height()]
+        };
 
+        let mut holes = Vec::with_capacity(df_header.height());
+        for i in 0..df_header.height() {
+            let (Some(hole_id), Some(x), Some(y), Some(z)) = (&header_ids[i], x[i], y[i], z[i]) else {
+                continue;
+            };
+            let hole_stations = stations.get(hole_id).map(|s| s.as_slice()).unwrap_or(&[]);
+            let hole_length = length[i].or_else(|| deepest.get(hole_id).copied());
+            let trace = DrillTrace::desurvey([x, y, z], hole_stations, hole_length, method);
+            holes.push(DrillHole { hole_id: hole_id.clone(), collar: [x, y, z], trace });
         }
 
-        let au_final_mesh = super::mesh_handlers::combine_meshes(au_grades_meshes_result,
-                                                                 transforms_result.clone(),
-                                                                 true, false,
-                                                                 false, true);
+        Ok(holes)
+    }
 
-        let cu_final_mesh = super::mesh_handlers::combine_meshes(cu_grades_meshes_result,
-                                                                 transforms_result,
-                                                                 true, false,
-                                                                 false, true);
+    /// Matches every from/to row of `df` with its hole, rows of unknown holes are left out.
+    fn place_intervals(holes: &[DrillHole], df: &DataFrame) -> PolarsResult<Vec<DrillInterval>> {
+        let hole_index = holes.iter().enumerate()
+            .map(|(i, hole)| (hole.hole_id.as_str(), i))
+            .collect::<HashMap<_, _>>();
+
+        let ids = string_column(df, "hole-id")?;
+        let from = f32_column(df, "from")?;
+        let to = f32_column(df, "to")?;
+
+        Ok((0..df.height())
+            .filter_map(|row| {
+                let hole = *hole_index.get(ids[row].as_deref()?)?;
+                Some(DrillInterval { hole, row, from: from[row]?, to: to[row]? })
+            })
+            .collect())
+    }
 
-        //TODO
-        vec![au_final_mesh, cu_final_mesh]
+    /// One prism per trace segment of the interval, already oriented along the hole.
+    fn interval_prismas(trace: &DrillTrace, from: f32, to: f32, radius: f32) -> Vec<(Mesh, Transform)> {
+        trace.segment(from, to)
+            .windows(2)
+            .filter(|pair| pair[0].distance(pair[1]) > f32::EPSILON)
+            .map(|pair| {
+                let mesh = Self::generate_triangular_prisma(&pair[0], &pair[1], radius);
+                let middle = (pair[0] + pair[1]) * 0.5;
+                let rotation = Quat::from_rotation_arc(Vec3::Y, (pair[1] - pair[0]).normalize());
+                (mesh, Transform::from_translation(middle).with_rotation(rotation))
+            })
+            .collect()
     }
 
     fn generate_triangular_prisma(
@@ -175,3 +404,606 @@ impl DrillHolesMesh {
 
 
 }
+
+impl DrillHolesData {
+    /// Distinct rock codes of the lithology table.
+    pub fn rock_codes(&self) -> Vec<String> {
+        let mut codes = string_column(&self.lithology, "rock").unwrap_or_default().into_iter().flatten().collect::<Vec<_>>();
+        codes.sort();
+        codes.dedup();
+        codes
+    }
+
+    /// Values of an assay column, one per dataframe row.
+    pub fn values(&self, variable: &str) -> Vec<Option<f32>> {
+        f32_column(&self.assay, variable).unwrap_or_default()
+    }
+
+    pub fn intervals(&self, variable: &DrillHolesVariable) -> &[DrillInterval] {
+        match variable {
+            DrillHolesVariable::Assay(_) => &self.assay_intervals,
+            DrillHolesVariable::Lithology => &self.lithology_intervals,
+        }
+    }
+
+    /// Ramp between the P25 and P75 of the variable, used until the user edits its legend.
+    pub fn default_legend(&self, variable: &str) -> ColorLegend {
+        let present = self.values(variable).into_iter().flatten().collect::<Vec<_>>();
+        ColorLegend::from_values(&present)
+    }
+
+    /// Colour of every row of the table the variable belongs to.
+    pub fn colors(&self, variable: &DrillHolesVariable, legend: &LithologyLegend, grades: &GradeLegends) -> Vec<[f32;4]> {
+        match variable {
+            DrillHolesVariable::Assay(variable) => {
+                let default_legend;
+                let grade_legend = match grades.legends.get(variable) {
+                    Some(grade_legend) => grade_legend,
+                    None => {
+                        default_legend = self.default_legend(variable);
+                        &default_legend
+                    }
+                };
+
+                self.values(variable).iter()
+                    .map(|value| match value {
+                        Some(value) => grade_legend.color(*value),
+                        None => UNKNOWN_COLOR,
+                    })
+                    .collect()
+            }
+            DrillHolesVariable::Lithology => {
+                string_column(&self.lithology, "rock").unwrap_or_default().iter()
+                    .map(|code| match code {
+                        Some(code) => legend.get(code),
+                        None => UNKNOWN_COLOR,
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Elevation of the surface under every collar. The surface must be in the same
+    /// coordinates as the holes, as the topography the holes are linked to.
+    pub fn collar_deltas(&self, surface: &SurfaceGrid) -> Vec<CollarDelta> {
+        self.holes.iter().enumerate()
+            .map(|(i, hole)| CollarDelta {
+                hole: i,
+                hole_id: hole.hole_id.clone(),
+                collar_z: hole.collar[2] + self.offset[2],
+                surface_z: surface.elevation(hole.collar[0], hole.collar[1]).map(|z| z + self.offset[2]),
+            })
+            .collect()
+    }
+
+    /// Moves the collars of the deltas, with their traces, onto the surface. Collars outside of it are left as they are.
+    /// The `z` of the collar table is updated too, so the exports and the attributes show the new elevation.
+    pub fn snap_collars(&mut self, deltas: &[CollarDelta]) -> PolarsResult<()> {
+        let mut snapped: HashMap<&str, f32> = HashMap::new();
+        for delta in deltas {
+            let Some(dz) = delta.delta() else {
+                continue;
+            };
+            let hole = &mut self.holes[delta.hole];
+            hole.collar[2] -= dz;
+            hole.trace.translate(Vec3::new(0.0, -dz, 0.0));
+            snapped.insert(&delta.hole_id, dz);
+        }
+        if snapped.is_empty() {
+            return Ok(());
+        }
+
+        let header_ids = string_column(&self.header, "hole-id")?;
+        let z = self.header.column("z")?.cast(&DataType::Float64)?;
+        let z = z.f64()?.into_iter().zip(header_ids.iter())
+            .map(|(z, hole_id)| match hole_id.as_deref().and_then(|hole_id| snapped.get(hole_id)) {
+                Some(dz) => z.map(|z| z - *dz as f64),
+                None => z,
+            })
+            .collect::<Float64Chunked>();
+        self.header.with_column(z.with_name("z").into_series())?;
+        Ok(())
+    }
+
+    /// Table the intervals of the variable were read from.
+    pub fn table(&self, variable: &DrillHolesVariable) -> &DataFrame {
+        match variable {
+            DrillHolesVariable::Assay(_) => &self.assay,
+            DrillHolesVariable::Lithology => &self.lithology,
+        }
+    }
+
+    /// Rows and intervals of an interval table, `None` for the collar and survey tables.
+    pub fn interval_rows(&self, table: DrillTable) -> Option<(&DataFrame, &[DrillInterval])> {
+        match table {
+            DrillTable::Assay => Some((&self.assay, &self.assay_intervals)),
+            DrillTable::Lithology => Some((&self.lithology, &self.lithology_intervals)),
+            DrillTable::Header | DrillTable::Survey => None,
+        }
+    }
+
+    /// Mine grid coordinates `[x, y, z]` of a point of a trace.
+    pub fn real_coordinates(&self, point: Vec3) -> [f32;3] {
+        [point.x + self.offset[0], point.z + self.offset[1], point.y + self.offset[2]]
+    }
+
+    /// Rock code of the lithology interval of the hole at the given depth.
+    pub fn rock_at(&self, hole: usize, depth: f32) -> Option<String> {
+        let interval = self.lithology_intervals.iter()
+            .find(|interval| interval.hole == hole && interval.from <= depth && depth < interval.to)?;
+        string_column(&self.lithology, "rock").ok()?.get(interval.row)?.clone()
+    }
+
+    /// Every column of the interval row, plus the rock at its middle and the coordinates of its ends.
+    pub fn interval_attributes(&self, variable: &DrillHolesVariable, interval: &DrillInterval) -> Vec<(String, String)> {
+        let hole = &self.holes[interval.hole];
+        let mut attributes = vec![
+            ("hole-id".to_string(), hole.hole_id.clone()),
+            ("from".to_string(), format!("{:.2}", interval.from)),
+            ("to".to_string(), format!("{:.2}", interval.to)),
+        ];
+
+        let table = self.table(variable);
+        for name in table.get_column_names() {
+            if ["hole-id", "from", "to"].contains(&name) {
+                continue;
+            }
+            let value = string_column(table, name).ok()
+                .and_then(|column| column.get(interval.row).cloned().flatten())
+                .unwrap_or_default();
+            attributes.push((name.to_string(), value));
+        }
+
+        if !table.get_column_names().contains(&"rock") {
+            if let Some(rock) = self.rock_at(interval.hole, (interval.from + interval.to) * 0.5) {
+                attributes.push(("rock".to_string(), rock));
+            }
+        }
+
+        for (name, depth) in [("from xyz", interval.from), ("to xyz", interval.to)] {
+            let [x, y, z] = self.real_coordinates(hole.trace.point_at(depth));
+            attributes.push((name.to_string(), format!("{:.2}, {:.2}, {:.2}", x, y, z)));
+        }
+
+        attributes
+    }
+
+    /// Rock code at the middle of each interval, by row of a table with `rows` rows.
+    pub fn middle_rocks(&self, intervals: &[DrillInterval], rows: usize) -> PolarsResult<Vec<Option<String>>> {
+        let codes = string_column(&self.lithology, "rock")?;
+        let mut contacts = vec![vec![]; self.holes.len()];
+        for contact in self.lithology_intervals.iter() {
+            contacts[contact.hole].push(contact);
+        }
+
+        let mut rocks = vec![None; rows];
+        for interval in intervals {
+            let middle = (interval.from + interval.to) * 0.5;
+            rocks[interval.row] = contacts[interval.hole].iter()
+                .find(|contact| contact.from <= middle && middle < contact.to)
+                .and_then(|contact| codes[contact.row].clone());
+        }
+        Ok(rocks)
+    }
+
+    /// Whether each row of `table` passes the expression. Besides the columns of the table, it can read
+    /// the columns of the collar table and the rock at the middle of each interval.
+    /// `None` when the table has no column for some name of the expression.
+    pub fn filter_rows(
+        &self,
+        table: &DataFrame,
+        intervals: &[DrillInterval],
+        expression: &FilterExpression,
+    ) -> Result<Option<Vec<bool>>, Box<dyn Error + Send + Sync>> {
+        let mut frame = table.clone();
+        let ids = string_column(table, "hole-id")?;
+        let header_ids = string_column(&self.header, "hole-id")?;
+        let header_rows = header_ids.iter().enumerate()
+            .filter_map(|(row, id)| Some((id.as_deref()?, row)))
+            .collect::<HashMap<_, _>>();
+
+        for name in expression.columns() {
+            if frame.get_column_names().iter().any(|column| column.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+
+            if let Some(column) = self.header.get_column_names().into_iter().find(|column| column.eq_ignore_ascii_case(name)) {
+                let values = string_column(&self.header, column)?;
+                let joined = ids.iter()
+                    .map(|id| header_rows.get(id.as_deref()?).and_then(|row| values[*row].clone()))
+                    .collect::<Vec<_>>();
+                frame.with_column(Series::new(column, joined))?;
+            } else if name.eq_ignore_ascii_case("rock") && !self.lithology_intervals.is_empty() {
+                frame.with_column(Series::new("rock", self.middle_rocks(intervals, frame.height())?))?;
+            } else {
+                return Ok(None);
+            }
+        }
+
+        expression.evaluate(&frame).map(Some)
+    }
+
+    /// Composites the assay intervals of every hole. The result has the same holes and
+    /// variables, its assay table has one row per composite with its length and coverage,
+    /// plus the rock code when compositing by lithology.
+    pub fn composite(&self, settings: &CompositeSettings) -> PolarsResult<DrillHolesData> {
+        let values = self.variables.iter().map(|variable| self.values(variable)).collect::<Vec<_>>();
+        let rocks = string_column(&self.lithology, "rock").unwrap_or_default();
+
+        let mut samples = vec![vec![]; self.holes.len()];
+        for interval in self.assay_intervals.iter() {
+            samples[interval.hole].push(*interval);
+        }
+        let mut contacts = vec![vec![]; self.holes.len()];
+        for interval in self.lithology_intervals.iter() {
+            if let Some(Some(rock)) = rocks.get(interval.row) {
+                contacts[interval.hole].push((interval.from, interval.to, rock.clone()));
+            }
+        }
+
+        let mut hole_ids = vec![];
+        let mut from = vec![];
+        let mut to = vec![];
+        let mut length = vec![];
+        let mut coverage = vec![];
+        let mut rock = vec![];
+        let mut composite_values = vec![vec![]; self.variables.len()];
+        let mut intervals = vec![];
+
+        for (hole, hole_samples) in samples.iter_mut().enumerate() {
+            if hole_samples.is_empty() {
+                continue;
+            }
+            hole_samples.sort_by(|a, b| a.from.total_cmp(&b.from));
+            let start = hole_samples[0].from;
+            let end = hole_samples.iter().map(|sample| sample.to).fold(start, f32::max);
+
+            let runs = match settings.method {
+                CompositeMethod::FixedLength => compositing::fixed_length_runs(start, end, settings.length, settings.residual)
+                    .into_iter()
+                    .map(|(from, to)| (from, to, None))
+                    .collect::<Vec<_>>(),
+                CompositeMethod::Bench => {
+                    // Bench floors are given in real elevations
+                    let origin = settings.bench_origin - self.offset[2];
+                    compositing::bench_runs(&self.holes[hole].trace, start, end, settings.bench_height, origin)
+                        .into_iter()
+                        .map(|(from, to)| (from, to, None))
+                        .collect()
+                }
+                CompositeMethod::Lithology => lithology_domains(&mut contacts[hole]).into_iter()
+                    .flat_map(|(from, to, rock)| {
+                        compositing::fixed_length_runs(from.max(start), to.min(end), settings.length, settings.residual)
+                            .into_iter()
+                            .map(move |(from, to)| (from, to, Some(rock.clone())))
+                    })
+                    .collect(),
+            };
+
+            let extents = hole_samples.iter().map(|sample| (sample.from, sample.to)).collect::<Vec<_>>();
+            for (run_from, run_to, run_rock) in runs {
+                let sampled = (compositing::coverage(&extents, run_from, run_to) / (run_to - run_from)).min(1.0);
+                if sampled < settings.min_coverage {
+                    continue;
+                }
+
+                for (variable_values, composited) in values.iter().zip(composite_values.iter_mut()) {
+                    let variable_samples = hole_samples.iter()
+                        .map(|sample| (sample.from, sample.to, variable_values.get(sample.row).copied().flatten()))
+                        .collect::<Vec<_>>();
+                    composited.push(compositing::weighted_average(&variable_samples, run_from, run_to, settings.min_coverage));
+                }
+
+                intervals.push(DrillInterval { hole, row: from.len(), from: run_from, to: run_to });
+                hole_ids.push(self.holes[hole].hole_id.clone());
+                from.push(run_from);
+                to.push(run_to);
+                length.push(run_to - run_from);
+                coverage.push(sampled);
+                rock.push(run_rock);
+            }
+        }
+
+        let mut columns = vec![
+            Series::new("hole-id", hole_ids),
+            Series::new("from", from),
+            Series::new("to", to),
+            Series::new("length", length),
+            Series::new("coverage", coverage),
+        ];
+        if settings.method == CompositeMethod::Lithology {
+            columns.push(Series::new("rock", rock));
+        }
+        for (variable, values) in self.variables.iter().zip(composite_values) {
+            columns.push(Series::new(variable, values));
+        }
+        let assay = DataFrame::new(columns)?;
+
+        let (lithology, lithology_intervals) = if settings.method == CompositeMethod::Lithology {
+            (assay.clone(), intervals.clone())
+        } else {
+            (DataFrame::default(), vec![])
+        };
+
+        Ok(DrillHolesData {
+            holes: self.holes.clone(),
+            header: self.header.clone(),
+            assay,
+            assay_intervals: intervals,
+            lithology,
+            lithology_intervals,
+            variables: self.variables.clone(),
+            offset: self.offset,
+        })
+    }
+
+    /// Significant intercepts of the assay variable in every hole, in hole order.
+    pub fn significant_intercepts(&self, variable: &str, settings: &InterceptSettings) -> Vec<HoleIntercept> {
+        let values = self.values(variable);
+        let mut samples = vec![vec![]; self.holes.len()];
+        for interval in self.assay_intervals.iter() {
+            samples[interval.hole].push((interval.from, interval.to, values.get(interval.row).copied().flatten()));
+        }
+
+        samples.iter().enumerate()
+            .flat_map(|(hole, hole_samples)| {
+                intercepts::significant_intercepts(hole_samples, settings).into_iter()
+                    .map(move |intercept| HoleIntercept { hole, hole_id: self.holes[hole].hole_id.clone(), intercept })
+            })
+            .collect()
+    }
+
+    /// Mesh of downhole runs `(hole, from, to, radius, color)` drawn over the layers.
+    pub fn runs_mesh(&self, runs: &[(usize, f32, f32, f32, [f32;4])]) -> Mesh {
+        let mut meshes_result: Vec<Mesh> = Vec::new();
+        let mut transforms_result: Vec<Transform> = Vec::new();
+
+        for (hole, from, to, radius, color) in runs {
+            let trace = &self.holes[*hole].trace;
+            for (mut prisma, transform) in DrillHolesMesh::interval_prismas(trace, *from, *to, *radius) {
+                prisma.insert_attribute(Mesh::ATTRIBUTE_COLOR, vec![*color; prisma.count_vertices()]);
+                meshes_result.push(prisma);
+                transforms_result.push(transform);
+            }
+        }
+
+        super::mesh_handlers::combine_meshes(meshes_result,
+                                             transforms_result,
+                                             true, false,
+                                             false, true)
+    }
+
+    /// Indices in [`Self::intervals`] of the intervals of every hole.
+    pub fn intervals_by_hole(&self, variable: &DrillHolesVariable) -> Vec<Vec<usize>> {
+        let mut by_hole = vec![vec![]; self.holes.len()];
+        for (i, interval) in self.intervals(variable).iter().enumerate() {
+            by_hole[interval.hole].push(i);
+        }
+        by_hole
+    }
+
+    /// Mesh of the layer, `colors` are the ones of [`Self::colors`] and `by_hole` the groups of
+    /// [`Self::intervals_by_hole`] for the layer variable. Intervals hidden by the filter are left out.
+    pub fn layer_mesh(&self, layer: &DrillHolesLayer, colors: &[[f32;4]], by_hole: &[Vec<usize>], filter: Option<&DrillHolesFilter>) -> Mesh {
+        let mut meshes_result: Vec<Mesh> = Vec::new();
+        let mut transforms_result: Vec<Transform> = Vec::new();
+
+        let all = self.intervals(&layer.variable);
+        let intervals = match layer.hole {
+            Some(hole) => by_hole.get(hole).map_or(vec![], |group| group.iter().map(|i| &all[*i]).collect()),
+            None => all.iter().collect::<Vec<_>>(),
+        };
+        let intervals = intervals.into_iter()
+            .filter(|interval| !filter.map_or(false, |filter| filter.hides(&layer.variable, interval.row)));
+        for interval in intervals {
+            let trace = &self.holes[interval.hole].trace;
+            for (mut prisma, transform) in DrillHolesMesh::interval_prismas(trace, interval.from, interval.to, layer.radius) {
+                let color = colors.get(interval.row).copied().unwrap_or(UNKNOWN_COLOR);
+                prisma.insert_attribute(Mesh::ATTRIBUTE_COLOR, vec![color; prisma.count_vertices()]);
+                meshes_result.push(prisma);
+                transforms_result.push(transform);
+            }
+        }
+
+        super::mesh_handlers::combine_meshes(meshes_result,
+                                             transforms_result,
+                                             true, false,
+                                             false, true)
+    }
+}
+
+/// Sorts the lithology intervals of a hole and merges the touching ones with the same rock.
+fn lithology_domains(contacts: &mut [(f32, f32, String)]) -> Vec<(f32, f32, String)> {
+    contacts.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut domains: Vec<(f32, f32, String)> = vec![];
+    for (from, to, rock) in contacts.iter() {
+        match domains.last_mut() {
+            Some(last) if last.2 == *rock && *from <= last.1 + 1e-3 => last.1 = last.1.max(*to),
+            _ => domains.push((*from, *to, rock.clone())),
+        }
+    }
+    domains
+}
+
+/// Spawns the data entity with its layers, the layer meshes are built by
+/// `update_drill_holes_layers` on the next frame. Returns the data entity.
+///
+/// With `per_hole` every hole gets its own entity, with one layer per variable logged in the hole,
+/// so that holes can be hidden, focused or deleted one by one. Otherwise each variable is a
+/// single layer merging every hole, which is much lighter for large campaigns.
+pub fn spawn_drill_holes(world: &mut World, data: DrillHolesData, name: &str, per_hole: bool) -> Entity {
+    let mut variables = data.variables.iter()
+        .map(|variable| DrillHolesVariable::Assay(variable.clone()))
+        .collect::<Vec<_>>();
+    if !data.lithology_intervals.is_empty(){
+        variables.push(DrillHolesVariable::Lithology);
+    }
+
+    let holes = if per_hole {
+        let groups = variables.iter().map(|variable| data.intervals_by_hole(variable)).collect::<Vec<_>>();
+        data.holes.iter().enumerate()
+            .map(|(i, hole)| {
+                let points = hole.trace.segment(0.0, hole.trace.length());
+                let min = points.iter().fold(Vec3::splat(f32::MAX), |min, point| min.min(*point));
+                let max = points.iter().fold(Vec3::splat(f32::MIN), |max, point| max.max(*point));
+                let collar = DrillHoleCollar {
+                    hole: i,
+                    hole_id: hole.hole_id.clone(),
+                    collar: data.real_coordinates(hole.trace.collar()),
+                    length: hole.trace.length(),
+                };
+                let logged = variables.iter().zip(groups.iter())
+                    .filter(|(_, by_hole)| !by_hole[i].is_empty())
+                    .map(|(variable, _)| variable.clone())
+                    .collect::<Vec<_>>();
+                (collar, Aabb::from_min_max(min, max), logged)
+            })
+            .collect::<Vec<_>>()
+    } else {
+        vec![]
+    };
+
+    let drill_holes_id = world.spawn((
+        SpatialBundle::default(),
+        data,
+        DrillHolesFilter::default(),
+        Name::new(name.to_string()),
+    )).id();
+
+    if !per_hole {
+        for variable in variables{
+            let layer_id = spawn_layer(world, variable, None);
+            world.entity_mut(drill_holes_id).add_child(layer_id);
+        }
+        return drill_holes_id;
+    }
+
+    for (collar, bounds, logged) in holes {
+        let hole = collar.hole;
+        let hole_id = world.spawn((SpatialBundle::default(), Name::new(collar.hole_id.clone()), collar, bounds)).id();
+        world.entity_mut(drill_holes_id).add_child(hole_id);
+
+        for variable in logged {
+            let layer_id = spawn_layer(world, variable, Some(hole));
+            world.entity_mut(hole_id).add_child(layer_id);
+        }
+    }
+
+    drill_holes_id
+}
+
+fn spawn_layer(world: &mut World, variable: DrillHolesVariable, hole: Option<usize>) -> Entity {
+    let mut meshes = world.get_resource_mut::<Assets<Mesh>>().unwrap();
+    let mesh = meshes.add(Mesh::new(PrimitiveTopology::TriangleList));
+
+    let mut materials = world
+        .get_resource_mut::<Assets<StandardMaterial>>()
+        .unwrap();
+    let material = materials.add(
+        StandardMaterial::default()
+    );
+
+    let radius = match variable {
+        DrillHolesVariable::Assay(_) => 3.0,
+        DrillHolesVariable::Lithology => 4.0,
+    };
+
+    world.spawn((PbrBundle {
+        mesh,
+        material,
+        ..Default::default()
+    },
+                 Name::new(variable.name()),
+                 DrillHolesLayer { variable, radius, hole }
+    )).id()
+}
+
+/// Closest ancestor of `entity` holding the [`DrillHolesData`] it draws.
+pub fn drill_holes_root(world: &World, entity: Entity) -> Option<Entity> {
+    let mut ancestor = world.get::<Parent>(entity)?.get();
+    while world.get::<DrillHolesData>(ancestor).is_none() {
+        ancestor = world.get::<Parent>(ancestor)?.get();
+    }
+    Some(ancestor)
+}
+
+/// Rebuilds the mesh of every layer whose variable, data, filter or legends changed.
+pub fn update_drill_holes_layers(
+    mut commands: Commands,
+    legend: Res<LithologyLegend>,
+    mut grades: ResMut<GradeLegends>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    data: Query<Ref<DrillHolesData>>,
+    mut filters: Query<&mut DrillHolesFilter>,
+    parents: Query<&Parent>,
+    layers: Query<(Entity, Ref<DrillHolesLayer>, &Handle<Mesh>)>,
+) {
+    // Per hole layers share the colours of their variable, and its intervals grouped by hole
+    let mut colors: HashMap<(Entity, String), Vec<[f32;4]>> = HashMap::new();
+    let mut groups: HashMap<(Entity, String), Vec<Vec<usize>>> = HashMap::new();
+    let mut filtered: Vec<Entity> = vec![];
+
+    for (entity, layer, handle) in layers.iter() {
+        let mut ancestor = entity;
+        let (root, data) = loop {
+            let Ok(parent) = parents.get(ancestor) else {
+                break (ancestor, None);
+            };
+            ancestor = parent.get();
+            if let Ok(data) = data.get(ancestor) {
+                break (ancestor, Some(data));
+            }
+        };
+        let Some(data) = data else {
+            continue;
+        };
+        let mut filter = filters.get_mut(root).ok();
+        let filter_changed = filter.as_ref().map_or(false, |filter| filter.is_changed());
+        if !(layer.is_changed() || data.is_changed() || filter_changed || legend.is_changed() || grades.is_changed()) {
+            continue;
+        }
+
+        if let Some(filter) = filter.as_mut() {
+            if (filter_changed || data.is_changed()) && !filtered.contains(&root) {
+                filter.bypass_change_detection().update(&data);
+                filtered.push(root);
+            }
+        }
+        let filter = filter.as_deref();
+
+        if let DrillHolesVariable::Assay(variable) = &layer.variable {
+            if !grades.legends.contains_key(variable) {
+                let grade_legend = data.default_legend(variable);
+                grades.bypass_change_detection().legends.insert(variable.clone(), grade_legend);
+            }
+        }
+
+        let layer_colors = colors.entry((root, layer.variable.name()))
+            .or_insert_with(|| {
+                let mut layer_colors = data.colors(&layer.variable, &legend, &grades);
+                let greyed = filter
+                    .filter(|filter| filter.display == FilterDisplay::Grey)
+                    .and_then(|filter| filter.passed(&layer.variable));
+                if let Some(passed) = greyed {
+                    for (color, passed) in layer_colors.iter_mut().zip(passed) {
+                        if !passed {
+                            *color = FILTERED_COLOR;
+                        }
+                    }
+                }
+                layer_colors
+            });
+        let by_hole = match layer.hole {
+            Some(_) => groups.entry((root, layer.variable.name()))
+                .or_insert_with(|| data.intervals_by_hole(&layer.variable))
+                .as_slice(),
+            None => &[],
+        };
+        if let Some(mesh) = meshes.get_mut(handle) {
+            *mesh = data.layer_mesh(&layer, layer_colors, by_hole, filter);
+            // Bounds are computed again from the new mesh
+            commands.entity(entity).remove::<Aabb>();
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::error::Error;
+
+use bevy::prelude::*;
+use csv::{ReaderBuilder, WriterBuilder};
+use indexmap::IndexMap;
+
+pub const DEFAULT_LEGEND_FILENAME: &str = "lithology_legend.csv";
+
+const UNKNOWN_COLOR: [f32; 3] = [0.5, 0.5, 0.5];
+
+/// Colours handed out to rock codes that are not in the legend yet.
+const PALETTE: [[f32; 3]; 12] = [
+    [0.894, 0.102, 0.110],
+    [0.216, 0.494, 0.722],
+    [0.302, 0.686, 0.290],
+    [0.596, 0.306, 0.639],
+    [1.000, 0.498, 0.000],
+    [1.000, 1.000, 0.200],
+    [0.651, 0.337, 0.157],
+    [0.969, 0.506, 0.749],
+    [0.400, 0.761, 0.647],
+    [0.988, 0.553, 0.384],
+    [0.553, 0.627, 0.796],
+    [0.906, 0.541, 0.765],
+];
+
+/// Rock code -> colour used to draw the lithology drill-hole layer.
+#[derive(Resource, Default)]
+pub struct LithologyLegend {
+    pub colors: IndexMap<String, [f32; 3]>,
+}
+
+impl LithologyLegend {
+    /// Colour of the rock code, adding it to the legend if it is new.
+    pub fn color(&mut self, code: &str) -> [f32; 4] {
+        let next = PALETTE[self.colors.len() % PALETTE.len()];
+        let color = self.colors.entry(code.to_string()).or_insert(next);
+        [color[0], color[1], color[2], 1.0]
+    }
+
+    pub fn get(&self, code: &str) -> [f32; 4] {
+        let color = self.colors.get(code).unwrap_or(&UNKNOWN_COLOR);
+        [color[0], color[1], color[2], 1.0]
+    }
+
+    /// Reads a `code,r,g,b` csv file, components between 0 and 1.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut reader = ReaderBuilder::new().has_headers(true).from_path(path)?;
+        let mut colors = IndexMap::new();
+
+        for result in reader.records() {
+            let record = result?;
+            let color = [
+                record[1].parse::<f32>()?,
+                record[2].parse::<f32>()?,
+                record[3].parse::<f32>()?,
+            ];
+            colors.insert(record[0].to_string(), color);
+        }
+
+        Ok(Self { colors })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut writer = WriterBuilder::new().from_path(path)?;
+        writer.write_record(["code", "r", "g", "b"])?;
+        for (code, color) in self.colors.iter() {
+            writer.write_record([
+                code.clone(),
+                color[0].to_string(),
+                color[1].to_string(),
+                color[2].to_string(),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
@@ -1,4 +1,10 @@
 
 pub mod topography_mesh;
 pub mod drill_holes_mesh;
-pub mod mesh_handlers;
\ No newline at end of file
+pub mod mesh_handlers;
+pub mod lithology_legend;
+pub mod color_legend;
+pub mod downhole_log_mesh;
+pub mod planned_holes_mesh;
+pub mod contour_mesh;
+pub mod topography_lod;
@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+use bevy::render::mesh::PrimitiveTopology;
+use bevy::render::primitives::Aabb;
+use polars::prelude::DataFrame;
+
+use crate::custom_meshes::drill_holes_mesh::{DrillHole, DrillHolesData};
+use crate::math::desurvey::{DesurveyMethod, DrillTrace, SurveyStation};
+
+/// Colour of the planned traces, distinct from the grade and lithology layers.
+pub const PLANNED_COLOR: [f32; 4] = [0.1, 0.8, 0.9, 1.0];
+
+/// A hole being designed, straight from the collar. The collar is `[x, y, z]` in mine grid
+/// axes, relative to the offsets of the [`DrillHolesData`] it belongs to, as [`DrillHole::collar`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct PlannedHole {
+    pub hole_id: String,
+    pub collar: [f32; 3],
+    pub azimuth: f32,
+    /// Negative downwards, as in the survey tables
+    pub dip: f32,
+    pub length: f32,
+}
+
+impl PlannedHole {
+    pub fn new(hole_id: String, collar: [f32; 3]) -> Self {
+        Self { hole_id, collar, azimuth: 0.0, dip: -60.0, length: 200.0 }
+    }
+
+    pub fn trace(&self) -> DrillTrace {
+        let station = SurveyStation { depth: 0.0, azimuth: self.azimuth, dip: self.dip };
+        DrillTrace::desurvey(self.collar, &[station], Some(self.length), DesurveyMethod::MinimumCurvature)
+    }
+}
+
+/// Holes designed on a topography. `update_planned_holes` keeps the [`DrillHolesData`] of the
+/// entity in sync, so labels and exports treat them as any loaded drill holes.
+#[derive(Component)]
+pub struct PlannedHoles {
+    pub holes: Vec<PlannedHole>,
+    /// Radius of the drawn traces
+    pub radius: f32,
+}
+
+/// Mesh of the traces of the [`PlannedHoles`] of the parent entity.
+#[derive(Component)]
+pub struct PlannedHolesTrace;
+
+/// Spawns an empty design under the topography, using its offsets. Returns the data entity.
+pub fn spawn_planned_holes(world: &mut World, topography: Entity, offset: [f32; 3], name: &str) -> Entity {
+    let data = DrillHolesData {
+        holes: vec![],
+        header: DataFrame::default(),
+        assay: DataFrame::default(),
+        assay_intervals: vec![],
+        lithology: DataFrame::default(),
+        lithology_intervals: vec![],
+        variables: vec![],
+        offset,
+    };
+
+    let planned_id = world.spawn((
+        SpatialBundle::default(),
+        data,
+        PlannedHoles { holes: vec![], radius: 2.0 },
+        Name::new(name.to_string()),
+    )).id();
+
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(Mesh::new(PrimitiveTopology::TriangleList));
+    let material = world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial::default());
+    let trace_id = world.spawn((
+        PbrBundle {
+            mesh,
+            material,
+            ..Default::default()
+        },
+        Name::new("Traces"),
+        PlannedHolesTrace,
+    )).id();
+
+    world.entity_mut(planned_id).add_child(trace_id);
+    world.entity_mut(topography).add_child(planned_id);
+    planned_id
+}
+
+/// Desurveys the planned holes again and rebuilds their traces whenever the design changes.
+pub fn update_planned_holes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut designs: Query<(Ref<PlannedHoles>, &mut DrillHolesData, &Children)>,
+    traces: Query<(Entity, &Handle<Mesh>), With<PlannedHolesTrace>>,
+) {
+    for (planned, mut data, children) in designs.iter_mut() {
+        if !planned.is_changed() {
+            continue;
+        }
+
+        data.holes = planned.holes.iter()
+            .map(|hole| DrillHole {
+                hole_id: hole.hole_id.clone(),
+                collar: hole.collar,
+                trace: hole.trace(),
+            })
+            .collect();
+
+        let runs = data.holes.iter().enumerate()
+            .map(|(i, hole)| (i, 0.0, hole.trace.length(), planned.radius, PLANNED_COLOR))
+            .collect::<Vec<_>>();
+        for (entity, handle) in traces.iter_many(children) {
+            if let Some(mesh) = meshes.get_mut(handle) {
+                *mesh = data.runs_mesh(&runs);
+                commands.entity(entity).remove::<Aabb>();
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues};
+use bevy::render::view::RenderLayers;
+
+use crate::custom_meshes::topography_mesh::TopographyMesh;
+use crate::math::decimation::{decimate, DecimationTarget};
+use crate::ui_windows::cameras::ActiveEditorCamera;
+
+pub const DEFAULT_TILE_SIZE: f32 = 250.0;
+/// Layer no camera renders, the whole topography mesh is moved there while its tiles are shown.
+const HIDDEN_LAYER: u8 = RenderLayers::TOTAL_LAYERS as u8 - 1;
+/// Levels of every tile, each one with a quarter of the triangles of the one before.
+const LOD_LEVELS: usize = 3;
+/// Tiles are not simplified below this many triangles.
+const MIN_TILE_TRIANGLES: usize = 32;
+
+/// Tiled level of detail of the topography the entity is a child of, its children being the
+/// [`LodTile`]s. The topography mesh is kept whole for the tools and hidden from the cameras.
+#[derive(Component)]
+pub struct TopographyLod {
+    pub tile_size: f32,
+    /// Camera distance where the tiles switch to the first simplified level, every next level
+    /// starting twice as far
+    pub switch_distance: f32,
+}
+
+impl TopographyLod {
+    /// Camera distances the level is shown between.
+    fn range(&self, level: usize, last: bool) -> (f32, f32) {
+        let min = if level == 0 { 0.0 } else { self.switch_distance * (1 << (level - 1)) as f32 };
+        let max = if last { f32::INFINITY } else { self.switch_distance * (1 << level) as f32 };
+        (min, max)
+    }
+}
+
+/// A level of a tile of the topography, made of some of its vertices so their attributes,
+/// as the shading colours, can be copied over when the topography mesh changes.
+#[derive(Component)]
+pub struct LodTile {
+    pub level: usize,
+    /// Whether it is the simplest level of its tile, shown at any distance past its start
+    pub last: bool,
+    /// Centre of the tile, in the space of the topography
+    pub center: Vec3,
+    /// Vertices of the topography mesh, in the order of the tile mesh
+    pub vertices: Vec<u32>,
+    pub indices: Vec<u32>,
+}
+
+impl LodTile {
+    fn new(level: usize, center: Vec3, triangles: &[[usize; 3]]) -> Self {
+        let mut local: HashMap<usize, u32> = HashMap::new();
+        let mut vertices = vec![];
+        let indices = triangles.iter()
+            .flatten()
+            .map(|vertex| *local.entry(*vertex).or_insert_with(|| {
+                vertices.push(*vertex as u32);
+                vertices.len() as u32 - 1
+            }))
+            .collect();
+        Self { level, last: false, center, vertices, indices }
+    }
+
+    /// Mesh of the tile with the position, normal, UV and colour of the topography vertices.
+    pub fn mesh(&self, topography: &Mesh) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        for attribute in [Mesh::ATTRIBUTE_POSITION, Mesh::ATTRIBUTE_NORMAL, Mesh::ATTRIBUTE_UV_0, Mesh::ATTRIBUTE_COLOR] {
+            let values = match topography.attribute(attribute.id) {
+                Some(VertexAttributeValues::Float32x2(values)) => VertexAttributeValues::Float32x2(self.gather(values)),
+                Some(VertexAttributeValues::Float32x3(values)) => VertexAttributeValues::Float32x3(self.gather(values)),
+                Some(VertexAttributeValues::Float32x4(values)) => VertexAttributeValues::Float32x4(self.gather(values)),
+                _ => continue,
+            };
+            mesh.insert_attribute(attribute, values);
+        }
+        mesh.set_indices(Some(Indices::U32(self.indices.clone())));
+        mesh
+    }
+
+    fn gather<T: Copy>(&self, values: &[T]) -> Vec<T> {
+        self.vertices.iter().map(|vertex| values[*vertex as usize]).collect()
+    }
+}
+
+/// Splits the topography in square tiles of `tile_size` with [`LOD_LEVELS`] levels each, the
+/// simpler ones decimated from the one before. Replaces the tiles it had. Returns the entity
+/// holding the tiles, `None` when the topography has no triangles.
+pub fn spawn_lod(world: &mut World, topography: Entity, tile_size: f32, switch_distance: f32) -> Option<Entity> {
+    remove_lod(world, topography);
+
+    let mesh_handle = world.get::<Handle<Mesh>>(topography)?.clone();
+    let material = world.get::<Handle<StandardMaterial>>(topography)?.clone();
+    let mesh = world.resource::<Assets<Mesh>>().get(&mesh_handle)?.clone();
+    let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+        return None;
+    };
+    let positions = positions.iter().map(|p| Vec3::from(*p)).collect::<Vec<_>>();
+    let indices = mesh.indices()?.iter().collect::<Vec<_>>();
+
+    let mut tiles: HashMap<(i32, i32), Vec<[usize; 3]>> = HashMap::new();
+    for triangle in indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]) {
+        let centroid = triangle.iter().map(|vertex| positions[*vertex]).sum::<Vec3>() / 3.0;
+        let key = ((centroid.x / tile_size).floor() as i32, (centroid.z / tile_size).floor() as i32);
+        tiles.entry(key).or_default().push(triangle);
+    }
+    if tiles.is_empty() {
+        return None;
+    }
+    let mut keys = tiles.keys().copied().collect::<Vec<_>>();
+    keys.sort();
+
+    let mut levels: Vec<(String, LodTile)> = vec![];
+    for key in keys {
+        let triangles = &tiles[&key];
+        let used = triangles.iter().flatten().map(|vertex| positions[*vertex].y);
+        let elevation = used.clone().sum::<f32>() / used.count() as f32;
+        let center = Vec3::new((key.0 as f32 + 0.5) * tile_size, elevation, (key.1 as f32 + 0.5) * tile_size);
+
+        let mut level_triangles = triangles.clone();
+        for level in 0..LOD_LEVELS {
+            if level > 0 {
+                let target = (level_triangles.len() / 4).max(MIN_TILE_TRIANGLES);
+                if target >= level_triangles.len() {
+                    break;
+                }
+                level_triangles = decimate(&positions, &level_triangles, &DecimationTarget { triangles: Some(target), vertical_tolerance: None });
+            }
+            levels.push((format!("Tile {}, {} LOD {}", key.0, key.1, level), LodTile::new(level, center, &level_triangles)));
+        }
+        if let Some((_, tile)) = levels.last_mut() {
+            tile.last = true;
+        }
+    }
+
+    let mut children = vec![];
+    for (name, tile) in levels {
+        let tile_mesh = world.resource_mut::<Assets<Mesh>>().add(tile.mesh(&mesh));
+        children.push(world.spawn((
+            PbrBundle {
+                mesh: tile_mesh,
+                material: material.clone(),
+                visibility: Visibility::Hidden,
+                ..Default::default()
+            },
+            Name::new(name),
+            tile,
+        )).id());
+    }
+
+    let lod = world.spawn((
+        SpatialBundle::default(),
+        Name::new("Level of detail"),
+        TopographyLod { tile_size, switch_distance },
+    )).push_children(&children).id();
+    world.entity_mut(topography)
+        .insert(RenderLayers::layer(HIDDEN_LAYER))
+        .add_child(lod);
+    Some(lod)
+}
+
+/// The child of the topography holding its tiles.
+pub fn lod_of(world: &World, topography: Entity) -> Option<Entity> {
+    world.get::<Children>(topography)?.iter()
+        .copied()
+        .find(|child| world.get::<TopographyLod>(*child).is_some())
+}
+
+/// Despawns the tiles and shows the whole topography mesh again.
+pub fn remove_lod(world: &mut World, topography: Entity) {
+    if let Some(lod) = lod_of(world, topography) {
+        world.entity_mut(lod).despawn_recursive();
+        world.entity_mut(topography).remove::<RenderLayers>();
+    }
+}
+
+/// Shows the level of every tile matching its distance to the active camera.
+pub fn update_lod_visibility(
+    cameras: Query<&GlobalTransform, With<ActiveEditorCamera>>,
+    lods: Query<&TopographyLod>,
+    mut tiles: Query<(&LodTile, &Parent, &GlobalTransform, &mut Visibility)>,
+) {
+    let Ok(camera) = cameras.get_single().map(|transform| transform.translation()) else {
+        return;
+    };
+
+    for (tile, parent, transform, mut visibility) in tiles.iter_mut() {
+        let Ok(lod) = lods.get(parent.get()) else {
+            continue;
+        };
+        let (min, max) = lod.range(tile.level, tile.last);
+        let distance = camera.distance(transform.transform_point(tile.center));
+        let wanted = if distance >= min && distance < max { Visibility::Inherited } else { Visibility::Hidden };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+    }
+}
+
+/// Copies the attributes of the topographies whose mesh changed, as when they are shaded,
+/// to their tiles.
+pub fn refresh_lod_tiles(
+    mut events: EventReader<AssetEvent<Mesh>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    topographies: Query<(&Handle<Mesh>, &Children), With<TopographyMesh>>,
+    lods: Query<&Children, With<TopographyLod>>,
+    tiles: Query<(&LodTile, &Handle<Mesh>)>,
+) {
+    let modified = events.iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.clone()),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    if modified.is_empty() {
+        return;
+    }
+
+    for (handle, children) in topographies.iter() {
+        if !modified.contains(handle) {
+            continue;
+        }
+        let Some(topography) = meshes.get(handle) else {
+            continue;
+        };
+        let rebuilt = children.iter()
+            .filter_map(|child| lods.get(*child).ok())
+            .flat_map(|lod_tiles| lod_tiles.iter())
+            .filter_map(|child| tiles.get(*child).ok())
+            .map(|(tile, tile_handle)| (tile_handle.clone(), tile.mesh(topography)))
+            .collect::<Vec<_>>();
+        for (tile_handle, mesh) in rebuilt {
+            if let Some(tile_mesh) = meshes.get_mut(&tile_handle) {
+                *tile_mesh = mesh;
+            }
+        }
+    }
+}
@@ -6,20 +6,101 @@ use bevy::prelude::*;
 
 
 use delaunator::{Point, triangulate};
-use bevy::render::mesh::{PrimitiveTopology};
+use bevy::render::mesh::{PrimitiveTopology, VertexAttributeValues};
 
 use csv::ReaderBuilder;
+use crate::custom_meshes::color_legend::{ColorLegend, Colormap, UNKNOWN_COLOR};
 use crate::files_manager::csv_parser::CsvFile;
+use crate::files_manager::dem_parser::DemGrid;
+use crate::files_manager::orthophoto::WorldFile;
+use crate::math::constrained_delaunay::constrained_delaunay;
+use crate::math::decimation::{decimate, DecimationTarget};
+use crate::math::statistics;
+use crate::math::surface::SurfaceGrid;
+use crate::math::surface_trim::SurfaceTrim;
+use crate::math::terrain::{aspect, hillshade, slope, SUN_ALTITUDE, SUN_AZIMUTH};
 
+/// Base colour of the topography material, white while vertex colours are shown.
+pub const TOPOGRAPHY_COLOR: Color = Color::rgb(135.0/255.0, 135.0/255.0, 73.0/255.0);
+/// Slope shading of the walls steeper than the design angle.
+pub const STEEP_SLOPE_COLOR: [f32; 4] = [0.85, 0.0, 0.0, 1.0];
 
+/// Share of the light a fully shaded vertex keeps with the hillshade on.
+const HILLSHADE_AMBIENT: f32 = 0.35;
+
+/// Value the topography vertices are coloured by.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum TopographyShading {
+    /// The plain topography colour
+    #[default]
+    Flat,
+    /// Hypsometric ramp of the mine grid elevation
+    Elevation,
+    Slope,
+    Aspect,
+}
+
+impl TopographyShading {
+    pub fn name(self) -> &'static str {
+        match self {
+            TopographyShading::Flat => "Flat",
+            TopographyShading::Elevation => "Elevation",
+            TopographyShading::Slope => "Slope",
+            TopographyShading::Aspect => "Aspect",
+        }
+    }
+
+    /// Title of the legend, with the unit of the values.
+    pub fn title(self) -> &'static str {
+        match self {
+            TopographyShading::Flat => "",
+            TopographyShading::Elevation => "Elevation (m)",
+            TopographyShading::Slope => "Slope (°)",
+            TopographyShading::Aspect => "Aspect (° from north)",
+        }
+    }
+
+    pub fn all() -> [TopographyShading; 4] {
+        [TopographyShading::Flat, TopographyShading::Elevation, TopographyShading::Slope, TopographyShading::Aspect]
+    }
+}
+
+/// A triangulated surface, its positions being the mine grid ones minus the offsets.
+/// Changing the shading recolours the mesh of the entity.
 #[derive(Component)]
 pub struct TopographyMesh{
     pub offset_x: f64,
     pub offset_y: f64,
     pub offset_z: f64,
+    pub shading: TopographyShading,
+    /// Darkens the vertices facing away from a light in the north-west
+    pub hillshade: bool,
+    /// Walls steeper than this, in degrees, are flagged in the slope shading
+    pub design_slope: f32,
+    pub legend: ColorLegend,
+    /// Name of the image draped as the base colour texture
+    pub orthophoto: Option<String>,
 }
 
 impl TopographyMesh {
+    pub fn new(offset_x: f64, offset_y: f64, offset_z: f64) -> Self {
+        Self {
+            offset_x,
+            offset_y,
+            offset_z,
+            shading: TopographyShading::default(),
+            hillshade: false,
+            design_slope: 45.0,
+            legend: ColorLegend::default(),
+            orthophoto: None,
+        }
+    }
+
+    /// Base colour of the material, white when the vertex colours or the orthophoto show as they are.
+    pub fn base_color(&self, vertex_colors: bool) -> Color {
+        if vertex_colors || self.orthophoto.is_some() { Color::WHITE } else { TOPOGRAPHY_COLOR }
+    }
+
     fn calculate_normals(vertices: &[Vec3], triangles: &[usize]) -> Vec<Vec3> {
         let mut normals = vec![Vec3::ZERO; vertices.len()];
         for chunk in triangles.chunks(3) {
@@ -32,17 +113,66 @@ impl TopographyMesh {
             normals[chunk[2]] += normal;
         }
         for normal in &mut normals {
-            *normal = normal.normalize();
+            // Vertices trimmed out of every triangle are left without normal
+            *normal = normal.normalize_or_zero();
         }
         normals
     }
 
-    fn create_mesh(vec: Vec<[f64;3]>) -> Mesh{
+    /// Triangulates the points on the horizontal plane. Every `breaklines` segment, indices
+    /// of two points, is kept as an edge so crests, toes and roads are not cut across.
+    fn triangulate_points(vec: &[[f64;3]], breaklines: &[[usize;2]]) -> Vec<usize>{
         let points = vec.iter().map(|v| Point { x: v[0], y: v[1] }).collect::<Vec<Point>>();
-        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
         let result = triangulate(&points);
 
-        let triangles = result.triangles;
+        if breaklines.is_empty() {
+            return result.triangles;
+        }
+        let planar = vec.iter().map(|v| [v[0], v[1]]).collect::<Vec<_>>();
+        let (triangles, skipped) = constrained_delaunay(&planar, &result.triangles, breaklines);
+        if skipped > 0 {
+            warn!("{} breakline segments cross other breaklines and were not kept", skipped);
+        }
+        triangles
+    }
+
+    /// Triangulates the points, see [`Self::triangulate_points`], and removes the triangles
+    /// left out by `trim`. The vertices of the boundary and holes are draped on the surface and
+    /// added as breaklines first, so the trimmed edges follow the polygons.
+    fn create_mesh(mut vec: Vec<[f64;3]>, breaklines: &[[usize;2]], trim: &SurfaceTrim) -> Mesh{
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        let mut triangles = Self::triangulate_points(&vec, breaklines);
+
+        if trim.polygons().next().is_some() {
+            let surface = SurfaceGrid::new(
+                vec.iter().map(|v| Vec3::new(v[0] as f32, v[2] as f32, v[1] as f32)).collect(),
+                triangles.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+            );
+
+            let mut breaklines = breaklines.to_vec();
+            for polygon in trim.polygons() {
+                // Vertices outside of the surface are left out, with the segments joining them
+                let draped = polygon.iter()
+                    .map(|vertex| {
+                        let elevation = surface.elevation(vertex[0] as f32, vertex[1] as f32)?;
+                        vec.push([vertex[0], vertex[1], elevation as f64]);
+                        Some(vec.len() - 1)
+                    })
+                    .collect::<Vec<_>>();
+                for (i, vertex) in draped.iter().enumerate() {
+                    if let (Some(a), Some(b)) = (vertex, draped[(i + 1) % draped.len()]) {
+                        breaklines.push([*a, b]);
+                    }
+                }
+            }
+            triangles = Self::triangulate_points(&vec, &breaklines);
+        }
+
+        if !trim.is_empty() {
+            let planar = vec.iter().map(|v| [v[0], v[1]]).collect::<Vec<_>>();
+            triangles = trim.trim(&planar, &triangles);
+        }
+
         let vector_values = vec.iter().map(|v| Vec3::new(v[0] as f32, v[2] as f32, v[1] as f32)).collect::<Vec<_>>();
         let normals = Self::calculate_normals(&vector_values, &triangles);
 
@@ -54,7 +184,9 @@ impl TopographyMesh {
         mesh
     }
 
-    pub fn from_points(mut vec: Vec<[f64;3]>) -> (Mesh, Self){
+    /// Mesh of the points with the offsets removed, see [`Self::create_mesh`] for the breaklines
+    /// and trimming. The polygons of `trim` are in the coordinates of the points.
+    pub fn from_points(mut vec: Vec<[f64;3]>, breaklines: &[[usize;2]], trim: &SurfaceTrim) -> (Mesh, Self){
         let min_x = vec.iter().map(|v| v[0]).min_by(|a, b| a.partial_cmp(b).unwrap()).unwrap();
         let min_y = vec.iter().map(|v| v[1]).min_by(|a, b| a.partial_cmp(b).unwrap()).unwrap();
         let min_z = vec.iter().map(|v| v[2]).min_by(|a, b| a.partial_cmp(b).unwrap()).unwrap();
@@ -64,12 +196,15 @@ impl TopographyMesh {
             v[1] -= min_y;
             v[2] -= min_z;
         };
-        let mesh = Self::create_mesh(vec);
+        let mut trim = trim.clone();
+        trim.translate(min_x, min_y);
+        let mesh = Self::create_mesh(vec, breaklines, &trim);
 
-        (mesh, Self { offset_x:min_x, offset_y: min_y, offset_z: min_z })
+        (mesh, Self::new(min_x, min_y, min_z))
     }
 
-    pub fn from_csv(csv: &CsvFile) -> Result<(Mesh, Self), Box<dyn Error>>{
+    /// Mesh of the `x, y, z` rows of the file. Only the edge length and alpha of `trim` apply.
+    pub fn from_csv(csv: &CsvFile, trim: &SurfaceTrim) -> Result<(Mesh, Self), Box<dyn Error>>{
 
         let file = csv.get_file().unwrap();
         let reader = BufReader::new(file);
@@ -102,9 +237,261 @@ impl TopographyMesh {
             v[2] -= min_z;
         }
 
-        let mesh = Self::create_mesh(coords);
-        Ok((mesh, Self { offset_x:min_x, offset_y: min_y, offset_z: min_z }))
+        let trim = SurfaceTrim {
+            boundaries: vec![],
+            holes: vec![],
+            ..trim.clone()
+        };
+        let mesh = Self::create_mesh(coords, &[], &trim);
+        Ok((mesh, Self::new(min_x, min_y, min_z)))
+
+    }
+
+    /// Mesh of the grid cells, two triangles between every four nodes with elevation and one
+    /// where a node of them is NODATA. The grid is meshed as it is, without Delaunay.
+    pub fn from_grid(grid: &DemGrid) -> Result<(Mesh, Self), Box<dyn Error + Send + Sync>> {
+        let min_z = grid.values.iter().flatten().copied().min_by(|a, b| a.partial_cmp(b).unwrap())
+            .ok_or("Every cell of the grid is NODATA")?;
+        let (min_x, min_y) = (grid.x_min, grid.y_min);
+
+        let mut positions: Vec<[f32;3]> = Vec::with_capacity(grid.valid_cells());
+        let mut indices: Vec<Option<u32>> = Vec::with_capacity(grid.values.len());
+        for row in 0..grid.rows {
+            for column in 0..grid.columns {
+                let index = grid.value(column, row).map(|z| {
+                    let (x, y) = grid.position(column, row);
+                    positions.push([(x - min_x) as f32, (z - min_z) as f32, (y - min_y) as f32]);
+                    positions.len() as u32 - 1
+                });
+                indices.push(index);
+            }
+        }
+
+        let mut triangles: Vec<u32> = vec![];
+        for row in 0..grid.rows.saturating_sub(1) {
+            for column in 0..grid.columns.saturating_sub(1) {
+                let node = |c: usize, r: usize| indices[r * grid.columns + c];
+                // Counter-clockwise seen from above, so the normals point up
+                let quad = [node(column, row), node(column, row + 1), node(column + 1, row + 1), node(column + 1, row)];
+                match quad {
+                    [Some(a), Some(b), Some(c), Some(d)] => triangles.extend([a, b, c, a, c, d]),
+                    _ => {
+                        let corners = quad.into_iter().flatten().collect::<Vec<_>>();
+                        if corners.len() == 3 {
+                            triangles.extend(corners);
+                        }
+                    }
+                }
+            }
+        }
+        if triangles.is_empty() {
+            return Err("The grid has no cells with elevation at all four corners".into());
+        }
+
+        let vertices = positions.iter().map(|p| Vec3::from(*p)).collect::<Vec<_>>();
+        let normals = Self::calculate_normals(&vertices, &triangles.iter().map(|i| *i as usize).collect::<Vec<_>>());
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; positions.len()]);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.set_indices(Some(bevy::render::mesh::Indices::U32(triangles)));
 
+        Ok((mesh, Self::new(min_x, min_y, min_z)))
     }
 
-}
\ No newline at end of file
+    /// Simplified copy of a topography mesh, see [`decimate`]. The vertices left out of every
+    /// triangle are dropped and the normals recalculated, other attributes are not kept.
+    pub fn decimate(mesh: &Mesh, target: &DecimationTarget) -> Option<Mesh> {
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            return None;
+        };
+        let positions = positions.iter().map(|p| Vec3::from(*p)).collect::<Vec<_>>();
+        let indices = mesh.indices()?.iter().collect::<Vec<_>>();
+        let triangles = indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect::<Vec<_>>();
+
+        let mut kept: Vec<Option<u32>> = vec![None; positions.len()];
+        let mut vertices: Vec<Vec3> = vec![];
+        let mut indices: Vec<usize> = vec![];
+        for vertex in decimate(&positions, &triangles, target).into_iter().flatten() {
+            let index = *kept[vertex].get_or_insert_with(|| {
+                vertices.push(positions[vertex]);
+                vertices.len() as u32 - 1
+            });
+            indices.push(index as usize);
+        }
+        let normals = Self::calculate_normals(&vertices, &indices);
+
+        let mut decimated = Mesh::new(PrimitiveTopology::TriangleList);
+        decimated.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; vertices.len()]);
+        decimated.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+        decimated.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        decimated.set_indices(Some(bevy::render::mesh::Indices::U32(indices.into_iter().map(|i| i as u32).collect())));
+        Some(decimated)
+    }
+
+    /// Planar UVs draping an image of `width` by `height` pixels georeferenced by `world_file`,
+    /// from the mine grid easting and northing of every vertex.
+    pub fn planar_uvs(&self, mesh: &Mesh, world_file: &WorldFile, width: u32, height: u32) -> Vec<[f32;2]> {
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            return vec![];
+        };
+        positions.iter()
+            .map(|position| {
+                let x = position[0] as f64 + self.offset_x;
+                let y = position[2] as f64 + self.offset_y;
+                // Pixel centres are half a pixel in from the edges of the texture
+                world_file.pixel(x, y).map_or([0.0, 0.0], |(column, row)| {
+                    [((column + 0.5) / width as f64) as f32, ((row + 0.5) / height as f64) as f32]
+                })
+            })
+            .collect()
+    }
+
+    /// Value of the shading at every vertex of the mesh, `None` where it has none.
+    pub fn shading_values(&self, mesh: &Mesh) -> Vec<Option<f32>> {
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            return vec![];
+        };
+        let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            Some(VertexAttributeValues::Float32x3(normals)) => normals.clone(),
+            _ => vec![[0.0; 3]; positions.len()],
+        };
+
+        positions.iter().zip(normals.iter())
+            .map(|(position, normal)| match self.shading {
+                TopographyShading::Flat => None,
+                TopographyShading::Elevation => Some((position[1] as f64 + self.offset_z) as f32),
+                TopographyShading::Slope => slope(Vec3::from(*normal)),
+                TopographyShading::Aspect => aspect(Vec3::from(*normal)),
+            })
+            .collect()
+    }
+
+    /// Legend fitting the shading: the whole elevation range, slopes up to the design angle
+    /// and a full turn for the aspect.
+    pub fn reset_legend(&mut self, mesh: &Mesh) {
+        self.legend = match self.shading {
+            TopographyShading::Flat => ColorLegend::default(),
+            TopographyShading::Elevation => {
+                let values = self.shading_values(mesh).into_iter().flatten().collect::<Vec<_>>();
+                ColorLegend {
+                    colormap: Colormap::Viridis,
+                    min: statistics::quantile(&values, 0.0).unwrap_or(0.0),
+                    max: statistics::quantile(&values, 1.0).unwrap_or(1.0),
+                    ..Default::default()
+                }
+            }
+            TopographyShading::Slope => ColorLegend {
+                colormap: Colormap::Classic,
+                min: 0.0,
+                max: self.design_slope,
+                ..Default::default()
+            },
+            TopographyShading::Aspect => ColorLegend {
+                colormap: Colormap::Jet,
+                min: 0.0,
+                max: 360.0,
+                ..Default::default()
+            },
+        };
+    }
+
+    /// Vertex colours of the shading, with the hillshade applied. `None` when the topography
+    /// is drawn with its plain colour.
+    pub fn shading_colors(&self, mesh: &Mesh) -> Option<Vec<[f32;4]>> {
+        if self.shading == TopographyShading::Flat && !self.hillshade {
+            return None;
+        }
+
+        let base = self.base_color(false).as_linear_rgba_f32();
+        let mut colors = self.shading_values(mesh).into_iter()
+            .map(|value| match (self.shading, value) {
+                (TopographyShading::Flat, _) => base,
+                (TopographyShading::Slope, Some(value)) if value > self.design_slope => STEEP_SLOPE_COLOR,
+                (_, Some(value)) => self.legend.color(value),
+                (_, None) => UNKNOWN_COLOR,
+            })
+            .collect::<Vec<_>>();
+
+        if self.hillshade {
+            if let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+                for (color, normal) in colors.iter_mut().zip(normals.iter()) {
+                    let light = HILLSHADE_AMBIENT + (1.0 - HILLSHADE_AMBIENT) * hillshade(Vec3::from(*normal), SUN_AZIMUTH, SUN_ALTITUDE);
+                    for channel in color.iter_mut().take(3) {
+                        *channel *= light;
+                    }
+                }
+            }
+        }
+        Some(colors)
+    }
+}
+
+/// Recolours the topographies whose shading changed, and the ones whose difference map was
+/// cleared. The difference map takes the place of the shading while it is shown.
+pub fn update_topography_shading(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cleared: RemovedComponents<ElevationDifference>,
+    topographies: Query<(Entity, Ref<TopographyMesh>, &Handle<Mesh>, &Handle<StandardMaterial>), Without<ElevationDifference>>,
+) {
+    let cleared = cleared.iter().collect::<Vec<_>>();
+    for (entity, topography, mesh_handle, material_handle) in topographies.iter() {
+        if !(topography.is_changed() || cleared.contains(&entity)) {
+            continue;
+        }
+        let Some(mesh) = meshes.get_mut(mesh_handle) else {
+            continue;
+        };
+
+        let colors = topography.shading_colors(mesh);
+        let base_color = topography.base_color(colors.is_some());
+        match colors {
+            Some(colors) => mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors),
+            None => {
+                mesh.remove_attribute(Mesh::ATTRIBUTE_COLOR);
+            }
+        }
+        if let Some(material) = materials.get_mut(material_handle) {
+            material.base_color = base_color;
+        }
+    }
+}
+
+/// Elevation difference of the topography to an older surface at every vertex,
+/// shaded onto its mesh as vertex colours.
+#[derive(Component)]
+pub struct ElevationDifference{
+    /// Name of the surface it is compared with
+    pub base: String,
+    /// `None` where the other surface is missing or outside the boundary
+    pub values: Vec<Option<f32>>,
+    pub legend: ColorLegend,
+}
+
+impl ElevationDifference{
+    /// Diverging ramp centred on zero, as wide as the P95 of the absolute differences.
+    pub fn new(base: String, values: Vec<Option<f32>>) -> Self{
+        let mut magnitudes = values.iter().flatten().map(|value| value.abs()).collect::<Vec<_>>();
+        magnitudes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let range = magnitudes.get(magnitudes.len() * 95 / 100).copied().unwrap_or(1.0).max(0.1);
+
+        Self{
+            base,
+            values,
+            legend: ColorLegend{
+                colormap: Colormap::Diverging,
+                min: -range,
+                max: range,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn colors(&self) -> Vec<[f32;4]>{
+        self.values.iter()
+            .map(|value| value.map_or(UNKNOWN_COLOR, |value| self.legend.color(value)))
+            .collect()
+    }
+}
@@ -0,0 +1,273 @@
+use std::error::Error;
+use std::path::Path;
+
+use csv::{ReaderBuilder, WriterBuilder};
+use indexmap::IndexMap;
+use polars::prelude::*;
+
+/// Name of the file, saved next to the header (collar) csv, that remembers the mapping of a project.
+pub const PROJECT_MAPPING_FILENAME: &str = "decorous_columns.csv";
+
+/// The four tables of a drill-hole database, in the same order as [`crate::custom_meshes::drill_holes_mesh::DrillHolesMesh::files`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum DrillTable {
+    Assay,
+    Header,
+    Lithology,
+    Survey,
+}
+
+impl DrillTable {
+    pub fn name(self) -> &'static str {
+        match self {
+            DrillTable::Assay => "assay",
+            DrillTable::Header => "header",
+            DrillTable::Lithology => "lithology",
+            DrillTable::Survey => "survey",
+        }
+    }
+
+    pub fn all() -> [DrillTable; 4] {
+        [
+            DrillTable::Assay,
+            DrillTable::Header,
+            DrillTable::Lithology,
+            DrillTable::Survey,
+        ]
+    }
+
+    pub fn roles(self) -> &'static [ColumnRole] {
+        match self {
+            DrillTable::Assay => &[ColumnRole::HoleId, ColumnRole::From, ColumnRole::To],
+            DrillTable::Header => &[ColumnRole::HoleId, ColumnRole::X, ColumnRole::Y, ColumnRole::Z, ColumnRole::Length],
+            DrillTable::Lithology => &[ColumnRole::HoleId, ColumnRole::From, ColumnRole::To, ColumnRole::Rock],
+            DrillTable::Survey => &[ColumnRole::HoleId, ColumnRole::From, ColumnRole::To, ColumnRole::Azimuth, ColumnRole::Dip],
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::all().into_iter().find(|table| table.name() == name)
+    }
+}
+
+/// What a csv column means to the drill-hole pipeline.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ColumnRole {
+    HoleId,
+    X,
+    Y,
+    Z,
+    Length,
+    From,
+    To,
+    Azimuth,
+    Dip,
+    Rock,
+}
+
+impl ColumnRole {
+    /// Name the column gets once the mapping is applied to a dataframe.
+    pub fn column_name(self) -> &'static str {
+        match self {
+            ColumnRole::HoleId => "hole-id",
+            ColumnRole::X => "x",
+            ColumnRole::Y => "y",
+            ColumnRole::Z => "z",
+            ColumnRole::Length => "length",
+            ColumnRole::From => "from",
+            ColumnRole::To => "to",
+            ColumnRole::Azimuth => "azimuth",
+            ColumnRole::Dip => "dip",
+            ColumnRole::Rock => "rock",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColumnRole::HoleId => "Hole ID",
+            ColumnRole::X => "X (East)",
+            ColumnRole::Y => "Y (North)",
+            ColumnRole::Z => "Z (Elevation)",
+            ColumnRole::Length => "Length",
+            ColumnRole::From => "From",
+            ColumnRole::To => "To",
+            ColumnRole::Azimuth => "Azimuth",
+            ColumnRole::Dip => "Dip",
+            ColumnRole::Rock => "Rock code",
+        }
+    }
+
+    pub fn required(self) -> bool {
+        !matches!(self, ColumnRole::Length)
+    }
+
+    /// Common header names for the role, compared after removing case and punctuation.
+    fn aliases(self) -> &'static [&'static str] {
+        match self {
+            ColumnRole::HoleId => &["holeid", "hole", "bhid", "dhid", "holeno", "holename", "id"],
+            ColumnRole::X => &["x", "east", "easting", "xcollar", "collarx", "locationx", "e"],
+            ColumnRole::Y => &["y", "north", "northing", "ycollar", "collary", "locationy", "n"],
+            ColumnRole::Z => &["z", "rl", "elev", "elevation", "zcollar", "collarz", "height"],
+            ColumnRole::Length => &["length", "maxdepth", "totaldepth", "depth", "eoh", "td"],
+            ColumnRole::From => &["from", "depthfrom", "fromm", "mfrom", "fromdepth", "start", "depth", "at"],
+            ColumnRole::To => &["to", "depthto", "tom", "mto", "todepth", "end"],
+            ColumnRole::Azimuth => &["azimuth", "azi", "az", "bearing", "brg"],
+            ColumnRole::Dip => &["dip", "inclination", "incl", "inc"],
+            ColumnRole::Rock => &["rock", "lith", "lithology", "rockcode", "rocktype", "lithcode", "geol"],
+        }
+    }
+
+    fn from_column_name(name: &str) -> Option<Self> {
+        DrillTable::all().iter()
+            .flat_map(|table| table.roles().iter())
+            .find(|role| role.column_name() == name)
+            .copied()
+    }
+}
+
+fn normalize(header: &str) -> String {
+    header.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// Name of the pseudo role used to save assay variables in the project file.
+const VARIABLE_ROLE: &str = "variable";
+
+/// Which source column of a table plays each [`ColumnRole`].
+/// Assay tables also list the numeric columns that get their own drill-hole layer.
+#[derive(Clone, Debug)]
+pub struct ColumnMapping {
+    pub table: DrillTable,
+    pub columns: IndexMap<ColumnRole, String>,
+    pub variables: Vec<String>,
+}
+
+impl ColumnMapping {
+    pub fn new(table: DrillTable) -> Self {
+        Self { table, columns: IndexMap::new(), variables: vec![] }
+    }
+
+    /// Assigns every role of the table to the first header matching one of its aliases.
+    /// For assay tables every other column whose preview values are all numbers becomes a variable.
+    pub fn guess(table: DrillTable, headers: &[String], preview: &[Vec<String>]) -> Self {
+        let mut mapping = Self::new(table);
+        for role in table.roles() {
+            let found = role.aliases().iter().find_map(|alias| {
+                headers.iter()
+                    .filter(|header| !mapping.columns.values().any(|used| used == *header))
+                    .find(|header| normalize(header) == *alias)
+            });
+            if let Some(header) = found {
+                mapping.columns.insert(*role, header.clone());
+            }
+        }
+
+        if table == DrillTable::Assay {
+            mapping.variables = headers.iter().enumerate()
+                .filter(|(_, header)| !mapping.columns.values().any(|used| used == *header))
+                .filter(|(i, _)| {
+                    let mut values = preview.iter().filter_map(|row| row.get(*i)).filter(|v| !v.trim().is_empty()).peekable();
+                    values.peek().is_some() && values.all(|v| v.trim().parse::<f64>().is_ok())
+                })
+                .map(|(_, header)| header.clone())
+                .collect();
+        }
+        mapping
+    }
+
+    /// Columns of the file that are not used by any role.
+    pub fn unassigned<'a>(&self, headers: &'a [String]) -> Vec<&'a String> {
+        headers.iter()
+            .filter(|header| !self.columns.values().any(|used| used == *header))
+            .collect()
+    }
+
+    pub fn missing_roles(&self) -> Vec<ColumnRole> {
+        self.table.roles().iter()
+            .filter(|role| role.required() && !self.columns.contains_key(*role))
+            .copied()
+            .collect()
+    }
+
+    /// Renames the mapped columns of `df` to their role names.
+    /// Headers are compared ignoring case, as [`super::csv_parser::CsvFile::dataframe`] lowercases them.
+    pub fn apply(&self, mut df: DataFrame) -> PolarsResult<DataFrame> {
+        for role in self.table.roles() {
+            let Some(source) = self.columns.get(role) else {
+                if role.required() {
+                    polars_bail!(ColumnNotFound: "no column assigned to '{}' in the {} table", role.name(), self.table.name());
+                }
+                continue;
+            };
+
+            let source = df.get_column_names().into_iter()
+                .find(|name| name.eq_ignore_ascii_case(source))
+                .map(|name| name.to_string())
+                .ok_or_else(|| polars_err!(ColumnNotFound: "column '{}' not found in the {} table", source, self.table.name()))?;
+
+            let target = role.column_name();
+            if source == target {
+                continue;
+            }
+            if df.get_column_names().contains(&target) {
+                // Keep an unmapped column that happens to use the role name
+                df.rename(target, &format!("{}_original", target))?;
+            }
+            df.rename(&source, target)?;
+        }
+
+        // Variables are looked up by their lowercase name once loaded
+        for variable in self.variables.iter() {
+            let source = df.get_column_names().into_iter()
+                .find(|name| name.eq_ignore_ascii_case(variable))
+                .map(|name| name.to_string())
+                .ok_or_else(|| polars_err!(ColumnNotFound: "variable '{}' not found in the {} table", variable, self.table.name()))?;
+            let target = variable.to_lowercase();
+            if source != target {
+                df.rename(&source, &target)?;
+            }
+        }
+        Ok(df)
+    }
+
+    /// Loads the mappings saved in `dir`, if any.
+    pub fn load_project(dir: &Path) -> Result<[ColumnMapping; 4], Box<dyn Error + Send + Sync>> {
+        let mut mappings = DrillTable::all().map(ColumnMapping::new);
+        let mut reader = ReaderBuilder::new()
+            .has_headers(true)
+            .from_path(dir.join(PROJECT_MAPPING_FILENAME))?;
+
+        for result in reader.records() {
+            let record = result?;
+            if &record[1] == VARIABLE_ROLE {
+                if let Some(table) = DrillTable::from_name(&record[0]) {
+                    mappings[table as usize].variables.push(record[2].to_string());
+                }
+                continue;
+            }
+            let (Some(table), Some(role)) = (DrillTable::from_name(&record[0]), ColumnRole::from_column_name(&record[1])) else {
+                continue;
+            };
+            mappings[table as usize].columns.insert(role, record[2].to_string());
+        }
+
+        Ok(mappings)
+    }
+
+    pub fn save_project(dir: &Path, mappings: &[ColumnMapping; 4]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut writer = WriterBuilder::new().from_path(dir.join(PROJECT_MAPPING_FILENAME))?;
+        writer.write_record(["table", "role", "column"])?;
+        for mapping in mappings.iter() {
+            for (role, column) in mapping.columns.iter() {
+                writer.write_record([mapping.table.name(), role.column_name(), column.as_str()])?;
+            }
+            for variable in mapping.variables.iter() {
+                writer.write_record([mapping.table.name(), VARIABLE_ROLE, variable.as_str()])?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use std::error::Error;
+
+use dxf::entities::{Entity, EntityType, LwPolyline, LwPolylineVertex};
+use dxf::enums::AcadVersion;
+use dxf::tables::Layer;
+use dxf::Drawing;
+
+use crate::custom_meshes::contour_mesh::ContourLines;
+
+pub const MAJOR_CONTOURS_LAYER: &str = "CONTOURS_MAJOR";
+pub const MINOR_CONTOURS_LAYER: &str = "CONTOURS_MINOR";
+
+/// Writes every contour as a `LwPolyline` in mine grid coordinates, with its elevation set,
+/// on the major or minor contours layer. `DxfFile::get_breaklines` reads them back.
+pub fn write_contours_dxf(contours: &ContourLines, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut drawing = Drawing::new();
+    drawing.header.version = AcadVersion::R2000;
+    for name in [MAJOR_CONTOURS_LAYER, MINOR_CONTOURS_LAYER] {
+        drawing.add_layer(Layer {
+            name: name.to_string(),
+            ..Default::default()
+        });
+    }
+
+    // Elevations are already in mine grid coordinates
+    let [offset_x, offset_y, _] = contours.offset;
+    for contour in contours.contours.iter() {
+        let mut polyline = LwPolyline {
+            elevation: contour.elevation as f64,
+            ..Default::default()
+        };
+        // Points are Y-up, east and north are x and z
+        polyline.vertices = contour.points.iter()
+            .map(|point| LwPolylineVertex {
+                x: point.x as f64 + offset_x,
+                y: point.z as f64 + offset_y,
+                ..Default::default()
+            })
+            .collect();
+        polyline.set_is_closed(contour.closed);
+
+        let mut entity = Entity::new(EntityType::LwPolyline(polyline));
+        entity.common.layer = if contour.major { MAJOR_CONTOURS_LAYER } else { MINOR_CONTOURS_LAYER }.to_string();
+        drawing.add_entity(entity);
+    }
+
+    drawing.save_file(path)?;
+    Ok(())
+}
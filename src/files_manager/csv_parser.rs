@@ -3,6 +3,7 @@ use std::fs::File;
 use std::io::{BufRead, BufReader};
 use bevy::prelude::*;
 use polars::prelude::*;
+use csv::ReaderBuilder;
 use crate::files_manager::files_porperties::FileProperties;
 
 #[derive(Component, Clone)]
@@ -32,12 +33,45 @@ impl CsvFile {
         Ok(lines)
     }
 
+    /// Column names and first `rows` records, to preview the file before loading it.
+    /// Files without headers get the same `column_n` names polars gives them.
+    pub fn preview(&self, rows: usize) -> Result<(Vec<String>, Vec<Vec<String>>), Box<dyn Error>> {
+        let mut csv_reader = ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(self.sep)
+            .from_reader(self.get_file()?);
 
+        let mut records = csv_reader.records();
+        let first = match records.next() {
+            Some(record) => record?.iter().map(|field| field.to_string()).collect::<Vec<_>>(),
+            None => return Ok((vec![], vec![])),
+        };
+
+        let mut preview = vec![];
+        let headers = if self.header {
+            first
+        } else {
+            let headers = (1..=first.len()).map(|i| format!("column_{}", i)).collect();
+            preview.push(first);
+            headers
+        };
+
+        for record in records.take(rows.saturating_sub(preview.len())) {
+            preview.push(record?.iter().map(|field| field.to_string()).collect());
+        }
+
+        Ok((headers, preview))
+    }
+
+    /// Reads every column as text. Schema inference only looks at the first rows, so a value
+    /// that is not a number further down, as `<0.01` or `N/A`, would fail the whole read;
+    /// numbers are cast where they are used, the values that can not be parsed becoming `None`.
     pub fn dataframe(&self) -> PolarsResult<DataFrame> {
         let file = File::open(self.path.clone())?;
 
         let mut df = CsvReader::new(file)
             .has_header(self.header)
+            .infer_schema(Some(0))
             .finish()?;
 
         if self.header {
@@ -54,4 +88,30 @@ impl CsvFile {
         Ok(df)
     }
 
-}
\ No newline at end of file
+}
+/// Column of `df` as strings, numbers are formatted.
+pub fn string_column(df: &DataFrame, name: &str) -> PolarsResult<Vec<Option<String>>> {
+    let series = df.column(name)?.cast(&DataType::Utf8)?;
+    Ok(series.utf8()?.into_iter().map(|v| v.map(|s| s.to_string())).collect())
+}
+
+/// Column of `df` as numbers, values that can not be parsed become `None`.
+pub fn f32_column(df: &DataFrame, name: &str) -> PolarsResult<Vec<Option<f32>>> {
+    let series = df.column(name)?.cast(&DataType::Float32)?;
+    Ok(series.f32()?.into_iter().collect())
+}
+
+/// Writes every column of `df` to a comma separated file with headers.
+pub fn write_dataframe(df: &DataFrame, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let columns = df.get_column_names().iter()
+        .map(|name| string_column(df, name))
+        .collect::<PolarsResult<Vec<_>>>()?;
+
+    let mut writer = csv::WriterBuilder::new().from_path(path)?;
+    writer.write_record(df.get_column_names())?;
+    for row in 0..df.height() {
+        writer.write_record(columns.iter().map(|column| column[row].as_deref().unwrap_or("")))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
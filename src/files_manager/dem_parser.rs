@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use crate::files_manager::files_porperties::FileProperties;
+
+/// NODATA value of ESRI grids whose header leaves it out.
+const DEFAULT_NODATA: f64 = -9999.0;
+/// Largest distance from a point of an XYZ grid to its node, as a share of the cell size.
+const GRID_TOLERANCE: f64 = 0.01;
+/// Larger grids are taken as a sign the XYZ points are scattered rather than gridded.
+const MAX_GRID_CELLS: usize = 200_000_000;
+/// Most nodes an XYZ grid may have for every point of the file.
+const MAX_NODES_PER_POINT: usize = 4;
+
+/// A regular elevation grid, rows running south to north and columns west to east.
+#[derive(Clone, Debug)]
+pub struct DemGrid {
+    pub columns: usize,
+    pub rows: usize,
+    /// Mine grid easting of the centre of the west column
+    pub x_min: f64,
+    /// Mine grid northing of the centre of the south row
+    pub y_min: f64,
+    pub cell_width: f64,
+    pub cell_height: f64,
+    /// Elevation of every cell by rows from the south-west one, `None` for NODATA cells
+    pub values: Vec<Option<f64>>,
+}
+
+impl DemGrid {
+    pub fn value(&self, column: usize, row: usize) -> Option<f64> {
+        self.values[row * self.columns + column]
+    }
+
+    /// Mine grid easting and northing of the centre of the cell.
+    pub fn position(&self, column: usize, row: usize) -> (f64, f64) {
+        (self.x_min + column as f64 * self.cell_width, self.y_min + row as f64 * self.cell_height)
+    }
+
+    pub fn valid_cells(&self) -> usize {
+        self.values.iter().filter(|value| value.is_some()).count()
+    }
+
+    /// Grid keeping one of every `step` columns and rows, starting from the south-west cell.
+    pub fn downsample(&self, step: usize) -> DemGrid {
+        let step = step.max(1);
+        let columns = (self.columns + step - 1) / step;
+        let rows = (self.rows + step - 1) / step;
+        let values = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .map(|(column, row)| self.value(column * step, row * step))
+            .collect();
+        DemGrid {
+            columns,
+            rows,
+            x_min: self.x_min,
+            y_min: self.y_min,
+            cell_width: self.cell_width * step as f64,
+            cell_height: self.cell_height * step as f64,
+            values,
+        }
+    }
+}
+
+/// An ESRI ASCII grid (`.asc`) or an XYZ grid, one `x y z` point per node.
+#[derive(Component, Clone)]
+pub struct DemFile {
+    pub path: String,
+}
+
+impl FileProperties for DemFile {
+    fn path(&self) -> String {
+        self.path.clone()
+    }
+}
+
+impl DemFile {
+    fn is_esri_grid(&self) -> bool {
+        Path::new(&self.path).extension()
+            .and_then(|extension| extension.to_str())
+            .map_or(false, |extension| matches!(extension.to_lowercase().as_str(), "asc" | "grd"))
+    }
+
+    /// Reads the grid. XYZ points whose elevation is `xyz_nodata` are left out, as the
+    /// nodes missing from the file.
+    pub fn read(&self, xyz_nodata: Option<f64>) -> Result<DemGrid, Box<dyn Error + Send + Sync>> {
+        let reader = BufReader::new(fs::File::open(&self.path)?);
+        if self.is_esri_grid() {
+            read_esri_grid(reader)
+        } else {
+            read_xyz_grid(reader, xyz_nodata)
+        }
+    }
+}
+
+/// Header keys and cells of an ESRI ASCII grid, the rows of the file running north to south.
+pub fn read_esri_grid(reader: impl BufRead) -> Result<DemGrid, Box<dyn Error + Send + Sync>> {
+    let mut header: HashMap<String, f64> = HashMap::new();
+    let mut cells: Vec<f64> = vec![];
+
+    for line in reader.lines() {
+        let line = line?;
+        let mut tokens = line.split_whitespace().peekable();
+        let Some(first) = tokens.peek() else {
+            continue;
+        };
+        if cells.is_empty() && first.parse::<f64>().is_err() {
+            let key = first.to_lowercase();
+            tokens.next();
+            let value = tokens.next().ok_or(format!("The header {} has no value", key))?;
+            header.insert(key, value.parse()?);
+            continue;
+        }
+        for token in tokens {
+            cells.push(token.parse()?);
+        }
+    }
+
+    let key = |name: &str| header.get(name).copied().ok_or(format!("The grid header has no {}", name));
+    let columns = key("ncols")? as usize;
+    let rows = key("nrows")? as usize;
+    let cell_size = key("cellsize")?;
+    let nodata = header.get("nodata_value").copied().unwrap_or(DEFAULT_NODATA);
+    let x_min = match header.get("xllcenter") {
+        Some(x) => *x,
+        None => key("xllcorner")? + cell_size * 0.5,
+    };
+    let y_min = match header.get("yllcenter") {
+        Some(y) => *y,
+        None => key("yllcorner")? + cell_size * 0.5,
+    };
+    if cells.len() != columns * rows {
+        return Err(format!("The grid should have {} x {} cells, it has {}", columns, rows, cells.len()).into());
+    }
+
+    let values = (0..rows).rev()
+        .flat_map(|row| cells[row * columns..(row + 1) * columns].iter())
+        .map(|value| (*value != nodata && value.is_finite()).then_some(*value))
+        .collect();
+    Ok(DemGrid { columns, rows, x_min, y_min, cell_width: cell_size, cell_height: cell_size, values })
+}
+
+/// Smallest step between the sorted coordinates, `None` when they are all the same.
+fn grid_step(sorted: &[f64]) -> Option<f64> {
+    sorted.windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .filter(|step| *step > 1e-9)
+        .min_by(f64::total_cmp)
+}
+
+/// Points of a regular grid aligned with the axes, separated by spaces, commas or semicolons.
+/// Lines that are not three numbers, as a header, and points without a finite x and y are skipped.
+pub fn read_xyz_grid(reader: impl BufRead, nodata: Option<f64>) -> Result<DemGrid, Box<dyn Error + Send + Sync>> {
+    let mut points: Vec<[f64; 3]> = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        let values = line.split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>();
+        if let Ok([x, y, z, ..]) = values.as_deref() {
+            if x.is_finite() && y.is_finite() {
+                points.push([*x, *y, *z]);
+            }
+        }
+    }
+    if points.is_empty() {
+        return Err("The file has no x y z points".into());
+    }
+
+    let mut xs = points.iter().map(|point| point[0]).collect::<Vec<_>>();
+    let mut ys = points.iter().map(|point| point[1]).collect::<Vec<_>>();
+    xs.sort_by(f64::total_cmp);
+    ys.sort_by(f64::total_cmp);
+    let (x_min, y_min) = (xs[0], ys[0]);
+    let cell_width = grid_step(&xs).ok_or("The points are all on one column, it is not a grid")?;
+    let cell_height = grid_step(&ys).ok_or("The points are all on one row, it is not a grid")?;
+    let columns = ((xs[xs.len() - 1] - x_min) / cell_width).round() as usize + 1;
+    let rows = ((ys[ys.len() - 1] - y_min) / cell_height).round() as usize + 1;
+    // A grid mostly empty is as much a sign of scattered points
+    let cells = columns.saturating_mul(rows);
+    if cells > MAX_GRID_CELLS || cells > points.len().saturating_mul(MAX_NODES_PER_POINT) {
+        return Err("The points are not on a regular grid".into());
+    }
+
+    // Every point is checked before the grid is allocated
+    let mut nodes = Vec::with_capacity(points.len());
+    for [x, y, z] in points {
+        let column = ((x - x_min) / cell_width).round();
+        let row = ((y - y_min) / cell_height).round();
+        let off_grid = (x - x_min - column * cell_width).abs() > cell_width * GRID_TOLERANCE
+            || (y - y_min - row * cell_height).abs() > cell_height * GRID_TOLERANCE;
+        if off_grid {
+            return Err(format!("The point {} {} is not on a regular grid", x, y).into());
+        }
+        if nodata.map_or(false, |nodata| z == nodata) || !z.is_finite() {
+            continue;
+        }
+        nodes.push((row as usize * columns + column as usize, z));
+    }
+
+    let mut values = vec![None; cells];
+    for (node, z) in nodes {
+        values[node] = Some(z);
+    }
+    Ok(DemGrid { columns, rows, x_min, y_min, cell_width, cell_height, values })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn esri(text: &str) -> Result<DemGrid, Box<dyn Error + Send + Sync>> {
+        read_esri_grid(text.as_bytes())
+    }
+
+    fn xyz(text: &str, nodata: Option<f64>) -> Result<DemGrid, Box<dyn Error + Send + Sync>> {
+        read_xyz_grid(text.as_bytes(), nodata)
+    }
+
+    #[test]
+    fn esri_grid_from_the_corner() {
+        let grid = esri("ncols 3\nnrows 2\nxllcorner 100\nyllcorner 200\ncellsize 10\nNODATA_value -1\n1 2 3\n4 -1 6\n").unwrap();
+        assert_eq!((grid.columns, grid.rows), (3, 2));
+        assert_eq!((grid.x_min, grid.y_min), (105.0, 205.0));
+        // The last row of the file is the south one
+        assert_eq!(grid.values, vec![Some(4.0), None, Some(6.0), Some(1.0), Some(2.0), Some(3.0)]);
+        assert_eq!(grid.position(2, 1), (125.0, 215.0));
+    }
+
+    #[test]
+    fn esri_grid_from_the_centre_with_the_default_nodata() {
+        let grid = esri("NCOLS 2\nNROWS 2\nXLLCENTER 100\nYLLCENTER 200\nCELLSIZE 5\n-9999 2\n3 4\n").unwrap();
+        assert_eq!((grid.x_min, grid.y_min), (100.0, 200.0));
+        assert_eq!(grid.values, vec![Some(3.0), Some(4.0), None, Some(2.0)]);
+        assert_eq!(grid.valid_cells(), 3);
+    }
+
+    #[test]
+    fn esri_grid_errors() {
+        let error = esri("ncols 3\nnrows 2\nxllcorner 0\nyllcorner 0\ncellsize 1\n1 2 3\n4 5\n").unwrap_err();
+        assert!(error.to_string().contains("3 x 2 cells, it has 5"), "{}", error);
+        assert!(esri("ncols 2\nnrows 1\nxllcorner 0\nyllcorner 0\n1 2\n").is_err());
+        assert!(esri("ncols 2\nnrows 1\nxllcorner 0\nyllcorner 0\ncellsize\n1 2\n").is_err());
+    }
+
+    #[test]
+    fn xyz_grid_with_nodata_and_missing_nodes() {
+        let text = "x,y,z\n0,0,1\n10,0,2\n20,0,-99\n0,10,4\n20,10,6\n";
+        let grid = xyz(text, Some(-99.0)).unwrap();
+        assert_eq!((grid.columns, grid.rows), (3, 2));
+        assert_eq!((grid.cell_width, grid.cell_height), (10.0, 10.0));
+        assert_eq!(grid.values, vec![Some(1.0), Some(2.0), None, Some(4.0), None, Some(6.0)]);
+
+        let grid = xyz(text, None).unwrap();
+        assert_eq!(grid.value(2, 0), Some(-99.0));
+    }
+
+    #[test]
+    fn xyz_points_off_the_grid() {
+        let error = xyz("0 0 1\n1 0 2\n2 0 3\n0 1 4\n1.3 1 5\n2 1 6\n", None).unwrap_err();
+        assert!(error.to_string().contains("is not on a regular grid"), "{}", error);
+    }
+
+    #[test]
+    fn xyz_scattered_points_are_not_allocated() {
+        let error = xyz("0 0 1\n1000 0 2\n0.001 1000 3\n", None).unwrap_err();
+        assert_eq!(error.to_string(), "The points are not on a regular grid");
+        assert!(xyz("0 0 1\n0 1 2\n", None).is_err());
+        assert!(xyz("x y z\n", None).is_err());
+    }
+
+    #[test]
+    fn xyz_points_without_a_finite_position_are_skipped() {
+        let grid = xyz("nan 5 1\n0 0 1\n1 0 2\n0 inf 9\n0 1 3\n1 1 NaN\n", None).unwrap();
+        assert_eq!((grid.columns, grid.rows), (2, 2));
+        assert_eq!(grid.values, vec![Some(1.0), Some(2.0), Some(3.0), None]);
+    }
+}
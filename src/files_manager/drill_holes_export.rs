@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use dxf::entities::{Entity, EntityType, Polyline, Vertex};
+use dxf::enums::AcadVersion;
+use dxf::tables::{AppId, Layer};
+use dxf::{Drawing, Point, XData, XDataItem};
+use polars::prelude::*;
+
+use crate::custom_meshes::drill_holes_mesh::DrillHolesData;
+use crate::files_manager::column_mapping::DrillTable;
+use crate::files_manager::csv_parser::{string_column, write_dataframe};
+
+/// Application name of the extended data written on every hole polyline.
+const XDATA_APPLICATION: &str = "DECOROUS";
+
+/// Collar table in mine grid coordinates, with the current collars, their lengths and
+/// the other columns of the loaded collar table.
+pub fn collar_table(data: &DrillHolesData) -> PolarsResult<DataFrame> {
+    let collars = data.holes.iter()
+        .map(|hole| data.real_coordinates(hole.trace.collar()))
+        .collect::<Vec<_>>();
+
+    let mut columns = vec![
+        Series::new("hole-id", data.holes.iter().map(|hole| hole.hole_id.clone()).collect::<Vec<_>>()),
+        Series::new("x", collars.iter().map(|collar| collar[0]).collect::<Vec<_>>()),
+        Series::new("y", collars.iter().map(|collar| collar[1]).collect::<Vec<_>>()),
+        Series::new("z", collars.iter().map(|collar| collar[2]).collect::<Vec<_>>()),
+        Series::new("length", data.holes.iter().map(|hole| hole.trace.length()).collect::<Vec<_>>()),
+    ];
+
+    if data.header.height() > 0 {
+        let header_ids = string_column(&data.header, "hole-id")?;
+        let header_rows = header_ids.iter().enumerate()
+            .filter_map(|(row, id)| Some((id.as_deref()?, row)))
+            .collect::<HashMap<_, _>>();
+
+        for name in data.header.get_column_names() {
+            if ["hole-id", "x", "y", "z", "length"].contains(&name) {
+                continue;
+            }
+            let values = string_column(&data.header, name)?;
+            let joined = data.holes.iter()
+                .map(|hole| header_rows.get(hole.hole_id.as_str()).and_then(|row| values[*row].clone()))
+                .collect::<Vec<_>>();
+            columns.push(Series::new(name, joined));
+        }
+    }
+
+    DataFrame::new(columns)
+}
+
+/// Survey table with one station per trace point, so the holes desurvey the same way again.
+/// Every station runs `to` the next one, the last one to the end of the hole. The station
+/// the trace ends with only carries the one before down to the length, which the collar
+/// table gives again, so it is left out.
+pub fn survey_table(data: &DrillHolesData) -> PolarsResult<DataFrame> {
+    let mut hole_ids = vec![];
+    let mut from = vec![];
+    let mut to = vec![];
+    let mut azimuths = vec![];
+    let mut dips = vec![];
+
+    for hole in data.holes.iter() {
+        let length = hole.trace.length();
+        let mut stations = hole.trace.stations();
+        let projected = match stations.as_slice() {
+            [.., before, last] => last.depth >= length && before.azimuth == last.azimuth && before.dip == last.dip,
+            _ => false,
+        };
+        if projected {
+            stations.pop();
+        }
+        for (i, station) in stations.iter().enumerate() {
+            hole_ids.push(hole.hole_id.clone());
+            from.push(station.depth);
+            to.push(stations.get(i + 1).map_or(length, |next| next.depth));
+            azimuths.push(station.azimuth);
+            dips.push(station.dip);
+        }
+    }
+
+    DataFrame::new(vec![
+        Series::new("hole-id", hole_ids),
+        Series::new("from", from),
+        Series::new("to", to),
+        Series::new("azimuth", azimuths),
+        Series::new("dip", dips),
+    ])
+}
+
+/// Rows of the assay or lithology table placed on a hole, with their length, the mine grid
+/// coordinates of their midpoint and the rock at the middle when the table has none.
+pub fn interval_table(data: &DrillHolesData, table: DrillTable) -> PolarsResult<DataFrame> {
+    let (table, intervals) = data.interval_rows(table)
+        .ok_or_else(|| polars_err!(InvalidOperation: "the {} table has no intervals", table.name()))?;
+
+    let mut placed = vec![false; table.height()];
+    for interval in intervals {
+        placed[interval.row] = true;
+    }
+    let mut df = table.filter(&BooleanChunked::from_slice("placed", &placed))?;
+
+    // Intervals are placed in row order, so they follow the rows left
+    let mut lengths = vec![];
+    let mut middles = vec![];
+    for interval in intervals {
+        lengths.push(interval.to - interval.from);
+        let point = data.holes[interval.hole].trace.point_at((interval.from + interval.to) * 0.5);
+        middles.push(data.real_coordinates(point));
+    }
+
+    if !df.get_column_names().contains(&"length") {
+        df.with_column(Series::new("length", lengths))?;
+    }
+    df.with_column(Series::new("mid_x", middles.iter().map(|middle| middle[0]).collect::<Vec<_>>()))?;
+    df.with_column(Series::new("mid_y", middles.iter().map(|middle| middle[1]).collect::<Vec<_>>()))?;
+    df.with_column(Series::new("mid_z", middles.iter().map(|middle| middle[2]).collect::<Vec<_>>()))?;
+
+    if !df.get_column_names().contains(&"rock") && !data.lithology_intervals.is_empty() {
+        let rocks = data.middle_rocks(intervals, table.height())?
+            .into_iter()
+            .zip(placed)
+            .filter_map(|(rock, placed)| placed.then_some(rock))
+            .collect::<Vec<_>>();
+        df.with_column(Series::new("rock", rocks))?;
+    }
+
+    Ok(df)
+}
+
+/// Writes `collar.csv`, `survey.csv`, `assay.csv` and `lithology.csv` in the folder.
+/// Tables without rows, as the lithology of composites or the samples of a design, are
+/// written with only their role columns, so the folder can always be loaded back.
+pub fn write_tables(data: &DrillHolesData, folder: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let tables = [
+        ("collar.csv", collar_table(data)?),
+        ("survey.csv", survey_table(data)?),
+        ("assay.csv", or_empty(&data.assay, DrillTable::Assay)?),
+        ("lithology.csv", or_empty(&data.lithology, DrillTable::Lithology)?),
+    ];
+
+    for (name, table) in tables.iter() {
+        write_dataframe(table, &folder.join(name).display().to_string())?;
+    }
+    Ok(())
+}
+
+/// The table, or one with the role columns of `table` and no rows when it has none.
+fn or_empty(df: &DataFrame, table: DrillTable) -> PolarsResult<DataFrame> {
+    if df.height() > 0 {
+        return Ok(df.clone());
+    }
+    DataFrame::new(table.roles().iter()
+        .map(|role| Series::new_empty(role.column_name(), &DataType::Utf8))
+        .collect())
+}
+
+/// Writes one 3D polyline per hole, in mine grid coordinates, on the `DRILLHOLES` layer.
+/// Holes of no length are left out. The hole id, collar, length and collar orientation are
+/// attached as extended data.
+pub fn write_dxf(data: &DrillHolesData, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut drawing = Drawing::new();
+    drawing.header.version = AcadVersion::R2000;
+    drawing.add_app_id(AppId {
+        name: XDATA_APPLICATION.to_string(),
+        ..Default::default()
+    });
+    drawing.add_layer(Layer {
+        name: "DRILLHOLES".to_string(),
+        ..Default::default()
+    });
+
+    for hole in data.holes.iter() {
+        // Holes without length, survey past the collar or samples have nothing to draw
+        if hole.trace.length() <= 0.0 {
+            continue;
+        }
+        let mut polyline = Polyline::default();
+        polyline.set_is_3d_polyline(true);
+        for point in hole.trace.segment(0.0, hole.trace.length()) {
+            let [x, y, z] = data.real_coordinates(point);
+            let mut vertex = Vertex::new(Point::new(x as f64, y as f64, z as f64));
+            vertex.set_is_3d_polyline_vertex(true);
+            polyline.add_vertex(&mut drawing, vertex);
+        }
+
+        let [x, y, z] = data.real_coordinates(hole.trace.collar());
+        let station = hole.trace.stations().first().copied();
+        let mut items = vec![
+            XDataItem::Str(format!("hole-id={}", hole.hole_id)),
+            XDataItem::Str(format!("x={:.3}", x)),
+            XDataItem::Str(format!("y={:.3}", y)),
+            XDataItem::Str(format!("z={:.3}", z)),
+            XDataItem::Str(format!("length={:.2}", hole.trace.length())),
+        ];
+        if let Some(station) = station {
+            items.push(XDataItem::Str(format!("azimuth={:.2}", station.azimuth)));
+            items.push(XDataItem::Str(format!("dip={:.2}", station.dip)));
+        }
+
+        let mut entity = Entity::new(EntityType::Polyline(polyline));
+        entity.common.layer = "DRILLHOLES".to_string();
+        entity.common.x_data.push(XData {
+            application_name: XDATA_APPLICATION.to_string(),
+            items,
+        });
+        drawing.add_entity(entity);
+    }
+
+    drawing.save_file(path)?;
+    Ok(())
+}
@@ -0,0 +1,252 @@
+use std::collections::{HashMap, HashSet};
+
+use polars::prelude::*;
+
+use crate::files_manager::column_mapping::DrillTable;
+use crate::files_manager::csv_parser::{f32_column, string_column};
+
+/// Tolerance, in metres, used to compare interval depths.
+const DEPTH_TOLERANCE: f32 = 1e-3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IssueKind {
+    MissingHoleId,
+    DuplicateCollar,
+    CollarWithoutSurvey,
+    UnknownHole,
+    NonNumeric,
+    MissingValue,
+    DipOutOfRange,
+    InvertedInterval,
+    Overlap,
+    Gap,
+    PastLength,
+}
+
+impl IssueKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            IssueKind::MissingHoleId => "Missing hole id",
+            IssueKind::DuplicateCollar => "Duplicate collar",
+            IssueKind::CollarWithoutSurvey => "Collar without survey",
+            IssueKind::UnknownHole => "Unknown hole",
+            IssueKind::NonNumeric => "Non numeric value",
+            IssueKind::MissingValue => "Missing value",
+            IssueKind::DipOutOfRange => "Dip out of range",
+            IssueKind::InvertedInterval => "From >= To",
+            IssueKind::Overlap => "Overlapping interval",
+            IssueKind::Gap => "Gap before interval",
+            IssueKind::PastLength => "Past hole length",
+        }
+    }
+
+    /// Warnings are reported but the record can still be loaded as is.
+    pub fn is_error(self) -> bool {
+        !matches!(self, IssueKind::CollarWithoutSurvey | IssueKind::MissingValue | IssueKind::Gap)
+    }
+}
+
+pub struct ValidationIssue {
+    pub table: DrillTable,
+    /// Dataframe row, starting at 0
+    pub row: usize,
+    pub hole_id: String,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+/// Problems found in the four drill-hole tables once their columns are mapped.
+#[derive(Default)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn errors(&self) -> usize {
+        self.issues.iter().filter(|issue| issue.kind.is_error()).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.issues.len() - self.errors()
+    }
+
+    /// Rows of `table` with at least one error, the ones dropped when skipping bad records.
+    pub fn bad_rows(&self, table: DrillTable) -> HashSet<usize> {
+        self.issues.iter()
+            .filter(|issue| issue.table == table && issue.kind.is_error())
+            .map(|issue| issue.row)
+            .collect()
+    }
+
+    fn push(&mut self, table: DrillTable, row: usize, hole_id: &str, kind: IssueKind, message: String) {
+        self.issues.push(ValidationIssue { table, row, hole_id: hole_id.to_string(), kind, message });
+    }
+
+    /// Flags the rows of `column` that have a value that is not a number, and the empty ones.
+    /// `NaN` and infinite values are not numbers either, they are returned as `None`.
+    fn check_numeric(&mut self, df: &DataFrame, table: DrillTable, ids: &[Option<String>], column: &str, required: bool) -> PolarsResult<Vec<Option<f32>>> {
+        let raw = string_column(df, column)?;
+        let values = f32_column(df, column)?.into_iter()
+            .map(|value| value.filter(|value| value.is_finite()))
+            .collect::<Vec<_>>();
+
+        for (row, (raw, value)) in raw.iter().zip(values.iter()).enumerate() {
+            let hole_id = ids[row].as_deref().unwrap_or("");
+            match (raw.as_deref().map(str::trim), value) {
+                (Some(raw), None) if !raw.is_empty() => self.push(
+                    table, row, hole_id, IssueKind::NonNumeric,
+                    format!("'{}' in {} is not a number", raw, column),
+                ),
+                (_, None) => self.push(
+                    table, row, hole_id,
+                    if required { IssueKind::NonNumeric } else { IssueKind::MissingValue },
+                    format!("{} is empty", column),
+                ),
+                _ => {}
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Checks the collars, the survey and the from/to tables against each other.
+    pub fn validate(
+        header: &DataFrame,
+        survey: &DataFrame,
+        assay: &DataFrame,
+        lithology: &DataFrame,
+        variables: &[String],
+    ) -> PolarsResult<Self> {
+        let mut report = Self::default();
+
+        // Header
+        let header_ids = string_column(header, "hole-id")?;
+        for column in ["x", "y", "z"] {
+            report.check_numeric(header, DrillTable::Header, &header_ids, column, true)?;
+        }
+        let lengths = if header.get_column_names().contains(&"length") {
+            report.check_numeric(header, DrillTable::Header, &header_ids, "length", false)?
+        } else {
+            vec![None; header.height()]
+        };
+
+        let mut collars: HashMap<&str, Option<f32>> = HashMap::new();
+        for (row, hole_id) in header_ids.iter().enumerate() {
+            let Some(hole_id) = hole_id.as_deref() else {
+                report.push(DrillTable::Header, row, "", IssueKind::MissingHoleId, "hole id is empty".to_string());
+                continue;
+            };
+            if collars.insert(hole_id, lengths[row]).is_some() {
+                report.push(DrillTable::Header, row, hole_id, IssueKind::DuplicateCollar, format!("{} is defined more than once", hole_id));
+            }
+        }
+
+        // Survey
+        let survey_ids = string_column(survey, "hole-id")?;
+        let survey_depths = report.check_numeric(survey, DrillTable::Survey, &survey_ids, "from", true)?;
+        report.check_numeric(survey, DrillTable::Survey, &survey_ids, "azimuth", true)?;
+        let dips = report.check_numeric(survey, DrillTable::Survey, &survey_ids, "dip", true)?;
+
+        let mut surveyed = HashSet::new();
+        for (row, hole_id) in survey_ids.iter().enumerate() {
+            let Some(hole_id) = report.known_hole(DrillTable::Survey, row, hole_id, &collars) else {
+                continue;
+            };
+            surveyed.insert(hole_id.to_string());
+
+            if let Some(dip) = dips[row] {
+                if !(-90.0..=90.0).contains(&dip) {
+                    report.push(DrillTable::Survey, row, hole_id, IssueKind::DipOutOfRange, format!("dip {} is outside ±90", dip));
+                }
+            }
+            if let (Some(depth), Some(Some(length))) = (survey_depths[row], collars.get(hole_id)) {
+                if depth > length + DEPTH_TOLERANCE {
+                    report.push(DrillTable::Survey, row, hole_id, IssueKind::PastLength, format!("station at {} past hole length {}", depth, length));
+                }
+            }
+        }
+
+        for (row, hole_id) in header_ids.iter().enumerate() {
+            if let Some(hole_id) = hole_id {
+                if !surveyed.contains(hole_id) {
+                    report.push(DrillTable::Header, row, hole_id, IssueKind::CollarWithoutSurvey, "no survey, drawn as a vertical hole".to_string());
+                }
+            }
+        }
+
+        // From/to tables
+        report.check_intervals(assay, DrillTable::Assay, &collars)?;
+        report.check_intervals(lithology, DrillTable::Lithology, &collars)?;
+
+        let assay_ids = string_column(assay, "hole-id")?;
+        for variable in variables {
+            report.check_numeric(assay, DrillTable::Assay, &assay_ids, &variable.to_lowercase(), false)?;
+        }
+
+        Ok(report)
+    }
+
+    /// Returns the hole id if it has a collar, flagging the row otherwise.
+    fn known_hole<'a>(&mut self, table: DrillTable, row: usize, hole_id: &'a Option<String>, collars: &HashMap<&str, Option<f32>>) -> Option<&'a str> {
+        let Some(hole_id) = hole_id.as_deref() else {
+            self.push(table, row, "", IssueKind::MissingHoleId, "hole id is empty".to_string());
+            return None;
+        };
+        if !collars.contains_key(hole_id) {
+            self.push(table, row, hole_id, IssueKind::UnknownHole, format!("{} has no collar", hole_id));
+            return None;
+        }
+        Some(hole_id)
+    }
+
+    fn check_intervals(&mut self, df: &DataFrame, table: DrillTable, collars: &HashMap<&str, Option<f32>>) -> PolarsResult<()> {
+        let ids = string_column(df, "hole-id")?;
+        let from = self.check_numeric(df, table, &ids, "from", true)?;
+        let to = self.check_numeric(df, table, &ids, "to", true)?;
+
+        let mut by_hole: HashMap<&str, Vec<(usize, f32, f32)>> = HashMap::new();
+        for (row, hole_id) in ids.iter().enumerate() {
+            let Some(hole_id) = self.known_hole(table, row, hole_id, collars) else {
+                continue;
+            };
+            let (Some(from), Some(to)) = (from[row], to[row]) else {
+                continue;
+            };
+            if from >= to {
+                self.push(table, row, hole_id, IssueKind::InvertedInterval, format!("from {} is not above to {}", from, to));
+                continue;
+            }
+            if let Some(Some(length)) = collars.get(hole_id) {
+                if to > length + DEPTH_TOLERANCE {
+                    self.push(table, row, hole_id, IssueKind::PastLength, format!("to {} past hole length {}", to, length));
+                }
+            }
+            by_hole.entry(hole_id).or_default().push((row, from, to));
+        }
+
+        for (hole_id, mut intervals) in by_hole {
+            intervals.sort_by(|a, b| a.1.total_cmp(&b.1));
+            // Compared with the deepest end so far, an interval may lie inside an earlier long one
+            let mut max_to = intervals[0].2;
+            for (row, from, to) in intervals.into_iter().skip(1) {
+                if from < max_to - DEPTH_TOLERANCE {
+                    self.push(table, row, hole_id, IssueKind::Overlap, format!("starts at {} before the previous intervals end at {}", from, max_to));
+                } else if from > max_to + DEPTH_TOLERANCE {
+                    self.push(table, row, hole_id, IssueKind::Gap, format!("{} m gap from {}", from - max_to, max_to));
+                }
+                max_to = max_to.max(to);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes the given rows from `df`.
+pub fn drop_rows(df: &DataFrame, rows: &HashSet<usize>) -> PolarsResult<DataFrame> {
+    if rows.is_empty() {
+        return Ok(df.clone());
+    }
+    let mask = (0..df.height()).map(|row| !rows.contains(&row)).collect::<Vec<_>>();
+    df.filter(&BooleanChunked::from_slice("mask", &mask))
+}
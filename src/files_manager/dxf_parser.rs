@@ -1,3 +1,4 @@
+use std::error::Error;
 use bevy::prelude::*;
 use polars::prelude::*;
 use crate::files_manager::files_porperties::FileProperties;
@@ -16,18 +17,27 @@ impl FileProperties for DxfFile{
 }
 
 impl DxfFile {
-    pub fn get_points(&self) -> Vec<[f64;3]>{
+    /// Points of the `Line`, `LwPolyline` and `Polyline` entities, with the segments joining them
+    /// as pairs of point indices, closing segment included, to triangulate them as breaklines.
+    /// Entities on `skipped_layers`, as the trimming polygons, are left out.
+    pub fn get_breaklines(&self, skipped_layers: &[&str]) -> (Vec<[f64;3]>, Vec<[usize;2]>){
         let mut _points : Vec<[f64;3]> = Vec::new();
+        let mut segments : Vec<[usize;2]> = Vec::new();
         let path = self.path.clone();
         let drawing = Drawing::load_file(&path).unwrap();
         for e in drawing.entities() {
-            match e.specific {
+            if skipped_layers.iter().any(|layer| layer.eq_ignore_ascii_case(&e.common.layer)) {
+                continue;
+            }
+            let first = _points.len();
+            let closed = match e.specific {
                 EntityType::Line(ref _line) => {
                     let p1 = _line.p1.clone();
                     _points.push([p1.x, p1.y, p1.z]);
 
                     let p2 = _line.p2.clone();
                     _points.push([p2.x, p2.y, p2.z]);
+                    false
                 },
                 EntityType::LwPolyline(ref _lw_polyline) => {
                     let vertices = &_lw_polyline.vertices;
@@ -35,6 +45,7 @@ impl DxfFile {
                     for point in vertices{
                         _points.push([point.x, point.y, z]);
                     }
+                    _lw_polyline.is_closed()
                 },
                 EntityType::Polyline(ref p_line) => {
                     let vertices = p_line.vertices();
@@ -42,12 +53,46 @@ impl DxfFile {
                         let p = ver.location.clone();
                         _points.push([p.x, p.y, p.z]);
                     }
+                    p_line.is_closed()
                 },
-                _ => (),
+                _ => false,
+            };
+
+            let last = _points.len();
+            if last - first < 2 {
+                continue;
+            }
+            segments.extend((first..last - 1).map(|i| [i, i + 1]));
+            if closed && last - first > 2 {
+                segments.push([last - 1, first]);
             }
+        }
+        (_points, segments)
+    }
 
+    /// Closed `LwPolyline` and `Polyline` entities of the layer as `[x, y]` polygons, of every
+    /// layer when it is `None`. The layer name is not case sensitive.
+    pub fn get_polygons(&self, layer: Option<&str>) -> Result<Vec<Vec<[f64;2]>>, Box<dyn Error + Send + Sync>>{
+        let mut polygons = Vec::new();
+        let drawing = Drawing::load_file(&self.path)?;
+        for e in drawing.entities() {
+            if layer.map_or(false, |layer| !e.common.layer.eq_ignore_ascii_case(layer)) {
+                continue;
+            }
+            let polygon = match e.specific {
+                EntityType::LwPolyline(ref lw_polyline) if lw_polyline.is_closed() => {
+                    lw_polyline.vertices.iter().map(|point| [point.x, point.y]).collect::<Vec<_>>()
+                },
+                EntityType::Polyline(ref p_line) if p_line.is_closed() => {
+                    p_line.vertices().map(|ver| [ver.location.x, ver.location.y]).collect::<Vec<_>>()
+                },
+                _ => continue,
+            };
+            if polygon.len() > 2 {
+                polygons.push(polygon);
+            }
         }
-        _points
+        Ok(polygons)
     }
 
 }
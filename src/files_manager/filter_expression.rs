@@ -0,0 +1,387 @@
+use std::error::Error;
+
+use polars::prelude::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CompareOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl CompareOp {
+    /// Operator seen from the other side, `a < b` is `b > a`.
+    fn flip(self) -> Self {
+        match self {
+            CompareOp::Less => CompareOp::Greater,
+            CompareOp::LessEqual => CompareOp::GreaterEqual,
+            CompareOp::Greater => CompareOp::Less,
+            CompareOp::GreaterEqual => CompareOp::LessEqual,
+            op => op,
+        }
+    }
+
+    fn apply(self, left: Expr, right: Expr) -> Expr {
+        match self {
+            CompareOp::Equal => left.eq(right),
+            CompareOp::NotEqual => left.neq(right),
+            CompareOp::Less => left.lt(right),
+            CompareOp::LessEqual => left.lt_eq(right),
+            CompareOp::Greater => left.gt(right),
+            CompareOp::GreaterEqual => left.gt_eq(right),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Operand {
+    Column(String),
+    Number(f64),
+    Text(String),
+}
+
+/// Boolean expression over the columns of a drill-hole table, as `cu > 0.3 and rock == 4`.
+///
+/// Comparisons against a number read the column as numbers and comparisons against a quoted
+/// text read it as text, so codes stored either way can be compared with both.
+/// Rows where a value is missing or cannot be read fail the comparison.
+#[derive(Clone, PartialEq, Debug)]
+pub enum FilterExpression {
+    Compare(Operand, CompareOp, Operand),
+    And(Box<FilterExpression>, Box<FilterExpression>),
+    Or(Box<FilterExpression>, Box<FilterExpression>),
+    Not(Box<FilterExpression>),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Identifier(String),
+    Number(f64),
+    Text(String),
+    Op(CompareOp),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, Box<dyn Error + Send + Sync>> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let (token, length) = match (c, next) {
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            ('=', Some('=')) => (Token::Op(CompareOp::Equal), 2),
+            ('=', _) => (Token::Op(CompareOp::Equal), 1),
+            ('!', Some('=')) | ('<', Some('>')) => (Token::Op(CompareOp::NotEqual), 2),
+            ('<', Some('=')) => (Token::Op(CompareOp::LessEqual), 2),
+            ('<', _) => (Token::Op(CompareOp::Less), 1),
+            ('>', Some('=')) => (Token::Op(CompareOp::GreaterEqual), 2),
+            ('>', _) => (Token::Op(CompareOp::Greater), 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('!', _) => (Token::Not, 1),
+            ('\'' | '"', _) => {
+                let Some(end) = chars[i + 1..].iter().position(|other| *other == c) else {
+                    return Err(format!("Missing closing quote for the text at {}", i + 1).into());
+                };
+                (Token::Text(chars[i + 1..i + 1 + end].iter().collect()), end + 2)
+            }
+            _ if c.is_ascii_digit() || c == '.' || (c == '-' && next.map_or(false, |next| next.is_ascii_digit() || next == '.')) => {
+                // A sign is part of the number right after the exponent, as in 1e-3
+                let mut length = 1;
+                while let Some(other) = chars.get(i + length) {
+                    let signed_exponent = (*other == '-' || *other == '+') && matches!(chars[i + length - 1], 'e' | 'E');
+                    if !(other.is_ascii_digit() || *other == '.' || *other == 'e' || *other == 'E' || signed_exponent) {
+                        break;
+                    }
+                    length += 1;
+                }
+                let number = chars[i..i + length].iter().collect::<String>();
+                let value = number.parse::<f64>().map_err(|_| format!("Invalid number {}", number))?;
+                (Token::Number(value), length)
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                // Column names as "hole-id" may have dashes
+                let length = chars[i..].iter()
+                    .take_while(|other| other.is_alphanumeric() || **other == '_' || **other == '-' || **other == '.')
+                    .count();
+                let word = chars[i..i + length].iter().collect::<String>();
+                let token = match word.to_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Identifier(word),
+                };
+                (token, length)
+            }
+            _ => return Err(format!("Unexpected character '{}' at {}", c, i + 1).into()),
+        };
+
+        tokens.push(token);
+        i += length;
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser, `not` binds tighter than `and`, which binds tighter than `or`.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<FilterExpression, Box<dyn Error + Send + Sync>> {
+        let mut expression = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expression = FilterExpression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> Result<FilterExpression, Box<dyn Error + Send + Sync>> {
+        let mut expression = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expression = FilterExpression::And(Box::new(expression), Box::new(self.not()?));
+        }
+        Ok(expression)
+    }
+
+    fn not(&mut self) -> Result<FilterExpression, Box<dyn Error + Send + Sync>> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(FilterExpression::Not(Box::new(self.not()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.next();
+            let expression = self.or()?;
+            if self.next() != Some(Token::Close) {
+                return Err("Missing closing parenthesis".into());
+            }
+            return Ok(expression);
+        }
+
+        let left = self.operand()?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => return Err("Expected a comparison as ==, !=, <, <=, > or >=".into()),
+        };
+        let right = self.operand()?;
+
+        if !matches!(left, Operand::Column(_)) && !matches!(right, Operand::Column(_)) {
+            return Err("Comparisons need at least one column".into());
+        }
+        Ok(FilterExpression::Compare(left, op, right))
+    }
+
+    fn operand(&mut self) -> Result<Operand, Box<dyn Error + Send + Sync>> {
+        match self.next() {
+            Some(Token::Identifier(name)) => Ok(Operand::Column(name)),
+            Some(Token::Number(value)) => Ok(Operand::Number(value)),
+            Some(Token::Text(text)) => Ok(Operand::Text(text)),
+            _ => Err("Expected a column, a number or a quoted text".into()),
+        }
+    }
+}
+
+impl FilterExpression {
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut parser = Parser { tokens: tokenize(text)?, position: 0 };
+        if parser.tokens.is_empty() {
+            return Err("The expression is empty".into());
+        }
+        let expression = parser.or()?;
+        if parser.position < parser.tokens.len() {
+            return Err("Unexpected text after the expression, join comparisons with and / or".into());
+        }
+        Ok(expression)
+    }
+
+    /// Columns the expression reads, as written.
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            FilterExpression::Compare(left, _, right) => [left, right].into_iter()
+                .filter_map(|operand| match operand {
+                    Operand::Column(name) => Some(name.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            FilterExpression::And(left, right) | FilterExpression::Or(left, right) => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+            FilterExpression::Not(expression) => expression.columns(),
+        }
+    }
+
+    /// Polars expression of the filter, column names are matched ignoring case against `columns`.
+    pub fn to_polars(&self, columns: &[&str]) -> Result<Expr, Box<dyn Error + Send + Sync>> {
+        let column = |name: &str| {
+            columns.iter()
+                .find(|column| column.eq_ignore_ascii_case(name))
+                .map(|column| col(column))
+                .ok_or_else(|| format!("Unknown column {}", name))
+        };
+
+        Ok(match self {
+            FilterExpression::Compare(left, op, right) => match (left, right) {
+                (Operand::Column(left), Operand::Column(right)) => op.apply(
+                    column(left)?.cast(DataType::Float64),
+                    column(right)?.cast(DataType::Float64),
+                ),
+                (Operand::Column(name), Operand::Number(value)) => op.apply(column(name)?.cast(DataType::Float64), lit(*value)),
+                (Operand::Column(name), Operand::Text(text)) => op.apply(column(name)?.cast(DataType::Utf8), lit(text.as_str())),
+                (value, Operand::Column(name)) => {
+                    FilterExpression::Compare(Operand::Column(name.clone()), op.flip(), value.clone()).to_polars(columns)?
+                }
+                _ => return Err("Comparisons need at least one column".into()),
+            },
+            FilterExpression::And(left, right) => left.to_polars(columns)?.and(right.to_polars(columns)?),
+            FilterExpression::Or(left, right) => left.to_polars(columns)?.or(right.to_polars(columns)?),
+            FilterExpression::Not(expression) => expression.to_polars(columns)?.not(),
+        })
+    }
+
+    /// Whether each row of `df` passes, rows with missing values fail.
+    pub fn evaluate(&self, df: &DataFrame) -> Result<Vec<bool>, Box<dyn Error + Send + Sync>> {
+        let expression = self.to_polars(&df.get_column_names())?;
+        let passed = df.clone().lazy()
+            .select([expression.alias("passed")])
+            .collect()?;
+        Ok(passed.column("passed")?.bool()?.into_iter()
+            .map(|passed| passed.unwrap_or(false))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compare(column: &str, op: CompareOp, value: f64) -> FilterExpression {
+        FilterExpression::Compare(Operand::Column(column.to_string()), op, Operand::Number(value))
+    }
+
+    fn samples() -> DataFrame {
+        df!(
+            "hole-id" => &["DH1", "DH1", "DH2", "DH2"],
+            "cu" => &[Some(0.5), Some(0.1), None, Some(0.8)],
+            "rock" => &["4", "4", "4", "5"]
+        ).unwrap()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let expression = FilterExpression::parse("a == 1 or b == 2 and not c == 3").unwrap();
+        let expected = FilterExpression::Or(
+            Box::new(compare("a", CompareOp::Equal, 1.0)),
+            Box::new(FilterExpression::And(
+                Box::new(compare("b", CompareOp::Equal, 2.0)),
+                Box::new(FilterExpression::Not(Box::new(compare("c", CompareOp::Equal, 3.0)))),
+            )),
+        );
+        assert_eq!(expression, expected);
+    }
+
+    #[test]
+    fn parentheses_group_first() {
+        let expression = FilterExpression::parse("(a == 1 || b == 2) && c != 3").unwrap();
+        let expected = FilterExpression::And(
+            Box::new(FilterExpression::Or(
+                Box::new(compare("a", CompareOp::Equal, 1.0)),
+                Box::new(compare("b", CompareOp::Equal, 2.0)),
+            )),
+            Box::new(compare("c", CompareOp::NotEqual, 3.0)),
+        );
+        assert_eq!(expression, expected);
+        assert_eq!(expression.columns(), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn quoted_texts() {
+        assert_eq!(
+            tokenize("type == 'RC' or type = \"D D\"").unwrap(),
+            vec![
+                Token::Identifier("type".to_string()),
+                Token::Op(CompareOp::Equal),
+                Token::Text("RC".to_string()),
+                Token::Or,
+                Token::Identifier("type".to_string()),
+                Token::Op(CompareOp::Equal),
+                Token::Text("D D".to_string()),
+            ]
+        );
+        assert!(tokenize("type == 'RC").is_err());
+    }
+
+    #[test]
+    fn numbers_with_exponents() {
+        let numbers = |text: &str| tokenize(text).unwrap().into_iter()
+            .filter_map(|token| match token {
+                Token::Number(value) => Some(value),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(numbers("cu > 1e-3"), vec![1e-3]);
+        assert_eq!(numbers("cu < 2.5E+2"), vec![250.0]);
+        assert_eq!(numbers("z >= -.5 and z <= 3e2"), vec![-0.5, 300.0]);
+        assert!(tokenize("cu > 1e-").is_err());
+        assert!(tokenize("cu > 1.2.3").is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        for text in ["", "  ", "cu >", "cu > 1 au < 2", "(cu > 1", "cu > 1)", "1 > 2", "cu # 1", "and cu > 1"] {
+            assert!(FilterExpression::parse(text).is_err(), "{:?} parsed", text);
+        }
+    }
+
+    #[test]
+    fn evaluates_numbers_and_texts() {
+        let df = samples();
+        let passes = |text: &str| FilterExpression::parse(text).unwrap().evaluate(&df).unwrap();
+
+        assert_eq!(passes("cu > 0.3 and rock == 4"), vec![true, false, false, false]);
+        assert_eq!(passes("0.3 < CU"), vec![true, false, false, true]);
+        assert_eq!(passes("rock == '5' or hole-id == 'DH1'"), vec![true, true, false, true]);
+        // Missing values fail, also when negated
+        assert_eq!(passes("not cu > 0.3"), vec![false, true, false, false]);
+    }
+
+    #[test]
+    fn unknown_columns() {
+        let expression = FilterExpression::parse("cu > 0.3 and au > 1").unwrap();
+        let error = expression.evaluate(&samples()).unwrap_err();
+        assert!(error.to_string().contains("Unknown column au"), "{}", error);
+    }
+}
@@ -0,0 +1,187 @@
+use std::error::Error;
+use std::fs;
+
+use bevy::prelude::*;
+use crate::files_manager::files_porperties::FileProperties;
+
+/// Metres in a foot, LAS depths in feet are converted to metres.
+const FOOT: f32 = 0.3048;
+
+#[derive(Component, Clone)]
+pub struct LasFile {
+    pub path: String
+}
+
+impl FileProperties for LasFile {
+    fn path(&self) -> String {
+        self.path.clone()
+    }
+}
+
+/// A logged curve, one value per depth of the [`LasLog`], missing where the file had the null value.
+#[derive(Clone)]
+pub struct LasCurve {
+    pub mnemonic: String,
+    pub unit: String,
+    pub description: String,
+    pub values: Vec<Option<f32>>,
+}
+
+impl LasCurve {
+    pub fn name(&self) -> String {
+        if self.unit.is_empty() {
+            self.mnemonic.clone()
+        } else {
+            format!("{} ({})", self.mnemonic, self.unit)
+        }
+    }
+}
+
+/// The well information and curves of a LAS 2.0 file.
+#[derive(Clone)]
+pub struct LasLog {
+    pub well: String,
+    pub uwi: String,
+    /// Downhole depth of every sample, in metres
+    pub depths: Vec<f32>,
+    /// Every curve but the depth index
+    pub curves: Vec<LasCurve>,
+}
+
+impl LasFile {
+    pub fn read(&self) -> Result<LasLog, Box<dyn Error + Send + Sync>> {
+        let text = fs::read_to_string(&self.path)?;
+        LasLog::parse(&text)
+    }
+
+    /// Index of the hole of the file, matching the well name, the UWI and then the file name with the hole ids.
+    /// Case, spaces and underscores are ignored.
+    pub fn match_hole(&self, log: &LasLog, hole_ids: &[&str]) -> Option<usize> {
+        let normalize = |name: &str| name.trim().to_uppercase().replace([' ', '_'], "");
+        let hole_ids = hole_ids.iter().map(|hole_id| normalize(hole_id)).collect::<Vec<_>>();
+
+        [Some(log.well.clone()), Some(log.uwi.clone()), self.name()].into_iter()
+            .flatten()
+            .filter(|name| !name.trim().is_empty())
+            .find_map(|name| {
+                let name = normalize(&name);
+                hole_ids.iter().position(|hole_id| *hole_id == name)
+            })
+    }
+}
+
+/// A header line, `MNEM.UNIT  DATA : DESCRIPTION`.
+struct HeaderLine {
+    mnemonic: String,
+    unit: String,
+    data: String,
+    description: String,
+}
+
+fn header_line(line: &str) -> Option<HeaderLine> {
+    let (mnemonic, rest) = line.split_once('.')?;
+    // The unit ends at the first space, the description starts after the last colon
+    let (unit, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    if let Some((unit, description)) = unit.split_once(':') {
+        return Some(HeaderLine {
+            mnemonic: mnemonic.trim().to_string(),
+            unit: unit.to_string(),
+            data: String::new(),
+            description: format!("{} {}", description, rest).trim().to_string(),
+        });
+    }
+    let (data, description) = rest.rsplit_once(':').unwrap_or((rest, ""));
+
+    Some(HeaderLine {
+        mnemonic: mnemonic.trim().to_string(),
+        unit: unit.to_string(),
+        data: data.trim().to_string(),
+        description: description.trim().to_string(),
+    })
+}
+
+impl LasLog {
+    /// Reads the `~W`, `~C` and `~A` sections, wrapped and unwrapped data are both accepted.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut section = ' ';
+        let mut well = String::new();
+        let mut uwi = String::new();
+        let mut null_value = -999.25;
+        let mut curves: Vec<LasCurve> = vec![];
+        let mut data: Vec<f32> = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('~') {
+                section = name.chars().next().unwrap_or(' ').to_ascii_uppercase();
+                continue;
+            }
+
+            match section {
+                'V' => {
+                    if let Some(header) = header_line(line) {
+                        if header.mnemonic.eq_ignore_ascii_case("VERS") && header.data.starts_with('3') {
+                            return Err("LAS 3.0 files are not supported, save them as LAS 2.0".into());
+                        }
+                    }
+                }
+                'W' => {
+                    let Some(header) = header_line(line) else {
+                        continue;
+                    };
+                    match header.mnemonic.to_uppercase().as_str() {
+                        "WELL" => well = header.data,
+                        "UWI" => uwi = header.data,
+                        "NULL" => null_value = header.data.parse().unwrap_or(null_value),
+                        _ => (),
+                    }
+                }
+                'C' => {
+                    if let Some(header) = header_line(line) {
+                        curves.push(LasCurve {
+                            mnemonic: header.mnemonic,
+                            unit: header.unit,
+                            description: header.description,
+                            values: vec![],
+                        });
+                    }
+                }
+                'A' => {
+                    for value in line.split_whitespace() {
+                        let value = value.parse::<f32>()
+                            .map_err(|_| format!("Invalid value {} in line {}", value, number + 1))?;
+                        data.push(value);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        if curves.len() < 2 {
+            return Err("The file has no curves besides the depth".into());
+        }
+        if data.len() % curves.len() != 0 {
+            return Err(format!("The data has {} values, not a multiple of the {} curves", data.len(), curves.len()).into());
+        }
+
+        let depth_curve = curves.remove(0);
+        let scale = match depth_curve.unit.to_uppercase().as_str() {
+            "F" | "FT" | "FEET" => FOOT,
+            _ => 1.0,
+        };
+
+        let mut depths = vec![];
+        for row in data.chunks(curves.len() + 1) {
+            depths.push(row[0] * scale);
+            for (curve, value) in curves.iter_mut().zip(&row[1..]) {
+                let missing = (value - null_value).abs() < 1e-4 || !value.is_finite();
+                curve.values.push(if missing { None } else { Some(*value) });
+            }
+        }
+
+        Ok(Self { well, uwi, depths, curves })
+    }
+}
@@ -1,3 +1,12 @@
 pub mod csv_parser;
 pub mod dxf_parser;
 pub mod files_porperties;
+pub mod column_mapping;
+pub mod drill_holes_validation;
+pub mod filter_expression;
+pub mod las_parser;
+pub mod drill_holes_export;
+pub mod contours_export;
+pub mod orthophoto;
+pub mod dem_parser;
+pub mod surface_export;
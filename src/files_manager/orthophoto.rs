@@ -0,0 +1,144 @@
+use std::error::Error;
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+
+use bevy::prelude::*;
+use image::DynamicImage;
+use tiff::decoder::Decoder;
+use tiff::tags::Tag;
+
+use crate::files_manager::files_porperties::FileProperties;
+
+/// Extensions of the world files next to an image, tried in order after the `<extension>w` one.
+const WORLD_FILE_EXTENSIONS: [&str; 5] = ["wld", "pgw", "jgw", "tfw", "j2w"];
+
+/// Mapping from pixels to mine grid coordinates, as in a world file: the pixel at `column`,
+/// `row` has its centre at `x = a * column + b * row + c` and `y = d * column + e * row + f`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WorldFile {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl WorldFile {
+    /// The six lines of a world file: `a`, `d`, `b`, `e`, `c` and `f`.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let values = text.split_whitespace()
+            .map(|value| value.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()?;
+        let [a, d, b, e, c, f] = values[..] else {
+            return Err(format!("A world file has 6 values, this one has {}", values.len()).into());
+        };
+        Ok(Self { a, b, c, d, e, f })
+    }
+
+    /// Fractional column and row of the mine grid point, pixel centres being at whole numbers.
+    pub fn pixel(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let determinant = self.a * self.e - self.b * self.d;
+        if determinant.abs() < f64::EPSILON {
+            return None;
+        }
+        let (x, y) = (x - self.c, y - self.f);
+        Some(((self.e * x - self.b * y) / determinant, (self.a * y - self.d * x) / determinant))
+    }
+}
+
+#[derive(Component, Clone)]
+pub struct ImageFile {
+    pub path: String
+}
+
+impl FileProperties for ImageFile {
+    fn path(&self) -> String {
+        self.path.clone()
+    }
+}
+
+/// A georeferenced image.
+pub struct Orthophoto {
+    pub image: DynamicImage,
+    pub world_file: WorldFile,
+}
+
+impl ImageFile {
+    /// Reads the image and its georeference, from the GeoTIFF tags or otherwise from the world file next to it.
+    pub fn read(&self) -> Result<Orthophoto, Box<dyn Error + Send + Sync>> {
+        let image = image::open(&self.path)?;
+        let world_file = match self.geotiff()? {
+            Some(world_file) => world_file,
+            None => self.world_file()?,
+        };
+        Ok(Orthophoto { image, world_file })
+    }
+
+    fn extension(&self) -> String {
+        Path::new(&self.path).extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase()
+    }
+
+    /// Georeference of the GeoTIFF tags, `None` for other images and TIFFs without them.
+    /// The raster is taken as pixels covering an area, the GeoTIFF default.
+    fn geotiff(&self) -> Result<Option<WorldFile>, Box<dyn Error + Send + Sync>> {
+        if !matches!(self.extension().as_str(), "tif" | "tiff") {
+            return Ok(None);
+        }
+        let mut decoder = Decoder::new(BufReader::new(fs::File::open(&self.path)?))?;
+
+        if let Some(transformation) = decoder.find_tag(Tag::ModelTransformationTag)? {
+            let m = transformation.into_f64_vec()?;
+            if m.len() < 8 {
+                return Err("The GeoTIFF transformation is not a 4x4 matrix".into());
+            }
+            // The matrix maps the corner of the raster, moved half a pixel to the first centre
+            return Ok(Some(WorldFile {
+                a: m[0],
+                b: m[1],
+                c: m[3] + (m[0] + m[1]) * 0.5,
+                d: m[4],
+                e: m[5],
+                f: m[7] + (m[4] + m[5]) * 0.5,
+            }));
+        }
+
+        let scale = decoder.find_tag(Tag::ModelPixelScaleTag)?.map(|value| value.into_f64_vec()).transpose()?;
+        let tiepoint = decoder.find_tag(Tag::ModelTiepointTag)?.map(|value| value.into_f64_vec()).transpose()?;
+        match (scale, tiepoint) {
+            (Some(scale), Some(tiepoint)) if scale.len() >= 2 && tiepoint.len() >= 6 => {
+                let (i, j, x, y) = (tiepoint[0], tiepoint[1], tiepoint[3], tiepoint[4]);
+                Ok(Some(WorldFile {
+                    a: scale[0],
+                    b: 0.0,
+                    c: x + (0.5 - i) * scale[0],
+                    d: 0.0,
+                    e: -scale[1],
+                    f: y - (0.5 - j) * scale[1],
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// World file with the name of the image, the extension ending in `w` or one of the usual ones.
+    fn world_file(&self) -> Result<WorldFile, Box<dyn Error + Send + Sync>> {
+        let path = Path::new(&self.path);
+        let extension = self.extension();
+        let candidates = [format!("{}w", extension)].into_iter()
+            .chain(WORLD_FILE_EXTENSIONS.iter().map(|extension| extension.to_string()));
+        for candidate in candidates {
+            for candidate in [candidate.clone(), candidate.to_uppercase()] {
+                let world_path = path.with_extension(candidate);
+                if world_path.is_file() {
+                    return WorldFile::parse(&fs::read_to_string(world_path)?);
+                }
+            }
+        }
+        Err(format!("No GeoTIFF tags or world file found for {}", self.name_with_extension().unwrap_or_default()).into())
+    }
+}
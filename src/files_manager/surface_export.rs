@@ -0,0 +1,200 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use bevy::prelude::{GlobalTransform, Mesh, Vec3};
+use bevy::render::mesh::{PrimitiveTopology, VertexAttributeValues};
+use dxf::entities::{Entity, EntityType, Face3D};
+use dxf::enums::AcadVersion;
+use dxf::tables::Layer;
+use dxf::{Drawing, Point};
+
+pub const SURFACE_LAYER: &str = "SURFACE";
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum SurfaceFormat {
+    #[default]
+    Obj,
+    PlyBinary,
+    PlyAscii,
+    /// ASCII, binary STL only holds single precision coordinates, which round mine grid ones
+    Stl,
+    /// One `3DFACE` per triangle
+    Dxf,
+}
+
+impl SurfaceFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            SurfaceFormat::Obj => "Wavefront OBJ",
+            SurfaceFormat::PlyBinary => "PLY, binary",
+            SurfaceFormat::PlyAscii => "PLY, ASCII",
+            SurfaceFormat::Stl => "STL, ASCII",
+            SurfaceFormat::Dxf => "DXF 3DFACE",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            SurfaceFormat::Obj => "obj",
+            SurfaceFormat::PlyBinary | SurfaceFormat::PlyAscii => "ply",
+            SurfaceFormat::Stl => "stl",
+            SurfaceFormat::Dxf => "dxf",
+        }
+    }
+
+    pub fn all() -> [SurfaceFormat; 5] {
+        [SurfaceFormat::Obj, SurfaceFormat::PlyBinary, SurfaceFormat::PlyAscii, SurfaceFormat::Stl, SurfaceFormat::Dxf]
+    }
+}
+
+/// Triangles of a mesh in mine grid coordinates, Z up.
+pub struct SurfaceExport {
+    pub name: String,
+    /// `[east, north, elevation]`
+    pub vertices: Vec<[f64; 3]>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl SurfaceExport {
+    /// Mesh positions moved by the transform, Y-up, with the `[x, y, z]` offsets added back.
+    /// `None` when the mesh is not a triangle list.
+    pub fn from_mesh(name: &str, mesh: &Mesh, transform: &GlobalTransform, offset: [f64; 3]) -> Option<Self> {
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            return None;
+        }
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            return None;
+        };
+
+        let vertices = positions.iter()
+            .map(|position| {
+                let position = transform.transform_point(Vec3::from(*position)).as_dvec3();
+                [position.x + offset[0], position.z + offset[1], position.y + offset[2]]
+            })
+            .collect::<Vec<_>>();
+        let indices = match mesh.indices() {
+            Some(indices) => indices.iter().map(|i| i as u32).collect::<Vec<_>>(),
+            None => (0..vertices.len() as u32).collect(),
+        };
+        // Swapping Y and Z mirrors the mesh, the winding is reversed to keep the faces the same way
+        let triangles = indices.chunks_exact(3).map(|t| [t[0], t[2], t[1]]).collect();
+
+        Some(Self { name: name.to_string(), vertices, triangles })
+    }
+
+    pub fn write(&self, format: SurfaceFormat, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if format == SurfaceFormat::Dxf {
+            return self.write_dxf(path);
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        match format {
+            SurfaceFormat::Obj => self.write_obj(&mut writer)?,
+            SurfaceFormat::PlyBinary => self.write_ply(&mut writer, true)?,
+            SurfaceFormat::PlyAscii => self.write_ply(&mut writer, false)?,
+            SurfaceFormat::Stl => self.write_stl(&mut writer)?,
+            SurfaceFormat::Dxf => unreachable!(),
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn write_obj(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "o {}", self.name)?;
+        for [x, y, z] in self.vertices.iter() {
+            writeln!(writer, "v {} {} {}", x, y, z)?;
+        }
+        for [a, b, c] in self.triangles.iter() {
+            writeln!(writer, "f {} {} {}", a + 1, b + 1, c + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Coordinates are doubles, floats would round the mine grid ones.
+    fn write_ply(&self, writer: &mut impl Write, binary: bool) -> io::Result<()> {
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format {} 1.0", if binary { "binary_little_endian" } else { "ascii" })?;
+        writeln!(writer, "comment {}", self.name)?;
+        writeln!(writer, "element vertex {}", self.vertices.len())?;
+        for axis in ["x", "y", "z"] {
+            writeln!(writer, "property double {}", axis)?;
+        }
+        writeln!(writer, "element face {}", self.triangles.len())?;
+        writeln!(writer, "property list uchar int vertex_indices")?;
+        writeln!(writer, "end_header")?;
+
+        if binary {
+            for vertex in self.vertices.iter() {
+                for value in vertex {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+            }
+            for triangle in self.triangles.iter() {
+                writer.write_all(&[3])?;
+                for index in triangle {
+                    writer.write_all(&(*index as i32).to_le_bytes())?;
+                }
+            }
+        } else {
+            for [x, y, z] in self.vertices.iter() {
+                writeln!(writer, "{} {} {}", x, y, z)?;
+            }
+            for [a, b, c] in self.triangles.iter() {
+                writeln!(writer, "3 {} {} {}", a, b, c)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_stl(&self, writer: &mut impl Write) -> io::Result<()> {
+        let name = self.name.replace(char::is_whitespace, "_");
+        writeln!(writer, "solid {}", name)?;
+        for triangle in self.triangles.iter() {
+            let [a, b, c] = triangle.map(|index| self.vertices[index as usize]);
+            let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+            let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+            let normal = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+            let length = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt().max(f64::MIN_POSITIVE);
+
+            writeln!(writer, "  facet normal {} {} {}", normal[0] / length, normal[1] / length, normal[2] / length)?;
+            writeln!(writer, "    outer loop")?;
+            for [x, y, z] in [a, b, c] {
+                writeln!(writer, "      vertex {} {} {}", x, y, z)?;
+            }
+            writeln!(writer, "    endloop")?;
+            writeln!(writer, "  endfacet")?;
+        }
+        writeln!(writer, "endsolid {}", name)
+    }
+
+    /// Every triangle as a `3DFACE` on the surface layer, the fourth corner repeating the third.
+    fn write_dxf(&self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut drawing = Drawing::new();
+        drawing.header.version = AcadVersion::R2000;
+        drawing.add_layer(Layer {
+            name: SURFACE_LAYER.to_string(),
+            ..Default::default()
+        });
+
+        for triangle in self.triangles.iter() {
+            let [a, b, c] = triangle.map(|index| {
+                let [x, y, z] = self.vertices[index as usize];
+                Point::new(x, y, z)
+            });
+            let face = Face3D {
+                first_corner: a,
+                second_corner: b,
+                third_corner: c.clone(),
+                fourth_corner: c,
+                ..Default::default()
+            };
+            let mut entity = Entity::new(EntityType::Face3D(face));
+            entity.common.layer = SURFACE_LAYER.to_string();
+            drawing.add_entity(entity);
+        }
+
+        drawing.save_file(path)?;
+        Ok(())
+    }
+}
@@ -27,4 +27,30 @@ pub fn interpolate_point_on_the_line(
     ];
 
     Vec3::new(point_1[0], point_1[2], point_1[1])
-}
\ No newline at end of file
+}
+
+/// Closest approach between a ray and the segment `a`-`b`.
+/// Returns the distance along the ray, the position on the segment between 0 and 1,
+/// and the distance between both points.
+pub fn ray_segment_closest(origin: Vec3, direction: Vec3, a: Vec3, b: Vec3) -> (f32, f32, f32) {
+    let direction = direction.normalize_or_zero();
+    let segment = b - a;
+    let offset = origin - a;
+
+    let along = direction.dot(segment);
+    let length_squared = segment.dot(segment);
+    let denominator = length_squared - along * along;
+
+    let mut t = if denominator.abs() < 1e-9 || length_squared < 1e-12 {
+        0.0
+    } else {
+        ((segment.dot(offset) - along * direction.dot(offset)) / denominator).clamp(0.0, 1.0)
+    };
+    let s = (t * along - direction.dot(offset)).max(0.0);
+    if length_squared >= 1e-12 {
+        t = ((segment.dot(offset) + s * along) / length_squared).clamp(0.0, 1.0);
+    }
+
+    let distance = (offset + direction * s - segment * t).length();
+    (s, t, distance)
+}
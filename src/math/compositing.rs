@@ -0,0 +1,203 @@
+use crate::math::desurvey::DrillTrace;
+
+/// Tolerance, in metres, used to ignore residuals and slivers.
+const DEPTH_TOLERANCE: f32 = 1e-3;
+
+/// Largest trace step used to look for bench crossings.
+const BENCH_SEARCH_STEP: f32 = 1.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum CompositeMethod {
+    /// Fixed downhole length from the first sample
+    #[default]
+    FixedLength,
+    /// Fixed downhole length restarted at every lithology contact
+    Lithology,
+    /// Split where the trace crosses the bench elevations
+    Bench,
+}
+
+impl CompositeMethod {
+    pub fn name(self) -> &'static str {
+        match self {
+            CompositeMethod::FixedLength => "Fixed length",
+            CompositeMethod::Lithology => "By lithology",
+            CompositeMethod::Bench => "Bench",
+        }
+    }
+
+    pub fn all() -> [CompositeMethod; 3] {
+        [
+            CompositeMethod::FixedLength,
+            CompositeMethod::Lithology,
+            CompositeMethod::Bench,
+        ]
+    }
+}
+
+/// What to do with the piece left at the end of a run that is shorter than the composite length.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum ResidualHandling {
+    /// Drop the residual
+    #[default]
+    Discard,
+    /// Keep it as a short composite
+    Keep,
+    /// Add it to the last full composite
+    MergeWithPrevious,
+    /// Stretch the composites so the run is split in equal lengths
+    Distribute,
+}
+
+impl ResidualHandling {
+    pub fn name(self) -> &'static str {
+        match self {
+            ResidualHandling::Discard => "Discard",
+            ResidualHandling::Keep => "Keep",
+            ResidualHandling::MergeWithPrevious => "Merge with previous",
+            ResidualHandling::Distribute => "Distribute",
+        }
+    }
+
+    pub fn all() -> [ResidualHandling; 4] {
+        [
+            ResidualHandling::Discard,
+            ResidualHandling::Keep,
+            ResidualHandling::MergeWithPrevious,
+            ResidualHandling::Distribute,
+        ]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CompositeSettings {
+    pub method: CompositeMethod,
+    /// Composite length of the fixed length and lithology methods
+    pub length: f32,
+    pub bench_height: f32,
+    /// Elevation of one of the bench floors, the others are found every `bench_height`
+    pub bench_origin: f32,
+    pub residual: ResidualHandling,
+    /// Fraction of the composite that must be sampled, between 0 and 1
+    pub min_coverage: f32,
+}
+
+impl Default for CompositeSettings {
+    fn default() -> Self {
+        Self {
+            method: CompositeMethod::default(),
+            length: 2.0,
+            bench_height: 10.0,
+            bench_origin: 0.0,
+            residual: ResidualHandling::default(),
+            min_coverage: 0.5,
+        }
+    }
+}
+
+/// Splits `from..to` in composites of `length`, the residual is handled as requested.
+pub fn fixed_length_runs(from: f32, to: f32, length: f32, residual: ResidualHandling) -> Vec<(f32, f32)> {
+    let span = to - from;
+    if span <= DEPTH_TOLERANCE || length <= 0.0 {
+        return vec![];
+    }
+
+    if residual == ResidualHandling::Distribute {
+        let count = (span / length).round().max(1.0) as usize;
+        let step = span / count as f32;
+        return (0..count)
+            .map(|i| {
+                let end = if i + 1 == count { to } else { from + step * (i + 1) as f32 };
+                (from + step * i as f32, end)
+            })
+            .collect();
+    }
+
+    let count = ((span + DEPTH_TOLERANCE) / length).floor() as usize;
+    let mut runs = (0..count)
+        .map(|i| (from + length * i as f32, from + length * (i + 1) as f32))
+        .collect::<Vec<_>>();
+
+    let residual_from = from + length * count as f32;
+    if to - residual_from > DEPTH_TOLERANCE {
+        match residual {
+            ResidualHandling::Keep => runs.push((residual_from, to)),
+            ResidualHandling::MergeWithPrevious => match runs.last_mut() {
+                Some(last) => last.1 = to,
+                None => runs.push((residual_from, to)),
+            },
+            _ => {}
+        }
+    } else if let Some(last) = runs.last_mut() {
+        last.1 = to;
+    }
+
+    runs
+}
+
+/// Splits `from..to` where the trace crosses a bench floor.
+pub fn bench_runs(trace: &DrillTrace, from: f32, to: f32, bench_height: f32, bench_origin: f32) -> Vec<(f32, f32)> {
+    if to - from <= DEPTH_TOLERANCE {
+        return vec![];
+    }
+    if bench_height <= 0.0 {
+        return vec![(from, to)];
+    }
+
+    let bench = |depth: f32| ((trace.point_at(depth).y - bench_origin) / bench_height).floor();
+
+    let mut depths = vec![from];
+    let steps = ((to - from) / BENCH_SEARCH_STEP).ceil().max(1.0) as usize;
+    let step = (to - from) / steps as f32;
+    for i in 0..steps {
+        let (mut low, mut high) = (from + step * i as f32, from + step * (i + 1) as f32);
+        if bench(low) == bench(high) {
+            continue;
+        }
+        // Steps are much shorter than a bench, so there is a single crossing to find
+        for _ in 0..24 {
+            let middle = (low + high) * 0.5;
+            if bench(middle) == bench(low) {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        depths.push((low + high) * 0.5);
+    }
+    depths.push(to);
+
+    depths.windows(2)
+        .filter(|pair| pair[1] - pair[0] > DEPTH_TOLERANCE)
+        .map(|pair| (pair[0], pair[1]))
+        .collect()
+}
+
+/// Sampled length of `from..to`, the samples must not overlap.
+pub fn coverage(samples: &[(f32, f32)], from: f32, to: f32) -> f32 {
+    samples.iter()
+        .map(|(sample_from, sample_to)| (sample_to.min(to) - sample_from.max(from)).max(0.0))
+        .sum()
+}
+
+/// Length weighted average of the samples inside `from..to`. `None` when less than
+/// `min_coverage` of the composite has a value.
+pub fn weighted_average(samples: &[(f32, f32, Option<f32>)], from: f32, to: f32, min_coverage: f32) -> Option<f32> {
+    let mut weight = 0.0;
+    let mut sum = 0.0;
+    for (sample_from, sample_to, value) in samples {
+        let Some(value) = value else {
+            continue;
+        };
+        let overlap = sample_to.min(to) - sample_from.max(from);
+        if overlap > 0.0 {
+            weight += overlap;
+            sum += overlap * value;
+        }
+    }
+
+    if weight <= 0.0 || weight < (to - from) * min_coverage - DEPTH_TOLERANCE {
+        return None;
+    }
+    Some(sum / weight)
+}
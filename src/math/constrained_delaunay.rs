@@ -0,0 +1,377 @@
+use std::collections::{HashMap, HashSet};
+
+/// Twice the signed area of `a`, `b`, `c`, positive when they turn counter-clockwise.
+fn orient(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> f64 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// Whether `d` is strictly inside the circumcircle of the counter-clockwise triangle `a`, `b`, `c`.
+fn in_circle(a: [f64; 2], b: [f64; 2], c: [f64; 2], d: [f64; 2]) -> bool {
+    let [adx, ady] = [a[0] - d[0], a[1] - d[1]];
+    let [bdx, bdy] = [b[0] - d[0], b[1] - d[1]];
+    let [cdx, cdy] = [c[0] - d[0], c[1] - d[1]];
+
+    let determinant = (adx * adx + ady * ady) * (bdx * cdy - cdx * bdy)
+        - (bdx * bdx + bdy * bdy) * (adx * cdy - cdx * ady)
+        + (cdx * cdx + cdy * cdy) * (adx * bdy - bdx * ady);
+    determinant > 0.0
+}
+
+/// Where a breakline leaves its first vertex.
+enum Start {
+    /// Through this vertex, lying on the breakline
+    Vertex(usize),
+    /// Across the edge `p`-`q` of the triangle, `p` on the right of the breakline
+    Crossing(usize, usize, usize),
+}
+
+/// Triangle list with the adjacency needed to insert breaklines.
+/// Triangles are counter-clockwise, removed ones are `None` until their slot is reused.
+struct Triangulation<'a> {
+    points: &'a [[f64; 2]],
+    triangles: Vec<Option<[usize; 3]>>,
+    /// Triangle holding every directed edge
+    edges: HashMap<(usize, usize), usize>,
+    /// A triangle around every vertex
+    vertex_triangles: Vec<Option<usize>>,
+    free: Vec<usize>,
+    /// Breaklines already inserted, smallest index first
+    constrained: HashSet<(usize, usize)>,
+}
+
+impl<'a> Triangulation<'a> {
+    fn add(&mut self, a: usize, b: usize, c: usize) {
+        let triangle = if orient(self.points[a], self.points[b], self.points[c]) < 0.0 { [a, c, b] } else { [a, b, c] };
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.triangles[index] = Some(triangle);
+                index
+            }
+            None => {
+                self.triangles.push(Some(triangle));
+                self.triangles.len() - 1
+            }
+        };
+        for i in 0..3 {
+            self.edges.insert((triangle[i], triangle[(i + 1) % 3]), index);
+            self.vertex_triangles[triangle[i]] = Some(index);
+        }
+    }
+
+    fn remove(&mut self, index: usize) {
+        if let Some(triangle) = self.triangles[index].take() {
+            for i in 0..3 {
+                self.edges.remove(&(triangle[i], triangle[(i + 1) % 3]));
+            }
+            self.free.push(index);
+        }
+    }
+
+    /// The triangle rotated so that `vertex` comes first.
+    fn around(&self, index: usize, vertex: usize) -> [usize; 3] {
+        let triangle = self.triangles[index].unwrap();
+        let i = triangle.iter().position(|v| *v == vertex).unwrap();
+        [triangle[i], triangle[(i + 1) % 3], triangle[(i + 2) % 3]]
+    }
+
+    /// Every triangle around `a`, walking both ways in case `a` is on the hull.
+    fn fan(&self, a: usize) -> Vec<usize> {
+        let Some(first) = self.vertex_triangles[a] else {
+            return vec![];
+        };
+        let mut fan = vec![first];
+
+        let mut current = first;
+        loop {
+            let [_, _, q] = self.around(current, a);
+            match self.edges.get(&(a, q)) {
+                Some(&next) if next == first => return fan,
+                Some(&next) => {
+                    fan.push(next);
+                    current = next;
+                }
+                None => break,
+            }
+        }
+
+        current = first;
+        while let Some(&previous) = self.edges.get(&(self.around(current, a)[1], a)) {
+            fan.push(previous);
+            current = previous;
+        }
+        fan
+    }
+
+    fn start(&self, a: usize, b: usize) -> Option<Start> {
+        let [pa, pb] = [self.points[a], self.points[b]];
+        let ahead = |v: usize| {
+            let pv = self.points[v];
+            orient(pa, pb, pv) == 0.0 && (pv[0] - pa[0]) * (pb[0] - pa[0]) + (pv[1] - pa[1]) * (pb[1] - pa[1]) > 0.0
+        };
+
+        for index in self.fan(a) {
+            let [_, p, q] = self.around(index, a);
+            if ahead(p) {
+                return Some(Start::Vertex(p));
+            }
+            if ahead(q) {
+                return Some(Start::Vertex(q));
+            }
+            if orient(pa, pb, self.points[p]) < 0.0 && orient(pa, pb, self.points[q]) > 0.0 {
+                return Some(Start::Crossing(index, p, q));
+            }
+        }
+        None
+    }
+
+    /// Makes `a`-`b` an edge, removing the triangles it crosses and filling both sides again.
+    /// Returns `false` when it would cross an earlier breakline.
+    fn insert(&mut self, a: usize, b: usize) -> bool {
+        let mut segments = vec![(a, b)];
+        while let Some((a, b)) = segments.pop() {
+            if a == b {
+                continue;
+            }
+            if self.edges.contains_key(&(a, b)) || self.edges.contains_key(&(b, a)) {
+                self.constrained.insert((a.min(b), a.max(b)));
+                continue;
+            }
+
+            let (mut index, mut p, mut q) = match self.start(a, b) {
+                None => return false,
+                Some(Start::Vertex(v)) => {
+                    segments.push((a, v));
+                    segments.push((v, b));
+                    continue;
+                }
+                Some(Start::Crossing(index, p, q)) => (index, p, q),
+            };
+
+            let mut crossed = vec![index];
+            let mut right = vec![p];
+            let mut left = vec![q];
+            let mut split = None;
+            loop {
+                if self.constrained.contains(&(p.min(q), p.max(q))) {
+                    return false;
+                }
+                let Some(&next) = self.edges.get(&(q, p)) else {
+                    return false;
+                };
+                index = next;
+                crossed.push(index);
+
+                let [_, _, r] = self.around(index, q);
+                if r == b {
+                    break;
+                }
+                let side = orient(self.points[a], self.points[b], self.points[r]);
+                if side == 0.0 {
+                    split = Some(r);
+                    break;
+                }
+                if side > 0.0 {
+                    left.push(r);
+                    q = r;
+                } else {
+                    right.push(r);
+                    p = r;
+                }
+            }
+
+            // A vertex on the breakline, both halves are inserted before anything is removed
+            if let Some(r) = split {
+                segments.push((a, r));
+                segments.push((r, b));
+                continue;
+            }
+
+            for index in crossed {
+                self.remove(index);
+            }
+            self.fill(a, b, &right);
+            self.fill(a, b, &left);
+            self.constrained.insert((a.min(b), a.max(b)));
+        }
+        true
+    }
+
+    /// Triangulates the polygon closed by `a`-`b` and the chain of vertices from `a` to `b`,
+    /// keeping the triangles Delaunay inside it.
+    fn fill(&mut self, a: usize, b: usize, chain: &[usize]) {
+        if chain.is_empty() {
+            return;
+        }
+
+        let circle = |c: usize, d: usize| {
+            let [pa, pb, pc] = [self.points[a], self.points[b], self.points[c]];
+            if orient(pa, pb, pc) > 0.0 {
+                in_circle(pa, pb, pc, self.points[d])
+            } else {
+                in_circle(pa, pc, pb, self.points[d])
+            }
+        };
+        let mut c = 0;
+        for i in 1..chain.len() {
+            if circle(chain[c], chain[i]) {
+                c = i;
+            }
+        }
+
+        self.fill(a, chain[c], &chain[..c]);
+        self.fill(chain[c], b, &chain[c + 1..]);
+        self.add(a, b, chain[c]);
+    }
+}
+
+/// Inserts the `breaklines` in a Delaunay triangulation of `points`, so that every one of them
+/// becomes a chain of edges and no triangle crosses it. `triangles` are the flat indices from
+/// `delaunator`, the result keeps their winding.
+///
+/// Breaklines through other points are split at them, and the ones crossing an earlier breakline
+/// are left out. Returns the triangles and the number of breaklines left out.
+pub fn constrained_delaunay(points: &[[f64; 2]], triangles: &[usize], breaklines: &[[usize; 2]]) -> (Vec<usize>, usize) {
+    let mut triangulation = Triangulation {
+        points,
+        triangles: Vec::with_capacity(triangles.len() / 3),
+        edges: HashMap::with_capacity(triangles.len()),
+        vertex_triangles: vec![None; points.len()],
+        free: vec![],
+        constrained: HashSet::new(),
+    };
+
+    let mut clockwise = false;
+    for (i, triangle) in triangles.chunks_exact(3).enumerate() {
+        if i == 0 {
+            clockwise = orient(points[triangle[0]], points[triangle[1]], points[triangle[2]]) < 0.0;
+        }
+        triangulation.add(triangle[0], triangle[1], triangle[2]);
+    }
+
+    // Duplicated points are left out of the triangulation, their breaklines use the one kept
+    let mut kept = HashMap::new();
+    for (i, point) in points.iter().enumerate() {
+        if triangulation.vertex_triangles[i].is_some() {
+            kept.insert((point[0].to_bits(), point[1].to_bits()), i);
+        }
+    }
+    let vertex = |i: usize| -> Option<usize> {
+        let point = points.get(i)?;
+        kept.get(&(point[0].to_bits(), point[1].to_bits())).copied()
+    };
+
+    let mut skipped = 0;
+    for [a, b] in breaklines {
+        let (Some(a), Some(b)) = (vertex(*a), vertex(*b)) else {
+            skipped += 1;
+            continue;
+        };
+        if !triangulation.insert(a, b) {
+            skipped += 1;
+        }
+    }
+
+    let mut result = Vec::with_capacity(triangles.len());
+    for triangle in triangulation.triangles.iter().flatten() {
+        if clockwise {
+            result.extend([triangle[0], triangle[2], triangle[1]]);
+        } else {
+            result.extend(*triangle);
+        }
+    }
+    (result, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_edge(triangles: &[usize], a: usize, b: usize) -> bool {
+        triangles.chunks_exact(3)
+            .any(|t| (0..3).any(|i| (t[i], t[(i + 1) % 3]) == (a, b) || (t[i], t[(i + 1) % 3]) == (b, a)))
+    }
+
+    /// Checks the triangles are counter-clockwise like the input, cover the same area and
+    /// number `2n - h - 2` for `n` points, `h` of them on the hull.
+    fn check(points: &[[f64; 2]], triangles: &[usize], area: f64, n: usize, h: usize) {
+        assert_eq!(triangles.len() / 3, 2 * n - h - 2);
+        let mut total = 0.0;
+        for t in triangles.chunks_exact(3) {
+            let twice = orient(points[t[0]], points[t[1]], points[t[2]]);
+            assert!(twice > 0.0, "{:?} is not counter-clockwise", t);
+            total += twice / 2.0;
+        }
+        assert!((total - area).abs() < 1e-9, "area {} instead of {}", total, area);
+    }
+
+    const SQUARE: [[f64; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+
+    #[test]
+    fn square_with_the_other_diagonal() {
+        let (triangles, skipped) = constrained_delaunay(&SQUARE, &[0, 1, 2, 0, 2, 3], &[[1, 3]]);
+        assert_eq!(skipped, 0);
+        assert!(has_edge(&triangles, 1, 3));
+        assert!(!has_edge(&triangles, 0, 2));
+        check(&SQUARE, &triangles, 1.0, 4, 4);
+    }
+
+    #[test]
+    fn breakline_split_at_a_collinear_vertex() {
+        // Point 2 lies on the breakline 0-1 but is not joined to 0, so the walk from 0 has
+        // to cross the edge 3-4 before finding it
+        let points = [[0.0, 0.0], [4.0, 0.0], [2.0, 0.0], [1.0, 1.0], [1.0, -1.0], [3.0, 1.0], [3.0, -1.0]];
+        let triangles = [0, 4, 3, 4, 2, 3, 4, 6, 2, 6, 1, 2, 1, 5, 2, 5, 3, 2];
+        let (triangles, skipped) = constrained_delaunay(&points, &triangles, &[[0, 1]]);
+        assert_eq!(skipped, 0);
+        assert!(has_edge(&triangles, 0, 2));
+        assert!(has_edge(&triangles, 2, 1));
+        check(&points, &triangles, 6.0, 7, 6);
+    }
+
+    #[test]
+    fn breakline_across_a_grid() {
+        // 4 x 4 grid, the breakline from (0, 1) to (3, 2) crosses five triangles without
+        // going through any other point
+        let points = (0..16).map(|i| [(i % 4) as f64, (i / 4) as f64]).collect::<Vec<_>>();
+        let mut triangles = vec![];
+        for row in 0..3 {
+            for column in 0..3 {
+                let v = row * 4 + column;
+                triangles.extend([v, v + 1, v + 5, v, v + 5, v + 4]);
+            }
+        }
+        let (triangles, skipped) = constrained_delaunay(&points, &triangles, &[[4, 11]]);
+        assert_eq!(skipped, 0);
+        assert!(has_edge(&triangles, 4, 11));
+        check(&points, &triangles, 9.0, 16, 12);
+    }
+
+    #[test]
+    fn crossing_breaklines() {
+        let (triangles, skipped) = constrained_delaunay(&SQUARE, &[0, 1, 2, 0, 2, 3], &[[0, 2], [1, 3]]);
+        assert_eq!(skipped, 1);
+        assert!(has_edge(&triangles, 0, 2));
+        assert!(!has_edge(&triangles, 1, 3));
+        check(&SQUARE, &triangles, 1.0, 4, 4);
+    }
+
+    #[test]
+    fn breakline_on_a_duplicated_point() {
+        // Point 4 repeats point 1 and, as delaunator does, is left out of the triangles
+        let points = [SQUARE[0], SQUARE[1], SQUARE[2], SQUARE[3], SQUARE[1]];
+        let (triangles, skipped) = constrained_delaunay(&points, &[0, 1, 2, 0, 2, 3], &[[4, 3]]);
+        assert_eq!(skipped, 0);
+        assert!(has_edge(&triangles, 1, 3));
+        assert!(!triangles.contains(&4));
+        check(&points, &triangles, 1.0, 4, 4);
+    }
+
+    #[test]
+    fn clockwise_input_stays_clockwise() {
+        let (triangles, skipped) = constrained_delaunay(&SQUARE, &[0, 2, 1, 0, 3, 2], &[[1, 3]]);
+        assert_eq!(skipped, 0);
+        assert!(has_edge(&triangles, 1, 3));
+        let flipped = triangles.chunks_exact(3).flat_map(|t| [t[0], t[2], t[1]]).collect::<Vec<_>>();
+        check(&SQUARE, &flipped, 1.0, 4, 4);
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::{HashMap, VecDeque};
+
+use bevy::math::Vec3;
+
+/// A contour line of a surface. Points are Y-up, in the space of the surface positions.
+#[derive(Clone, Debug)]
+pub struct Contour {
+    /// Elevation in mine grid coordinates, with the offset added back
+    pub elevation: f32,
+    pub points: Vec<Vec3>,
+    /// The last point joins the first one
+    pub closed: bool,
+    pub major: bool,
+}
+
+impl Contour {
+    pub fn length(&self) -> f32 {
+        let open: f32 = self.points.windows(2).map(|pair| pair[0].distance(pair[1])).sum();
+        match (self.closed, self.points.first(), self.points.last()) {
+            (true, Some(first), Some(last)) => open + first.distance(*last),
+            _ => open,
+        }
+    }
+
+    /// Points every `spacing` along the line, starting half a spacing in.
+    pub fn points_every(&self, spacing: f32) -> Vec<Vec3> {
+        let mut points = self.points.clone();
+        if self.closed {
+            points.extend(self.points.first().copied());
+        }
+
+        let mut result = vec![];
+        let mut next = spacing * 0.5;
+        let mut walked = 0.0;
+        for pair in points.windows(2) {
+            let length = pair[0].distance(pair[1]);
+            while next <= walked + length && length > 0.0 {
+                result.push(pair[0].lerp(pair[1], (next - walked) / length));
+                next += spacing;
+            }
+            walked += length;
+        }
+        result
+    }
+}
+
+/// Elevations between `min` and `max` that are multiples of `interval`.
+pub fn contour_levels(min: f32, max: f32, interval: f32) -> Vec<f32> {
+    if interval <= 0.0 || min > max {
+        return vec![];
+    }
+    let first = (min / interval).ceil() as i64;
+    let last = (max / interval).floor() as i64;
+    (first..=last).map(|level| level as f32 * interval).collect()
+}
+
+/// Contours of the triangles every `interval` of mine grid elevation, the positions being
+/// `offset_z` below it. Every `major_every` contour is a major one.
+pub fn contours(positions: &[Vec3], triangles: &[[usize; 3]], offset_z: f32, interval: f32, major_every: usize) -> Vec<Contour> {
+    let used = triangles.iter().flatten().map(|index| positions[*index].y);
+    let (min, max) = used.fold((f32::MAX, f32::MIN), |(min, max), y| (min.min(y), max.max(y)));
+    if min > max {
+        return vec![];
+    }
+
+    contour_levels(min + offset_z, max + offset_z, interval).into_iter()
+        .flat_map(|elevation| {
+            let index = (elevation / interval).round() as i64;
+            let major = major_every > 0 && index.rem_euclid(major_every as i64) == 0;
+            contour_lines(positions, triangles, elevation - offset_z).into_iter()
+                .map(move |(points, closed)| Contour { elevation, points, closed, major })
+        })
+        .collect()
+}
+
+/// Polylines where the triangles cross the `level`, in the space of the positions.
+/// Vertices exactly at the level are taken as above it, so every crossing is on an edge.
+pub fn contour_lines(positions: &[Vec3], triangles: &[[usize; 3]], level: f32) -> Vec<(Vec<Vec3>, bool)> {
+    type EdgeKey = (usize, usize);
+    let key = |a: usize, b: usize| (a.min(b), a.max(b));
+
+    let mut segments: Vec<[(EdgeKey, Vec3); 2]> = vec![];
+    for triangle in triangles {
+        let mut crossings = vec![];
+        for i in 0..3 {
+            let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+            let (pa, pb) = (positions[a], positions[b]);
+            if (pa.y >= level) != (pb.y >= level) {
+                let t = (level - pa.y) / (pb.y - pa.y);
+                let mut point = pa.lerp(pb, t);
+                point.y = level;
+                crossings.push((key(a, b), point));
+            }
+        }
+        if let [first, second] = crossings[..] {
+            segments.push([first, second]);
+        }
+    }
+
+    let mut by_edge: HashMap<EdgeKey, Vec<usize>> = HashMap::new();
+    for (i, segment) in segments.iter().enumerate() {
+        for (edge, _) in segment {
+            by_edge.entry(*edge).or_default().push(i);
+        }
+    }
+
+    let mut used = vec![false; segments.len()];
+    let mut lines = vec![];
+    for start in 0..segments.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let [(start_edge, start_point), (end_edge, end_point)] = segments[start];
+        let mut line = VecDeque::from([start_point, end_point]);
+
+        // Walks from an end of the line through the segments sharing its edge
+        let mut walk = |mut edge: EdgeKey, line: &mut VecDeque<Vec3>, front: bool| -> EdgeKey {
+            while let Some(next) = by_edge[&edge].iter().copied().find(|next| !used[*next]) {
+                used[next] = true;
+                let [(a, pa), (b, pb)] = segments[next];
+                let (other, point) = if a == edge { (b, pb) } else { (a, pa) };
+                if front {
+                    line.push_front(point);
+                } else {
+                    line.push_back(point);
+                }
+                edge = other;
+            }
+            edge
+        };
+
+        let last_edge = walk(end_edge, &mut line, false);
+        let closed = last_edge == start_edge;
+        if closed {
+            // The first point was reached again
+            line.pop_back();
+        } else {
+            walk(start_edge, &mut line, true);
+        }
+        // A peak exactly at the level leaves a line without length
+        let line = Vec::from(line);
+        if line.windows(2).any(|pair| pair[0].distance_squared(pair[1]) > 1e-12) {
+            lines.push((line, closed));
+        }
+    }
+    lines
+}
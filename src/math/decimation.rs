@@ -0,0 +1,294 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use bevy::math::{DVec3, Vec3};
+
+/// Normals closer to the horizontal than this count as this steep, so walls do not weigh
+/// without limit in the vertical error.
+const MIN_NORMAL_Y: f64 = 0.1;
+
+/// When to stop simplifying, the first one reached. With neither the surface is kept as it is.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct DecimationTarget {
+    pub triangles: Option<usize>,
+    /// Largest vertical distance, roughly, between the removed points and the simplified surface
+    pub vertical_tolerance: Option<f32>,
+}
+
+impl DecimationTarget {
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_none() && self.vertical_tolerance.is_none()
+    }
+}
+
+/// Sum of the squared distances to a set of planes, as the upper half of a symmetric 4x4 matrix.
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Plane `a x + b y + c z + d = 0`.
+    fn plane(a: f64, b: f64, c: f64, d: f64) -> Self {
+        Self([a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d])
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (value, other) in self.0.iter_mut().zip(other.0.iter()) {
+            *value += other;
+        }
+    }
+
+    fn sum(&self, other: &Quadric) -> Quadric {
+        let mut sum = *self;
+        sum.add(other);
+        sum
+    }
+
+    fn error(&self, p: DVec3) -> f64 {
+        let q = &self.0;
+        q[0] * p.x * p.x + 2.0 * q[1] * p.x * p.y + 2.0 * q[2] * p.x * p.z + 2.0 * q[3] * p.x
+            + q[4] * p.y * p.y + 2.0 * q[5] * p.y * p.z + 2.0 * q[6] * p.y
+            + q[7] * p.z * p.z + 2.0 * q[8] * p.z
+            + q[9]
+    }
+}
+
+/// Collapse of the vertex `from` onto `to`, queued by cost with the stamps both had.
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    stamps: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    /// Reversed, so the heap pops the cheapest first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Signed area on the horizontal plane, positions being Y-up.
+fn plan_area(a: DVec3, b: DVec3, c: DVec3) -> f64 {
+    (b.x - a.x) * (c.z - a.z) - (c.x - a.x) * (b.z - a.z)
+}
+
+struct Decimation {
+    points: Vec<DVec3>,
+    triangles: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    /// Triangles of every vertex, the removed ones included until it is collapsed
+    incident: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    /// Vertices on the border of the surface, never removed so its outline and the seams with
+    /// other pieces stay the same
+    locked: Vec<bool>,
+    /// Bumped when the vertex changes, to tell queued collapses are outdated
+    stamps: Vec<u32>,
+    heap: BinaryHeap<Collapse>,
+}
+
+impl Decimation {
+    fn neighbours(&self, vertex: usize) -> Vec<usize> {
+        let mut neighbours = self.incident[vertex].iter()
+            .filter(|t| self.alive[**t])
+            .flat_map(|t| self.triangles[*t])
+            .filter(|other| *other != vertex)
+            .collect::<Vec<_>>();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    fn is_outdated(&self, collapse: &Collapse) -> bool {
+        collapse.stamps != (self.stamps[collapse.from as usize], self.stamps[collapse.to as usize])
+    }
+
+    /// Queues the cheapest collapse of the edge allowed by the locks.
+    fn push_edge(&mut self, a: usize, b: usize) {
+        let quadric = self.quadrics[a].sum(&self.quadrics[b]);
+        let onto_b = (!self.locked[a]).then(|| (quadric.error(self.points[b]), a, b));
+        let onto_a = (!self.locked[b]).then(|| (quadric.error(self.points[a]), b, a));
+        let best = match (onto_b, onto_a) {
+            (Some(x), Some(y)) => Some(if x.0 <= y.0 { x } else { y }),
+            (x, y) => x.or(y),
+        };
+        if let Some((cost, from, to)) = best {
+            self.heap.push(Collapse { cost, from: from as u32, to: to as u32, stamps: (self.stamps[from], self.stamps[to]) });
+        }
+    }
+
+    /// Whether collapsing keeps the surface a manifold that does not fold over itself.
+    fn can_collapse(&self, from: usize, to: usize) -> bool {
+        // The shared neighbours have to be the vertices opposite the edge, or the collapse
+        // would pinch the surface
+        let to_neighbours = self.neighbours(to);
+        let shared = self.neighbours(from).into_iter()
+            .filter(|vertex| to_neighbours.contains(vertex))
+            .count();
+        let opposite = self.incident[from].iter()
+            .filter(|t| self.alive[**t] && self.triangles[**t].contains(&to))
+            .count();
+        if shared != opposite {
+            return false;
+        }
+
+        self.incident[from].iter()
+            .filter(|t| self.alive[**t] && !self.triangles[**t].contains(&to))
+            .all(|t| {
+                let triangle = self.triangles[*t];
+                let [a, b, c] = triangle.map(|vertex| self.points[vertex]);
+                let [a2, b2, c2] = triangle.map(|vertex| if vertex == from { self.points[to] } else { self.points[vertex] });
+                let (before, after) = (plan_area(a, b, c), plan_area(a2, b2, c2));
+                before * after > 0.0 && after.abs() > before.abs() * 1e-6
+            })
+    }
+
+    fn collapse(&mut self, from: usize, to: usize) -> usize {
+        let mut removed = 0;
+        for t in std::mem::take(&mut self.incident[from]) {
+            if !self.alive[t] {
+                continue;
+            }
+            if self.triangles[t].contains(&to) {
+                self.alive[t] = false;
+                removed += 1;
+            } else {
+                for vertex in self.triangles[t].iter_mut() {
+                    if *vertex == from {
+                        *vertex = to;
+                    }
+                }
+                self.incident[to].push(t);
+            }
+        }
+        let alive = &self.alive;
+        self.incident[to].retain(|t| alive[*t]);
+        let quadric = self.quadrics[from];
+        self.quadrics[to].add(&quadric);
+        self.stamps[from] += 1;
+        self.stamps[to] += 1;
+        for neighbour in self.neighbours(to) {
+            self.push_edge(to, neighbour);
+        }
+        removed
+    }
+}
+
+/// Quadric error decimation of the triangles by edge collapses, the positions being Y-up.
+/// The errors are measured vertically so the tolerance reads as an elevation one. Vertices are
+/// only removed, never moved, and the ones on the border are kept: the result indexes the same
+/// positions as the input.
+pub fn decimate(positions: &[Vec3], triangles: &[[usize; 3]], target: &DecimationTarget) -> Vec<[usize; 3]> {
+    if target.is_empty() || triangles.len() <= target.triangles.unwrap_or(0) {
+        return triangles.to_vec();
+    }
+
+    // Local numbering of the vertices used, the positions may hold many more
+    let mut local: HashMap<usize, usize> = HashMap::new();
+    let mut global: Vec<usize> = vec![];
+    let mut local_triangles = Vec::with_capacity(triangles.len());
+    for triangle in triangles {
+        let mut local_triangle = [0; 3];
+        for (i, vertex) in triangle.iter().enumerate() {
+            local_triangle[i] = *local.entry(*vertex).or_insert_with(|| {
+                global.push(*vertex);
+                global.len() - 1
+            });
+        }
+        local_triangles.push(local_triangle);
+    }
+
+    let count = global.len();
+    let points = global.iter().map(|vertex| positions[*vertex].as_dvec3()).collect::<Vec<_>>();
+    let mut incident = vec![vec![]; count];
+    let mut edges: HashMap<(usize, usize), u32> = HashMap::new();
+    let mut quadrics = vec![Quadric::default(); count];
+    for (t, triangle) in local_triangles.iter().enumerate() {
+        for i in 0..3 {
+            let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+            incident[a].push(t);
+            *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+
+        let [a, b, c] = triangle.map(|vertex| points[vertex]);
+        let Some(mut normal) = (b - a).cross(c - a).try_normalize() else {
+            continue;
+        };
+        if normal.y < 0.0 {
+            normal = -normal;
+        }
+        // Scaled so the distance to the plane is the vertical one
+        let scale = 1.0 / normal.y.max(MIN_NORMAL_Y);
+        let plane = Quadric::plane(normal.x * scale, normal.y * scale, normal.z * scale, -normal.dot(a) * scale);
+        for vertex in triangle {
+            quadrics[*vertex].add(&plane);
+        }
+    }
+
+    let mut locked = vec![false; count];
+    for ((a, b), uses) in edges.iter() {
+        if *uses == 1 {
+            locked[*a] = true;
+            locked[*b] = true;
+        }
+    }
+
+    let mut decimation = Decimation {
+        points,
+        alive: vec![true; local_triangles.len()],
+        triangles: local_triangles,
+        incident,
+        quadrics,
+        locked,
+        stamps: vec![0; count],
+        heap: BinaryHeap::new(),
+    };
+    for (a, b) in edges.keys() {
+        decimation.push_edge(*a, *b);
+    }
+
+    let max_cost = target.vertical_tolerance.map_or(f64::INFINITY, |tolerance| (tolerance as f64).powi(2));
+    let min_triangles = target.triangles.unwrap_or(0);
+    let mut remaining = decimation.triangles.len();
+    while remaining > min_triangles {
+        // Every collapse outdates a dozen queued ones, dropping them keeps the heap small
+        // enough to be fast. A surface has about one and a half edges per triangle.
+        if decimation.heap.len() > 2 * remaining {
+            let heap = std::mem::take(&mut decimation.heap);
+            decimation.heap = heap.into_iter().filter(|collapse| !decimation.is_outdated(collapse)).collect();
+        }
+        let Some(collapse) = decimation.heap.pop() else {
+            break;
+        };
+        if decimation.is_outdated(&collapse) {
+            continue;
+        }
+        if collapse.cost > max_cost {
+            break;
+        }
+        let (from, to) = (collapse.from as usize, collapse.to as usize);
+        if decimation.can_collapse(from, to) {
+            remaining -= decimation.collapse(from, to);
+        }
+    }
+
+    decimation.triangles.iter()
+        .zip(decimation.alive.iter())
+        .filter(|(_, alive)| **alive)
+        .map(|(triangle, _)| triangle.map(|vertex| global[vertex]))
+        .collect()
+}
@@ -0,0 +1,329 @@
+use bevy::math::Vec3;
+
+/// Method used to join the survey stations of a hole into a 3D trace.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum DesurveyMethod {
+    #[default]
+    MinimumCurvature,
+    BalancedTangential,
+    Tangential,
+}
+
+impl DesurveyMethod {
+    pub fn name(self) -> &'static str {
+        match self {
+            DesurveyMethod::MinimumCurvature => "Minimum curvature",
+            DesurveyMethod::BalancedTangential => "Balanced tangential",
+            DesurveyMethod::Tangential => "Tangential",
+        }
+    }
+
+    pub fn all() -> [DesurveyMethod; 3] {
+        [
+            DesurveyMethod::MinimumCurvature,
+            DesurveyMethod::BalancedTangential,
+            DesurveyMethod::Tangential,
+        ]
+    }
+}
+
+/// A downhole survey measurement, angles in degrees.
+#[derive(Clone, Copy, Debug)]
+pub struct SurveyStation {
+    pub depth: f32,
+    pub azimuth: f32,
+    pub dip: f32,
+}
+
+/// Continuous trace of a hole. `points` are in the same Y-up space as
+/// [`super::analytic_geometry::interpolate_point_on_the_line`], one per entry of `depths`.
+#[derive(Clone, Debug)]
+pub struct DrillTrace {
+    pub method: DesurveyMethod,
+    pub depths: Vec<f32>,
+    pub points: Vec<Vec3>,
+    pub directions: Vec<Vec3>,
+}
+
+/// Unit vector pointing down the hole, Y-up.
+pub fn direction(azimuth: f32, dip: f32) -> Vec3 {
+    let azimuth_rad = azimuth.to_radians();
+    let dip_rad = dip.to_radians();
+
+    Vec3::new(
+        azimuth_rad.sin() * dip_rad.cos(),
+        dip_rad.sin(),
+        azimuth_rad.cos() * dip_rad.cos(),
+    )
+}
+
+/// Azimuth and dip, in degrees, of a unit direction, the inverse of [`direction`].
+pub fn azimuth_dip(direction: Vec3) -> (f32, f32) {
+    let azimuth = direction.x.atan2(direction.z).to_degrees();
+    let dip = direction.y.clamp(-1.0, 1.0).asin().to_degrees();
+    (if azimuth < 0.0 { azimuth + 360.0 } else { azimuth }, dip)
+}
+
+/// Ratio factor of the minimum curvature method for a dogleg angle in radians.
+fn ratio_factor(dogleg: f32) -> f32 {
+    if dogleg.abs() < 1e-6 {
+        1.0
+    } else {
+        2.0 / dogleg * (dogleg / 2.0).tan()
+    }
+}
+
+fn dogleg(d1: Vec3, d2: Vec3) -> f32 {
+    d1.dot(d2).clamp(-1.0, 1.0).acos()
+}
+
+/// Spherical interpolation between two unit directions.
+fn slerp(d1: Vec3, d2: Vec3, t: f32) -> Vec3 {
+    let angle = dogleg(d1, d2);
+    if angle.abs() < 1e-6 {
+        return d1.lerp(d2, t).normalize_or_zero();
+    }
+    let sin = angle.sin();
+    (d1 * ((1.0 - t) * angle).sin() / sin + d2 * (t * angle).sin() / sin).normalize_or_zero()
+}
+
+impl DrillTrace {
+    /// Builds the trace from the collar and every survey station of the hole.
+    ///
+    /// `collar` is `[x, y, z]` in mine grid axes. Stations are sorted by depth; the first
+    /// station orientation is used from the collar down and the last one is projected to
+    /// `length` when it is deeper than the deepest station.
+    pub fn desurvey(
+        collar: [f32; 3],
+        stations: &[SurveyStation],
+        length: Option<f32>,
+        method: DesurveyMethod,
+    ) -> Self {
+        let mut stations = stations.to_vec();
+        stations.sort_by(|a, b| a.depth.partial_cmp(&b.depth).unwrap_or(std::cmp::Ordering::Equal));
+        stations.dedup_by(|a, b| a.depth == b.depth);
+
+        let collar = Vec3::new(collar[0], collar[2], collar[1]);
+
+        if stations.is_empty() {
+            // Vertical hole going down by default
+            stations.push(SurveyStation { depth: 0.0, azimuth: 0.0, dip: -90.0 });
+        }
+
+        if stations[0].depth > 0.0 {
+            let first = stations[0];
+            stations.insert(0, SurveyStation { depth: 0.0, ..first });
+        }
+
+        let last = *stations.last().unwrap();
+        if let Some(length) = length {
+            if length > last.depth {
+                stations.push(SurveyStation { depth: length, ..last });
+            }
+        }
+
+        let mut depths = Vec::with_capacity(stations.len());
+        let mut points = Vec::with_capacity(stations.len());
+        let mut directions = Vec::with_capacity(stations.len());
+
+        depths.push(stations[0].depth);
+        points.push(collar);
+        directions.push(direction(stations[0].azimuth, stations[0].dip));
+
+        for pair in stations.windows(2) {
+            let d1 = direction(pair[0].azimuth, pair[0].dip);
+            let d2 = direction(pair[1].azimuth, pair[1].dip);
+            let delta_depth = pair[1].depth - pair[0].depth;
+
+            let delta = match method {
+                DesurveyMethod::MinimumCurvature => {
+                    (d1 + d2) * (delta_depth / 2.0) * ratio_factor(dogleg(d1, d2))
+                }
+                DesurveyMethod::BalancedTangential => (d1 + d2) * (delta_depth / 2.0),
+                DesurveyMethod::Tangential => d1 * delta_depth,
+            };
+
+            depths.push(pair[1].depth);
+            points.push(*points.last().unwrap() + delta);
+            directions.push(d2);
+        }
+
+        Self { method, depths, points, directions }
+    }
+
+    /// Moves the whole trace, used when the collar is corrected.
+    pub fn translate(&mut self, offset: Vec3) {
+        for point in self.points.iter_mut() {
+            *point += offset;
+        }
+    }
+
+    pub fn length(&self) -> f32 {
+        *self.depths.last().unwrap_or(&0.0)
+    }
+
+    pub fn collar(&self) -> Vec3 {
+        self.points[0]
+    }
+
+    /// Position of the trace at the given downhole depth.
+    pub fn point_at(&self, depth: f32) -> Vec3 {
+        let last = self.depths.len() - 1;
+        if depth <= self.depths[0] || last == 0 {
+            return self.points[0] + self.directions[0] * (depth - self.depths[0]);
+        }
+        if depth >= self.depths[last] {
+            return self.points[last] + self.directions[last] * (depth - self.depths[last]);
+        }
+
+        let i = self.depths.partition_point(|d| *d <= depth) - 1;
+        let (d1, d2) = (self.directions[i], self.directions[i + 1]);
+        let span = self.depths[i + 1] - self.depths[i];
+        let delta_depth = depth - self.depths[i];
+        let t = delta_depth / span;
+
+        match self.method {
+            DesurveyMethod::MinimumCurvature => {
+                // The path between stations is a circular arc, so the direction at a partial
+                // depth is a slerp and the same ratio factor formula applies to the sub-arc.
+                let angle = dogleg(d1, d2) * t;
+                let dt = slerp(d1, d2, t);
+                self.points[i] + (d1 + dt) * (delta_depth / 2.0) * ratio_factor(angle)
+            }
+            _ => self.points[i].lerp(self.points[i + 1], t),
+        }
+    }
+
+    /// One station per trace point, desurveying them with the same method gives back the trace.
+    pub fn stations(&self) -> Vec<SurveyStation> {
+        self.depths.iter().zip(self.directions.iter())
+            .map(|(depth, direction)| {
+                let (azimuth, dip) = azimuth_dip(*direction);
+                SurveyStation { depth: *depth, azimuth, dip }
+            })
+            .collect()
+    }
+
+    /// Points along the trace between two depths, including every station in between,
+    /// so that curved intervals are drawn as several segments.
+    pub fn segment(&self, from: f32, to: f32) -> Vec<Vec3> {
+        let mut points = vec![self.point_at(from)];
+        for depth in self.depths.iter().filter(|d| **d > from && **d < to) {
+            points.push(self.point_at(*depth));
+        }
+        points.push(self.point_at(to));
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    const TOLERANCE: f32 = 1e-3;
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!(actual.distance(expected) < TOLERANCE, "{:?} is not {:?}", actual, expected);
+    }
+
+    fn station(depth: f32, azimuth: f32, dip: f32) -> SurveyStation {
+        SurveyStation { depth, azimuth, dip }
+    }
+
+    #[test]
+    fn vertical_hole() {
+        for method in DesurveyMethod::all() {
+            let trace = DrillTrace::desurvey([100.0, 200.0, 50.0], &[station(0.0, 0.0, -90.0)], Some(120.0), method);
+            assert_close(trace.collar(), Vec3::new(100.0, 50.0, 200.0));
+            assert_close(trace.point_at(120.0), Vec3::new(100.0, -70.0, 200.0));
+            assert_close(trace.point_at(30.0), Vec3::new(100.0, 20.0, 200.0));
+            assert_eq!(trace.length(), 120.0);
+        }
+    }
+
+    #[test]
+    fn hole_without_stations_is_vertical() {
+        let trace = DrillTrace::desurvey([0.0, 0.0, 0.0], &[], Some(10.0), DesurveyMethod::MinimumCurvature);
+        assert_close(trace.point_at(10.0), Vec3::new(0.0, -10.0, 0.0));
+    }
+
+    #[test]
+    fn straight_inclined_hole_is_the_same_for_every_method() {
+        // Due east, 60 degrees down: 50 m east and 86.6 m down every 100 m
+        let stations = [station(0.0, 90.0, -60.0), station(50.0, 90.0, -60.0), station(80.0, 90.0, -60.0)];
+        let expected = Vec3::new(50.0, -100.0 * 60f32.to_radians().sin(), 0.0);
+        for method in DesurveyMethod::all() {
+            let trace = DrillTrace::desurvey([0.0; 3], &stations, Some(100.0), method);
+            assert_close(trace.point_at(100.0), expected);
+            assert_close(trace.point_at(65.0), expected * 0.65);
+        }
+    }
+
+    #[test]
+    fn minimum_curvature_follows_a_vertical_arc() {
+        // From horizontal north to vertical over a quarter circle of 100 m radius, the
+        // method being exact on circular arcs
+        let length = 100.0 * FRAC_PI_2;
+        let stations = [station(0.0, 0.0, 0.0), station(length, 0.0, -90.0)];
+        let trace = DrillTrace::desurvey([0.0; 3], &stations, None, DesurveyMethod::MinimumCurvature);
+        assert_close(trace.point_at(length), Vec3::new(0.0, -100.0, 100.0));
+
+        // Half way the arc has turned 45 degrees
+        let half = 100.0 * std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(trace.point_at(length / 2.0), Vec3::new(0.0, half - 100.0, half));
+    }
+
+    #[test]
+    fn minimum_curvature_follows_a_horizontal_arc() {
+        // Turning from north to east on the level over a quarter circle of 100 m radius
+        let length = 100.0 * FRAC_PI_2;
+        let stations = [station(0.0, 0.0, 0.0), station(length, 90.0, 0.0)];
+        let trace = DrillTrace::desurvey([0.0; 3], &stations, None, DesurveyMethod::MinimumCurvature);
+        assert_close(trace.point_at(length), Vec3::new(100.0, 0.0, 100.0));
+    }
+
+    #[test]
+    fn minimum_curvature_matches_the_textbook_formulas() {
+        // Stations of the usual textbook example, given as inclination from the vertical:
+        // 3500 ft at 15 degrees and 3600 ft at 25 degrees, turning from N20E to N45E
+        let (i1, a1, i2, a2) = (15f64.to_radians(), 20f64.to_radians(), 25f64.to_radians(), 45f64.to_radians());
+        let dogleg = ((i2 - i1).cos() - i1.sin() * i2.sin() * (1.0 - (a2 - a1).cos())).acos();
+        let factor = 2.0 / dogleg * (dogleg / 2.0).tan();
+        let north = 50.0 * (i1.sin() * a1.cos() + i2.sin() * a2.cos()) * factor;
+        let east = 50.0 * (i1.sin() * a1.sin() + i2.sin() * a2.sin()) * factor;
+        let vertical = 50.0 * (i1.cos() + i2.cos()) * factor;
+
+        let stations = [station(3500.0, 20.0, 15.0 - 90.0), station(3600.0, 45.0, 25.0 - 90.0)];
+        let trace = DrillTrace::desurvey([0.0; 3], &stations, None, DesurveyMethod::MinimumCurvature);
+        let delta = trace.point_at(3600.0) - trace.point_at(3500.0);
+        assert_close(delta, Vec3::new(east as f32, -vertical as f32, north as f32));
+    }
+
+    #[test]
+    fn tangential_methods_on_an_arc() {
+        let length = 100.0 * FRAC_PI_2;
+        let stations = [station(0.0, 0.0, 0.0), station(length, 0.0, -90.0)];
+
+        // Averages both directions, a chord at 45 degrees
+        let balanced = DrillTrace::desurvey([0.0; 3], &stations, None, DesurveyMethod::BalancedTangential);
+        assert_close(balanced.point_at(length), Vec3::new(0.0, -length / 2.0, length / 2.0));
+
+        // Keeps the direction of the upper station down to the next one
+        let tangential = DrillTrace::desurvey([0.0; 3], &stations, None, DesurveyMethod::Tangential);
+        assert_close(tangential.point_at(length), Vec3::new(0.0, 0.0, length));
+    }
+
+    #[test]
+    fn stations_give_back_the_trace() {
+        let stations = [station(0.0, 30.0, -70.0), station(40.0, 45.0, -60.0), station(90.0, 70.0, -50.0)];
+        for method in DesurveyMethod::all() {
+            let trace = DrillTrace::desurvey([10.0, 20.0, 30.0], &stations, Some(120.0), method);
+            let again = DrillTrace::desurvey([10.0, 20.0, 30.0], &trace.stations(), None, method);
+            for (a, b) in trace.points.iter().zip(again.points.iter()) {
+                assert_close(*a, *b);
+            }
+        }
+    }
+}
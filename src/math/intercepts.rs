@@ -0,0 +1,151 @@
+/// Tolerance, in metres, used to find gaps between samples.
+const DEPTH_TOLERANCE: f32 = 1e-3;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct InterceptSettings {
+    pub cutoff: f32,
+    /// Shorter intercepts are not reported
+    pub min_length: f32,
+    /// Longest run of samples below the cut-off, or unsampled, kept inside an intercept
+    pub max_dilution: f32,
+    /// Report the higher grade parts of every intercept
+    pub include: bool,
+    pub include_cutoff: f32,
+    pub include_min_length: f32,
+}
+
+impl Default for InterceptSettings {
+    fn default() -> Self {
+        Self {
+            cutoff: 0.5,
+            min_length: 2.0,
+            max_dilution: 2.0,
+            include: false,
+            include_cutoff: 2.0,
+            include_min_length: 1.0,
+        }
+    }
+}
+
+/// A length weighted downhole run above a cut-off grade.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Intercept {
+    pub from: f32,
+    pub to: f32,
+    pub grade: f32,
+    /// Higher grade intercepts inside this one
+    pub includes: Vec<Intercept>,
+}
+
+impl Intercept {
+    pub fn length(&self) -> f32 {
+        self.to - self.from
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Run {
+    from: f32,
+    to: f32,
+    metal: f32,
+}
+
+impl Run {
+    fn grade(&self) -> f32 {
+        self.metal / (self.to - self.from)
+    }
+}
+
+/// Intercepts of the samples `(from, to, grade)` of a hole.
+///
+/// Samples without grade and the gaps between samples count as internal dilution of zero grade.
+/// A dilution run is only kept when it is shorter than `max_dilution` and the intercept including it,
+/// up to the next sample above the cut-off, still averages above the cut-off.
+pub fn intercepts(samples: &[(f32, f32, Option<f32>)], cutoff: f32, min_length: f32, max_dilution: f32) -> Vec<Intercept> {
+    let mut samples = samples.iter()
+        .filter(|sample| sample.1 > sample.0)
+        .copied()
+        .collect::<Vec<_>>();
+    samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut found = vec![];
+    let mut close = |run: Run| {
+        if run.to - run.from >= min_length - DEPTH_TOLERANCE {
+            found.push(Intercept { from: run.from, to: run.to, grade: run.grade(), includes: vec![] });
+        }
+    };
+
+    let mut current: Option<Run> = None;
+    let mut dilution: Option<Run> = None;
+    let mut last_to: Option<f32> = None;
+
+    for (from, to, grade) in samples {
+        // Overlapping samples are clipped to the end of the previous one
+        let from = last_to.map_or(from, |last_to| from.max(last_to));
+        if to - from <= DEPTH_TOLERANCE {
+            continue;
+        }
+
+        if let (Some(last_to), Some(run)) = (last_to, current) {
+            if from - last_to > DEPTH_TOLERANCE {
+                let gap = dilution.get_or_insert(Run { from: run.to, to: run.to, metal: 0.0 });
+                gap.to = from;
+            }
+        }
+        last_to = Some(to);
+
+        let grade = grade.unwrap_or(0.0);
+        let sample = Run { from, to, metal: grade * (to - from) };
+
+        if grade >= cutoff {
+            current = match (current, dilution.take()) {
+                (None, _) => Some(sample),
+                (Some(run), None) => Some(Run { to, metal: run.metal + sample.metal, ..run }),
+                (Some(run), Some(waste)) => {
+                    let extended = Run { to, metal: run.metal + waste.metal + sample.metal, ..run };
+                    if waste.to - waste.from <= max_dilution + DEPTH_TOLERANCE && extended.grade() >= cutoff {
+                        Some(extended)
+                    } else {
+                        close(run);
+                        Some(sample)
+                    }
+                }
+            };
+        } else if let Some(run) = current {
+            let waste = dilution.get_or_insert(Run { from: run.to, to: run.to, metal: 0.0 });
+            waste.to = to;
+            waste.metal += sample.metal;
+            if waste.to - waste.from > max_dilution + DEPTH_TOLERANCE {
+                close(run);
+                current = None;
+                dilution = None;
+            }
+        }
+    }
+
+    if let Some(run) = current {
+        close(run);
+    }
+    found
+}
+
+/// Intercepts of the samples with the settings, with their includes when requested.
+/// Includes that span the whole intercept are not reported.
+pub fn significant_intercepts(samples: &[(f32, f32, Option<f32>)], settings: &InterceptSettings) -> Vec<Intercept> {
+    let mut found = intercepts(samples, settings.cutoff, settings.min_length, settings.max_dilution);
+    if !settings.include {
+        return found;
+    }
+
+    for intercept in found.iter_mut() {
+        let inside = samples.iter()
+            .filter(|sample| sample.1 > intercept.from && sample.0 < intercept.to)
+            .map(|sample| (sample.0.max(intercept.from), sample.1.min(intercept.to), sample.2))
+            .collect::<Vec<_>>();
+        intercept.includes = intercepts(&inside, settings.include_cutoff, settings.include_min_length, settings.max_dilution)
+            .into_iter()
+            .filter(|include| include.length() < intercept.length() - DEPTH_TOLERANCE)
+            .collect();
+    }
+    found
+}
@@ -1 +1,12 @@
-pub mod analytic_geometry;
\ No newline at end of file
+pub mod analytic_geometry;
+pub mod compositing;
+pub mod constrained_delaunay;
+pub mod contours;
+pub mod decimation;
+pub mod desurvey;
+pub mod intercepts;
+pub mod statistics;
+pub mod surface;
+pub mod surface_trim;
+pub mod terrain;
+pub mod volumes;
@@ -0,0 +1,15 @@
+/// Linearly interpolated quantile of the values, `None` when there are no values.
+pub fn quantile(values: &[f32], q: f32) -> Option<f32> {
+    let mut sorted = values.iter().copied().filter(|v| v.is_finite()).collect::<Vec<_>>();
+    if sorted.is_empty() {
+        return None;
+    }
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let position = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f32;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    let t = position - lower as f32;
+
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * t)
+}
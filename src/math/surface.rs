@@ -0,0 +1,143 @@
+use bevy::math::{Vec2, Vec3};
+use bevy::render::mesh::{Mesh, VertexAttributeValues};
+
+/// Triangles of a surface bucketed in a regular grid on the horizontal plane,
+/// to find the elevation under a point without going through every triangle.
+/// Positions are Y-up, as in the topography meshes.
+pub struct SurfaceGrid {
+    positions: Vec<Vec3>,
+    triangles: Vec<[usize; 3]>,
+    origin: Vec2,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<usize>>,
+}
+
+impl SurfaceGrid {
+    pub fn new(positions: Vec<Vec3>, triangles: Vec<[usize; 3]>) -> Self {
+        let min = positions.iter().fold(Vec2::splat(f32::MAX), |min, p| min.min(Vec2::new(p.x, p.z)));
+        let max = positions.iter().fold(Vec2::splat(f32::MIN), |max, p| max.max(Vec2::new(p.x, p.z)));
+        let size = (max - min).max(Vec2::splat(1.0));
+
+        // About two triangles per cell
+        let cell_size = (size.x * size.y / triangles.len().max(1) as f32 * 2.0).sqrt().max(1e-3);
+        let columns = (size.x / cell_size).ceil() as usize + 1;
+        let rows = (size.y / cell_size).ceil() as usize + 1;
+
+        let mut grid = Self { positions, triangles: vec![], origin: min, cell_size, columns, rows, cells: vec![vec![]; columns * rows] };
+
+        for (i, triangle) in triangles.iter().enumerate() {
+            let corners = triangle.map(|index| grid.positions[index]);
+            let low = corners.iter().fold(Vec2::splat(f32::MAX), |low, p| low.min(Vec2::new(p.x, p.z)));
+            let high = corners.iter().fold(Vec2::splat(f32::MIN), |high, p| high.max(Vec2::new(p.x, p.z)));
+            let (first_column, first_row) = grid.cell(low);
+            let (last_column, last_row) = grid.cell(high);
+            for row in first_row..=last_row {
+                for column in first_column..=last_column {
+                    grid.cells[row * grid.columns + column].push(i);
+                }
+            }
+        }
+        grid.triangles = triangles;
+        grid
+    }
+
+    /// Reads the positions and indices of a triangle list mesh.
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            return None;
+        };
+        let indices = mesh.indices()?.iter().collect::<Vec<_>>();
+
+        let positions = positions.iter().map(|p| Vec3::from(*p)).collect();
+        let triangles = indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect();
+        Some(Self::new(positions, triangles))
+    }
+
+    /// Lower and upper corners of the surface on the horizontal plane, as `x`, `z`.
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        (self.origin, self.origin + Vec2::new(self.columns as f32, self.rows as f32) * self.cell_size)
+    }
+
+    fn cell(&self, point: Vec2) -> (usize, usize) {
+        let cell = ((point - self.origin) / self.cell_size).max(Vec2::ZERO);
+        (
+            (cell.x as usize).min(self.columns - 1),
+            (cell.y as usize).min(self.rows - 1),
+        )
+    }
+
+    /// Elevation of the surface under `x`, `z`, `None` outside of it.
+    pub fn elevation(&self, x: f32, z: f32) -> Option<f32> {
+        let point = Vec2::new(x, z);
+        let relative = point - self.origin;
+        if relative.x < 0.0 || relative.y < 0.0 {
+            return None;
+        }
+        let (column, row) = self.cell(point);
+        if relative.x > self.columns as f32 * self.cell_size || relative.y > self.rows as f32 * self.cell_size {
+            return None;
+        }
+
+        self.cells[row * self.columns + column].iter().find_map(|triangle| {
+            let [a, b, c] = self.triangles[*triangle].map(|index| self.positions[index]);
+            let weights = barycentric(point, Vec2::new(a.x, a.z), Vec2::new(b.x, b.z), Vec2::new(c.x, c.z))?;
+            Some(weights.x * a.y + weights.y * b.y + weights.z * c.y)
+        })
+    }
+
+    /// Closest point where the ray hits the surface, in the space of the positions.
+    /// Every triangle is tested, it is meant for clicks rather than every frame.
+    pub fn raycast(&self, origin: Vec3, direction: Vec3) -> Option<Vec3> {
+        self.triangles.iter()
+            .filter_map(|triangle| {
+                let [a, b, c] = triangle.map(|index| self.positions[index]);
+                ray_triangle(origin, direction, a, b, c)
+            })
+            .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|distance| origin + direction * distance)
+    }
+}
+
+/// Barycentric coordinates of `p` in the triangle `a`, `b`, `c`, `None` when it is outside.
+pub fn barycentric(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> Option<Vec3> {
+    let v0 = b - a;
+    let v1 = c - a;
+    let v2 = p - a;
+    let denominator = v0.x * v1.y - v1.x * v0.y;
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+    let v = (v2.x * v1.y - v1.x * v2.y) / denominator;
+    let w = (v0.x * v2.y - v2.x * v0.y) / denominator;
+    let u = 1.0 - v - w;
+
+    let tolerance = -1e-5;
+    (u >= tolerance && v >= tolerance && w >= tolerance).then_some(Vec3::new(u, v, w))
+}
+
+/// Distance along the ray to the triangle `a`, `b`, `c`, from either side (Möller-Trumbore).
+pub fn ray_triangle(origin: Vec3, direction: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < 1e-9 {
+        return None;
+    }
+
+    let offset = origin - a;
+    let u = offset.dot(p) / determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = offset.cross(edge1);
+    let v = direction.dot(q) / determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = edge2.dot(q) / determinant;
+    (distance >= 0.0).then_some(distance)
+}
@@ -0,0 +1,87 @@
+/// How a triangulated surface is trimmed. Polygons are `[x, y]` in the coordinates of the points,
+/// the last vertex joins the first one.
+#[derive(Clone, Default, Debug)]
+pub struct SurfaceTrim {
+    /// Triangles outside of all of them are removed
+    pub boundaries: Vec<Vec<[f64; 2]>>,
+    /// Triangles inside any of them are removed, as ponds or buildings
+    pub holes: Vec<Vec<[f64; 2]>>,
+    /// Triangles with a longer horizontal edge are removed
+    pub max_edge_length: Option<f64>,
+    /// Triangles with a larger circumradius are removed, leaving the alpha shape of the points
+    pub alpha: Option<f64>,
+}
+
+impl SurfaceTrim {
+    pub fn polygons(&self) -> impl Iterator<Item = &Vec<[f64; 2]>> {
+        self.boundaries.iter().chain(self.holes.iter())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boundaries.is_empty() && self.holes.is_empty() && self.max_edge_length.is_none() && self.alpha.is_none()
+    }
+
+    /// Moves the polygons by `-x`, `-y`, as the points when the offsets are removed.
+    pub fn translate(&mut self, x: f64, y: f64) {
+        let polygons = self.boundaries.iter_mut().chain(self.holes.iter_mut());
+        for vertex in polygons.flatten() {
+            vertex[0] -= x;
+            vertex[1] -= y;
+        }
+    }
+
+    /// Whether the triangle `a`, `b`, `c` is kept.
+    pub fn keeps(&self, a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> bool {
+        let distance = |p: [f64; 2], q: [f64; 2]| ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2)).sqrt();
+        let edges = [distance(a, b), distance(b, c), distance(c, a)];
+
+        if let Some(max_edge_length) = self.max_edge_length {
+            if edges.iter().any(|edge| *edge > max_edge_length) {
+                return false;
+            }
+        }
+
+        if let Some(alpha) = self.alpha {
+            // R = abc / 4A, degenerate triangles have an infinite circumcircle
+            let area = ((b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])).abs() / 2.0;
+            if area <= f64::EPSILON || edges[0] * edges[1] * edges[2] / (4.0 * area) > alpha {
+                return false;
+            }
+        }
+
+        // Breaklines follow the polygons, so the centroid tells on which side the triangle is
+        let centroid = [(a[0] + b[0] + c[0]) / 3.0, (a[1] + b[1] + c[1]) / 3.0];
+        if !self.boundaries.is_empty() && !self.boundaries.iter().any(|boundary| point_in_polygon(centroid, boundary)) {
+            return false;
+        }
+        !self.holes.iter().any(|hole| point_in_polygon(centroid, hole))
+    }
+
+    /// The flat triangle indices that are kept.
+    pub fn trim(&self, points: &[[f64; 2]], triangles: &[usize]) -> Vec<usize> {
+        triangles.chunks_exact(3)
+            .filter(|triangle| self.keeps(points[triangle[0]], points[triangle[1]], points[triangle[2]]))
+            .flatten()
+            .copied()
+            .collect()
+    }
+}
+
+/// Even-odd test of `point` against the polygon.
+pub fn point_in_polygon(point: [f64; 2], polygon: &[[f64; 2]]) -> bool {
+    let mut inside = false;
+    let mut previous = match polygon.last() {
+        Some(last) => *last,
+        None => return false,
+    };
+    for vertex in polygon {
+        if (vertex[1] > point[1]) != (previous[1] > point[1]) {
+            let x = vertex[0] + (point[1] - vertex[1]) * (previous[0] - vertex[0]) / (previous[1] - vertex[1]);
+            if point[0] < x {
+                inside = !inside;
+            }
+        }
+        previous = *vertex;
+    }
+    inside
+}
@@ -0,0 +1,39 @@
+use bevy::math::Vec3;
+
+/// Direction the hillshade light comes from, clockwise from north, in degrees.
+pub const SUN_AZIMUTH: f32 = 315.0;
+/// Height of the hillshade light over the horizon, in degrees.
+pub const SUN_ALTITUDE: f32 = 45.0;
+
+/// Normals are Y-up with X east and Z north. The triangulation does not keep a winding,
+/// so the ones pointing down are turned up. `None` for vertices without normal.
+fn upward(normal: Vec3) -> Option<Vec3> {
+    let normal = normal.try_normalize()?;
+    Some(if normal.y < 0.0 { -normal } else { normal })
+}
+
+/// Angle of the surface to the horizontal, in degrees.
+pub fn slope(normal: Vec3) -> Option<f32> {
+    let normal = upward(normal)?;
+    Some(normal.y.clamp(-1.0, 1.0).acos().to_degrees())
+}
+
+/// Direction the surface faces, clockwise from north in degrees. `None` where it is flat.
+pub fn aspect(normal: Vec3) -> Option<f32> {
+    let normal = upward(normal)?;
+    if normal.x.abs() < 1e-6 && normal.z.abs() < 1e-6 {
+        return None;
+    }
+    Some(normal.x.atan2(normal.z).to_degrees().rem_euclid(360.0))
+}
+
+/// Light from `azimuth` and `altitude`, both in degrees, falling on the surface.
+/// 1 facing the light, 0 facing away or in its shadow.
+pub fn hillshade(normal: Vec3, azimuth: f32, altitude: f32) -> f32 {
+    let Some(normal) = upward(normal) else {
+        return 1.0;
+    };
+    let (azimuth, altitude) = (azimuth.to_radians(), altitude.to_radians());
+    let light = Vec3::new(azimuth.sin() * altitude.cos(), altitude.sin(), azimuth.cos() * altitude.cos());
+    normal.dot(light).max(0.0)
+}
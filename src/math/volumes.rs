@@ -0,0 +1,113 @@
+use bevy::math::{DVec2, Vec2};
+
+use super::surface::SurfaceGrid;
+use super::surface_trim::point_in_polygon;
+
+/// A surface in its own local space, with the offsets that bring it to mine grid coordinates.
+pub struct PlacedSurface<'a> {
+    pub grid: &'a SurfaceGrid,
+    /// `[x, y, z]` offsets of the surface
+    pub offset: [f64; 3],
+}
+
+impl PlacedSurface<'_> {
+    /// Mine grid elevation under the mine grid point `x`, `y`.
+    pub fn elevation(&self, x: f64, y: f64) -> Option<f64> {
+        let local = self.grid.elevation((x - self.offset[0]) as f32, (y - self.offset[1]) as f32)?;
+        Some(local as f64 + self.offset[2])
+    }
+
+    /// Mine grid bounds on the horizontal plane.
+    pub fn bounds(&self) -> (DVec2, DVec2) {
+        let offset = DVec2::new(self.offset[0], self.offset[1]);
+        let (min, max) = self.grid.bounds();
+        (min.as_dvec2() + offset, max.as_dvec2() + offset)
+    }
+}
+
+/// Most cells sampled by [`surface_volumes`], finer cells over a large area are made larger
+/// so the calculation stays a matter of seconds.
+pub const MAX_VOLUME_CELLS: f64 = 10_000_000.0;
+
+/// Volumes between a base and a top surface. Cut is where the top is below the base,
+/// as material mined out between two surveys, and fill where it is above.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct VolumeReport {
+    pub cut: f64,
+    pub fill: f64,
+    /// Plan area where both surfaces exist, inside the boundary
+    pub area: f64,
+    /// Side of the cells sampled, larger than the one asked for when it would have made
+    /// more than [`MAX_VOLUME_CELLS`]
+    pub cell_size: f64,
+}
+
+impl VolumeReport {
+    /// Fill minus cut.
+    pub fn net(&self) -> f64 {
+        self.fill - self.cut
+    }
+}
+
+/// Whether the mine grid point is inside one of the polygons, every point is when there are none.
+pub fn inside_boundary(point: [f64; 2], boundary: &[Vec<[f64; 2]>]) -> bool {
+    boundary.is_empty() || boundary.iter().any(|polygon| point_in_polygon(point, polygon))
+}
+
+/// Cut and fill between the surfaces, sampling both at the centre of square cells of
+/// `cell_size` over the area they share. Both are read in mine grid coordinates, so their
+/// offsets do not need to match.
+pub fn surface_volumes(base: &PlacedSurface, top: &PlacedSurface, boundary: &[Vec<[f64; 2]>], cell_size: f64) -> VolumeReport {
+    let (base_min, base_max) = base.bounds();
+    let (top_min, top_max) = top.bounds();
+    let min = base_min.max(top_min);
+    let max = base_max.min(top_max);
+
+    let mut report = VolumeReport { cell_size, ..Default::default() };
+    if cell_size <= 0.0 || min.x >= max.x || min.y >= max.y {
+        return report;
+    }
+
+    let cell_size = cell_size.max(((max.x - min.x) * (max.y - min.y) / MAX_VOLUME_CELLS).sqrt());
+    report.cell_size = cell_size;
+
+    let cell_area = cell_size * cell_size;
+    let columns = ((max.x - min.x) / cell_size).ceil() as usize;
+    let rows = ((max.y - min.y) / cell_size).ceil() as usize;
+    for row in 0..rows {
+        for column in 0..columns {
+            let x = min.x + (column as f64 + 0.5) * cell_size;
+            let y = min.y + (row as f64 + 0.5) * cell_size;
+            if !inside_boundary([x, y], boundary) {
+                continue;
+            }
+            let (Some(base_z), Some(top_z)) = (base.elevation(x, y), top.elevation(x, y)) else {
+                continue;
+            };
+
+            let difference = top_z - base_z;
+            if difference > 0.0 {
+                report.fill += difference * cell_area;
+            } else {
+                report.cut -= difference * cell_area;
+            }
+            report.area += cell_area;
+        }
+    }
+    report
+}
+
+/// Top minus base elevation at every local position of the top surface, positions being Y-up.
+/// `None` where the base is missing or outside the boundary.
+pub fn elevation_differences(base: &PlacedSurface, top_positions: &[[f32; 3]], top_offset: [f64; 3], boundary: &[Vec<[f64; 2]>]) -> Vec<Option<f32>> {
+    top_positions.iter()
+        .map(|position| {
+            let point = Vec2::new(position[0], position[2]).as_dvec2() + DVec2::new(top_offset[0], top_offset[1]);
+            if !inside_boundary([point.x, point.y], boundary) {
+                return None;
+            }
+            let base_z = base.elevation(point.x, point.y)?;
+            Some((position[1] as f64 + top_offset[2] - base_z) as f32)
+        })
+        .collect()
+}
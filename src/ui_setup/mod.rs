@@ -99,6 +99,17 @@ impl Plugin for EditorPlugin {
             use crate::ui_windows::scenes::SceneWindow;
             use crate::ui_windows::load_drills::LoadDrills;
             use crate::ui_windows::nodes_creator::NodesCreator;
+            use crate::ui_windows::lithology_legend::LithologyLegendWindow;
+            use crate::ui_windows::composite_drills::CompositeDrillsWindow;
+            use crate::ui_windows::color_legend::ColorLegendWindow;
+            use crate::ui_windows::intercepts::InterceptsWindow;
+            use crate::ui_windows::downhole_logs::DownholeLogsWindow;
+            use crate::ui_windows::export_drills::ExportDrillsWindow;
+            use crate::ui_windows::plan_holes::PlanHolesWindow;
+            use crate::ui_windows::contours::ContoursWindow;
+            use crate::ui_windows::surface_volumes::SurfaceVolumesWindow;
+            use crate::ui_windows::orthophoto::OrthophotoWindow;
+            use crate::ui_windows::export_surface::ExportSurfaceWindow;
 
             app.add_editor_window::<HierarchyWindow>();
             app.add_editor_window::<AssetsWindow>();
@@ -114,6 +125,17 @@ impl Plugin for EditorPlugin {
             app.add_editor_window::<controls::ControlsWindow>();
             app.add_editor_window::<LoadDrills>();
             app.add_editor_window::<NodesCreator>();
+            app.add_editor_window::<LithologyLegendWindow>();
+            app.add_editor_window::<CompositeDrillsWindow>();
+            app.add_editor_window::<ColorLegendWindow>();
+            app.add_editor_window::<InterceptsWindow>();
+            app.add_editor_window::<DownholeLogsWindow>();
+            app.add_editor_window::<ExportDrillsWindow>();
+            app.add_editor_window::<PlanHolesWindow>();
+            app.add_editor_window::<ContoursWindow>();
+            app.add_editor_window::<SurfaceVolumesWindow>();
+            app.add_editor_window::<OrthophotoWindow>();
+            app.add_editor_window::<ExportSurfaceWindow>();
 
             app.add_plugins(bevy::pbr::wireframe::WireframePlugin);
 
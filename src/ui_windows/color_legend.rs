@@ -0,0 +1,299 @@
+use bevy::prelude::{App, Entity, World};
+use bevy_editor_pls_core::editor_window::{EditorWindow, EditorWindowContext, MenuBarWindow};
+use bevy_inspector_egui::egui::*;
+use egui::{Color32, Rgba, RichText};
+
+use crate::custom_meshes::color_legend::{ColorBin, ColorLegend, ColorScaling, Colormap, GradeLegends};
+use crate::custom_meshes::drill_holes_mesh::{DrillHolesData, DrillHolesLayer, DrillHolesVariable};
+use crate::custom_meshes::lithology_legend::LithologyLegend;
+use crate::custom_meshes::topography_mesh::{TopographyMesh, TopographyShading, STEEP_SLOPE_COLOR};
+use crate::ui_windows::hierarchy::HierarchyWindow;
+
+/// Largest number of rock codes listed in the viewport legend.
+const MAX_LEGEND_ROCKS: usize = 16;
+
+/// What the viewport legend shows.
+#[derive(Clone, PartialEq)]
+enum ActiveLegend {
+    Grade(DrillHolesVariable),
+    /// A shaded topography
+    Topography(Entity),
+}
+
+pub struct ColorLegendWindowState {
+    variable: Option<String>,
+    /// Variable of the layer or shaded topography last selected in the hierarchy, shown in the viewport
+    active: Option<ActiveLegend>,
+    show_in_viewport: bool,
+}
+
+impl Default for ColorLegendWindowState {
+    fn default() -> Self {
+        Self {
+            variable: None,
+            active: None,
+            show_in_viewport: true,
+        }
+    }
+}
+
+pub struct ColorLegendWindow;
+
+impl EditorWindow for ColorLegendWindow {
+    type State = ColorLegendWindowState;
+    const NAME: &'static str = "Grade Legend";
+    const DEFAULT_SIZE: (f32, f32) = (320.0, 420.0);
+    const MENU_BAR: MenuBarWindow = MenuBarWindow::Edit;
+
+    fn ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let state = cx.state_mut::<ColorLegendWindow>().unwrap();
+
+        let mut variables = world.resource::<GradeLegends>().legends.keys().cloned().collect::<Vec<_>>();
+        variables.sort();
+        if variables.is_empty() {
+            ui.label("Load drill holes to edit their colours");
+            return;
+        }
+        if state.variable.as_ref().map_or(true, |variable| !variables.contains(variable)) {
+            state.variable = Some(variables[0].clone());
+        }
+        let variable = state.variable.clone().unwrap();
+
+        ui.horizontal(|ui| {
+            ui.label("Variable");
+            egui::ComboBox::from_id_source("grade_legend_variable")
+                .selected_text(&variable)
+                .show_ui(ui, |ui| {
+                    for option in variables.iter() {
+                        ui.selectable_value(&mut state.variable, Some(option.clone()), option);
+                    }
+                });
+        });
+        ui.checkbox(&mut state.show_in_viewport, "Show legend in the viewport");
+        ui.separator();
+
+        let mut legend = world.resource::<GradeLegends>().legends[&variable].clone();
+        let before = legend.clone();
+
+        legend_editor_ui(ui, "grade_legend", &mut legend);
+
+        if ui.button("Reset range to P25 - P75").clicked() {
+            let mut query = world.query::<&DrillHolesData>();
+            if let Some(data) = query.iter(world).find(|data| data.variables.contains(&variable)) {
+                let default = data.default_legend(&variable);
+                legend.min = default.min;
+                legend.max = default.max;
+            }
+        }
+
+        if legend != before {
+            world.resource_mut::<GradeLegends>().legends.insert(variable, legend);
+        }
+    }
+
+    fn viewport_ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let selected = cx.state::<HierarchyWindow>()
+            .and_then(|hierarchy| match hierarchy.selected.as_slice() {
+                &[entity] => Some(entity),
+                _ => None,
+            })
+            .and_then(|entity| {
+                if let Some(layer) = world.get::<DrillHolesLayer>(entity) {
+                    return Some(ActiveLegend::Grade(layer.variable.clone()));
+                }
+                world.get::<TopographyMesh>(entity)
+                    .filter(|topography| topography.shading != TopographyShading::Flat)
+                    .map(|_| ActiveLegend::Topography(entity))
+            });
+
+        let Some(state) = cx.state_mut::<ColorLegendWindow>() else {
+            return;
+        };
+        if selected.is_some() {
+            state.active = selected;
+        }
+        if !state.show_in_viewport {
+            return;
+        }
+        let Some(active) = state.active.clone() else {
+            return;
+        };
+        if let ActiveLegend::Topography(entity) = active {
+            // Despawned or back to the plain colour
+            if world.get::<TopographyMesh>(entity).map_or(true, |topography| topography.shading == TopographyShading::Flat) {
+                return;
+            }
+        }
+
+        let viewport = ui.clip_rect();
+        egui::Area::new("grade_legend_viewport")
+            .fixed_pos(viewport.left_bottom() + egui::vec2(10.0, -10.0))
+            .pivot(egui::Align2::LEFT_BOTTOM)
+            .interactable(false)
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| match active {
+                    ActiveLegend::Grade(variable) => {
+                        ui.label(RichText::new(variable.name()).strong());
+                        match variable {
+                            DrillHolesVariable::Assay(variable) => {
+                                if let Some(legend) = world.resource::<GradeLegends>().legends.get(&variable) {
+                                    grade_legend_ui(ui, legend);
+                                }
+                            }
+                            DrillHolesVariable::Lithology => {
+                                let legend = world.resource::<LithologyLegend>();
+                                for (code, color) in legend.colors.iter().take(MAX_LEGEND_ROCKS) {
+                                    swatch_ui(ui, to_color32([color[0], color[1], color[2], 1.0]), code);
+                                }
+                                if legend.colors.len() > MAX_LEGEND_ROCKS {
+                                    ui.label(format!("... {} more", legend.colors.len() - MAX_LEGEND_ROCKS));
+                                }
+                            }
+                        }
+                    }
+                    ActiveLegend::Topography(entity) => {
+                        let topography = world.get::<TopographyMesh>(entity).unwrap();
+                        ui.label(RichText::new(topography.shading.title()).strong());
+                        if topography.shading == TopographyShading::Slope {
+                            swatch_ui(ui, to_color32(STEEP_SLOPE_COLOR), &format!("> {:.1} steeper than design", topography.design_slope));
+                        }
+                        grade_legend_ui(ui, &topography.legend);
+                    }
+                });
+            });
+    }
+
+    fn app_setup(app: &mut App) {
+        app.init_resource::<GradeLegends>();
+    }
+}
+
+/// Colormap, scaling, range and cut-off bins of a legend, shared by the grade legends and the
+/// topography shading. `id_source` keeps the widgets of two editors apart.
+pub fn legend_editor_ui(ui: &mut egui::Ui, id_source: &str, legend: &mut ColorLegend) {
+    // Dragging across the range takes a few hundred pixels, whatever the unit
+    let speed = ((legend.max - legend.min).abs() as f64 * 0.005).max(0.001);
+    let bin_count_id = egui::Id::new((id_source, "bin_count"));
+    let mut bin_count = ui.data_mut(|data| *data.get_temp_mut_or(bin_count_id, 5usize));
+
+    egui::Grid::new((id_source, "grid")).show(ui, |ui| {
+        ui.label("Colormap");
+        egui::ComboBox::from_id_source((id_source, "colormap"))
+            .selected_text(legend.colormap.name())
+            .show_ui(ui, |ui| {
+                for colormap in Colormap::all() {
+                    ui.selectable_value(&mut legend.colormap, colormap, colormap.name());
+                }
+            });
+        ui.end_row();
+
+        ui.label("Scaling");
+        egui::ComboBox::from_id_source((id_source, "scaling"))
+            .selected_text(legend.scaling.name())
+            .show_ui(ui, |ui| {
+                for scaling in ColorScaling::all() {
+                    ui.selectable_value(&mut legend.scaling, scaling, scaling.name());
+                }
+            });
+        ui.end_row();
+
+        ui.label("Min");
+        ui.add(egui::DragValue::new(&mut legend.min).speed(speed));
+        ui.end_row();
+
+        ui.label("Max");
+        ui.add(egui::DragValue::new(&mut legend.max).speed(speed));
+        ui.end_row();
+
+        ui.label("Clamp outside values");
+        ui.checkbox(&mut legend.clamp, "");
+        ui.end_row();
+    });
+
+    ui.separator();
+    ui.label(RichText::new("Cut-off bins").strong());
+
+    let mut remove = None;
+    egui::Grid::new((id_source, "bins")).striped(true).show(ui, |ui| {
+        for (i, bin) in legend.bins.iter_mut().enumerate() {
+            ui.label("From");
+            ui.add(egui::DragValue::new(&mut bin.from).speed(speed));
+            ui.color_edit_button_rgb(&mut bin.color);
+            if ui.small_button("\u{1F5D1}").clicked() {
+                remove = Some(i);
+            }
+            ui.end_row();
+        }
+    });
+    if let Some(i) = remove {
+        legend.bins.remove(i);
+    }
+
+    ui.horizontal(|ui| {
+        if ui.button("Add").clicked() {
+            let from = legend.bins.last().map_or(legend.min, |bin| bin.from + (legend.max - legend.min) * 0.1);
+            let color = legend.colormap.sample(legend.normalize(from));
+            legend.bins.push(ColorBin { from, color: [color[0], color[1], color[2]] });
+        }
+        if ui.button("From ramp").clicked() {
+            legend.bins = legend.equal_bins(bin_count);
+        }
+        ui.add(egui::DragValue::new(&mut bin_count).clamp_range(1..=20));
+        if ui.button("Clear").clicked() {
+            legend.bins.clear();
+        }
+    });
+    legend.sort_bins();
+    ui.data_mut(|data| data.insert_temp(bin_count_id, bin_count));
+}
+
+fn to_color32(color: [f32; 4]) -> Color32 {
+    Color32::from(Rgba::from_rgb(color[0], color[1], color[2]))
+}
+
+fn swatch_ui(ui: &mut egui::Ui, color: Color32, text: &str) {
+    ui.horizontal(|ui| {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(14.0, 14.0), egui::Sense::hover());
+        ui.painter().rect_filled(rect, 2.0, color);
+        ui.label(text);
+    });
+}
+
+/// Bins as swatches with their ranges, otherwise a vertical ramp from max to min.
+pub fn grade_legend_ui(ui: &mut egui::Ui, legend: &ColorLegend) {
+    if !legend.bins.is_empty() {
+        for (i, bin) in legend.bins.iter().enumerate().rev() {
+            let text = match legend.bins.get(i + 1) {
+                Some(next) => format!("{:.3} - {:.3}", bin.from, next.from),
+                None => format!("\u{2265} {:.3}", bin.from),
+            };
+            swatch_ui(ui, to_color32([bin.color[0], bin.color[1], bin.color[2], 1.0]), &text);
+        }
+        return;
+    }
+
+    let steps = 32;
+    ui.horizontal(|ui| {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(16.0, 128.0), egui::Sense::hover());
+        let step_height = rect.height() / steps as f32;
+        for i in 0..steps {
+            let t = 1.0 - (i as f32 + 0.5) / steps as f32;
+            let top = rect.top() + step_height * i as f32;
+            let step = egui::Rect::from_min_max(egui::pos2(rect.left(), top), egui::pos2(rect.right(), top + step_height + 0.5));
+            ui.painter().rect_filled(step, 0.0, to_color32(legend.colormap.sample(t)));
+        }
+
+        ui.vertical(|ui| {
+            ui.set_height(rect.height());
+            let middle = match legend.scaling {
+                ColorScaling::Linear => (legend.min + legend.max) * 0.5,
+                ColorScaling::Log => (legend.min.max(1e-6) * legend.max.max(1e-6)).sqrt(),
+            };
+            ui.label(format!("{:.3}", legend.max));
+            ui.add_space(rect.height() * 0.5 - 2.0 * ui.spacing().interact_size.y);
+            ui.label(format!("{:.3}", middle));
+            ui.add_space(rect.height() * 0.5 - 2.0 * ui.spacing().interact_size.y);
+            ui.label(format!("{:.3}", legend.min));
+        });
+    });
+}
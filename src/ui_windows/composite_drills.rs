@@ -0,0 +1,143 @@
+use std::error::Error;
+
+use bevy::prelude::{BuildWorldChildren, Entity, Name, World};
+use bevy_editor_pls_core::editor_window::{EditorWindow, EditorWindowContext, MenuBarWindow};
+use bevy_inspector_egui::egui::*;
+use egui::RichText;
+
+use crate::custom_meshes::drill_holes_mesh::{spawn_drill_holes, DrillHolesData};
+use crate::files_manager::csv_parser::write_dataframe;
+use crate::math::compositing::{CompositeMethod, CompositeSettings, ResidualHandling};
+
+#[derive(Default)]
+pub struct CompositeDrillsWindowState {
+    drill_holes: Option<Entity>,
+    settings: CompositeSettings,
+    composite_result: Option<Result<(), Box<dyn Error + Send + Sync>>>,
+}
+
+pub struct CompositeDrillsWindow;
+
+impl EditorWindow for CompositeDrillsWindow {
+    type State = CompositeDrillsWindowState;
+    const NAME: &'static str = "Composite Drill Holes";
+    const DEFAULT_SIZE: (f32, f32) = (350.0, 300.0);
+    const MENU_BAR: MenuBarWindow = MenuBarWindow::Edit;
+
+    fn ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let state = cx.state_mut::<CompositeDrillsWindow>().unwrap();
+
+        ui.label("Drill holes to composite: ");
+        let mut query = world.query::<(Entity, &Name, &DrillHolesData)>();
+        for (entity, name, _) in query.iter(world) {
+            let selected = state.drill_holes == Some(entity);
+            if ui.selectable_label(selected, name.as_str()).clicked() {
+                state.drill_holes = Some(entity);
+            }
+        }
+
+        ui.separator();
+
+        let settings = &mut state.settings;
+        egui::Grid::new("composite_settings").show(ui, |ui| {
+            ui.label("Method");
+            egui::ComboBox::from_id_source("composite_method")
+                .selected_text(settings.method.name())
+                .show_ui(ui, |ui| {
+                    for method in CompositeMethod::all() {
+                        ui.selectable_value(&mut settings.method, method, method.name());
+                    }
+                });
+            ui.end_row();
+
+            if settings.method == CompositeMethod::Bench {
+                ui.label("Bench height");
+                ui.add(egui::DragValue::new(&mut settings.bench_height).clamp_range(0.5..=100.0).speed(0.1).suffix(" m"));
+                ui.end_row();
+
+                ui.label("Bench floor elevation");
+                ui.add(egui::DragValue::new(&mut settings.bench_origin).speed(1.0).suffix(" m"));
+                ui.end_row();
+            } else {
+                ui.label("Length");
+                ui.add(egui::DragValue::new(&mut settings.length).clamp_range(0.1..=100.0).speed(0.1).suffix(" m"));
+                ui.end_row();
+
+                ui.label("Residual");
+                egui::ComboBox::from_id_source("composite_residual")
+                    .selected_text(settings.residual.name())
+                    .show_ui(ui, |ui| {
+                        for residual in ResidualHandling::all() {
+                            ui.selectable_value(&mut settings.residual, residual, residual.name());
+                        }
+                    });
+                ui.end_row();
+            }
+
+            ui.label("Minimum coverage");
+            ui.add(egui::Slider::new(&mut settings.min_coverage, 0.0..=1.0));
+            ui.end_row();
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if ui.button("Composite").clicked() {
+                state.composite_result = Some(composite(world, state.drill_holes, &state.settings));
+            }
+            if ui.button("Export CSV").clicked() {
+                state.composite_result = Some(export_csv(world, state.drill_holes));
+            }
+        });
+
+        if let Some(status) = &state.composite_result {
+            match status {
+                Ok(()) => {
+                    ui.label(RichText::new("Success!").color(egui::Color32::GREEN));
+                }
+                Err(error) => {
+                    ui.label(RichText::new(error.to_string()).color(egui::Color32::RED));
+                }
+            }
+        }
+    }
+}
+
+fn composite(
+    world: &mut World,
+    drill_holes: Option<Entity>,
+    settings: &CompositeSettings,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(drill_holes) = drill_holes else {
+        return Err("Select the drill holes to composite".into());
+    };
+    let Some(data) = world.get::<DrillHolesData>(drill_holes) else {
+        return Err("The selected drill holes no longer exist".into());
+    };
+
+    let composites = data.composite(settings)?;
+    if composites.assay_intervals.is_empty() {
+        return Err("No composite reached the minimum coverage".into());
+    }
+
+    let name = match settings.method {
+        CompositeMethod::Bench => format!("Composites {} m benches", settings.bench_height),
+        method => format!("Composites {} m {}", settings.length, method.name().to_lowercase()),
+    };
+    let composites_id = spawn_drill_holes(world, composites, &name, false);
+    world.entity_mut(drill_holes).add_child(composites_id);
+
+    Ok(())
+}
+
+/// Saves the assay table of the selected drill holes, composites keep their length and coverage.
+fn export_csv(world: &mut World, drill_holes: Option<Entity>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(data) = drill_holes.and_then(|entity| world.get::<DrillHolesData>(entity)) else {
+        return Err("Select the composites to export".into());
+    };
+
+    if let Some(path) = rfd::FileDialog::new().add_filter("Composites (csv)", &["csv"]).save_file() {
+        write_dataframe(&data.assay, &path.display().to_string())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use std::error::Error;
+
+use bevy::prelude::*;
+use bevy_editor_pls_core::editor_window::{EditorWindow, EditorWindowContext, MenuBarWindow};
+use bevy_inspector_egui::egui;
+use egui::{Color32, FontId, Rect, RichText};
+
+use crate::custom_meshes::contour_mesh::{spawn_contours, ContourLines};
+use crate::custom_meshes::topography_mesh::TopographyMesh;
+use crate::files_manager::contours_export::write_contours_dxf;
+use crate::ui_windows::cameras::ActiveEditorCamera;
+
+pub struct ContoursWindowState {
+    topography: Option<Entity>,
+    interval: f32,
+    major_every: usize,
+    label_spacing: f32,
+    contours: Option<Entity>,
+    contours_result: Option<Result<(), Box<dyn Error + Send + Sync>>>,
+}
+
+impl Default for ContoursWindowState {
+    fn default() -> Self {
+        Self {
+            topography: None,
+            interval: 5.0,
+            major_every: 5,
+            label_spacing: 250.0,
+            contours: None,
+            contours_result: None,
+        }
+    }
+}
+
+pub struct ContoursWindow;
+
+impl EditorWindow for ContoursWindow {
+    type State = ContoursWindowState;
+    const NAME: &'static str = "Contour Lines";
+    const DEFAULT_SIZE: (f32, f32) = (350.0, 300.0);
+    const MENU_BAR: MenuBarWindow = MenuBarWindow::Edit;
+
+    fn ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let state = cx.state_mut::<ContoursWindow>().unwrap();
+
+        ui.label("Topography: ");
+        ui.horizontal(|ui| {
+            let mut query = world.query_filtered::<(Entity, &Name), With<TopographyMesh>>();
+            for (entity, name) in query.iter(world) {
+                let selected = state.topography == Some(entity);
+                if ui.selectable_label(selected, name.as_str()).clicked() {
+                    state.topography = Some(entity);
+                }
+            }
+        });
+
+        ui.separator();
+
+        egui::Grid::new("contours_settings").show(ui, |ui| {
+            ui.label("Interval");
+            ui.add(egui::DragValue::new(&mut state.interval).clamp_range(0.1..=500.0).speed(0.1).suffix(" m"));
+            ui.end_row();
+
+            ui.label("Major every");
+            ui.add(egui::DragValue::new(&mut state.major_every).clamp_range(1..=50).suffix(" contours"));
+            ui.end_row();
+
+            ui.label("Label spacing");
+            ui.add(egui::DragValue::new(&mut state.label_spacing).clamp_range(10.0..=10000.0).speed(1.0).suffix(" m"));
+            ui.end_row();
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Generate").clicked() {
+                state.contours_result = Some(generate_contours(world, state));
+            }
+            if ui.button("Export DXF").clicked() {
+                state.contours_result = Some(export_contours(world, state));
+            }
+        });
+
+        if let Some(mut contours) = state.contours.and_then(|entity| world.get_mut::<ContourLines>(entity)) {
+            ui.separator();
+            let count = contours.contours.len();
+            ui.label(format!("{} contour lines every {} m", count, contours.interval));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut contours.labels, "Elevation labels");
+                ui.add(egui::DragValue::new(&mut contours.font_size).clamp_range(6.0..=32.0).speed(0.1).prefix("size "));
+            });
+        }
+
+        if let Some(status) = &state.contours_result {
+            match status {
+                Ok(()) => {
+                    ui.label(RichText::new("Success!").color(egui::Color32::GREEN));
+                }
+                Err(error) => {
+                    ui.label(RichText::new(error.to_string()).color(egui::Color32::RED));
+                }
+            }
+        }
+    }
+
+    fn viewport_ui(world: &mut World, _cx: EditorWindowContext, ui: &mut egui::Ui) {
+        labels_ui(world, ui);
+    }
+}
+
+/// Replaces the contours of the topography with new ones, with the settings of the window.
+fn generate_contours(world: &mut World, state: &mut ContoursWindowState) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(topography) = state.topography.filter(|entity| world.get::<TopographyMesh>(*entity).is_some()) else {
+        return Err("Select the topography to contour".into());
+    };
+
+    let contours = world.get::<Handle<Mesh>>(topography)
+        .and_then(|handle| world.resource::<Assets<Mesh>>().get(handle))
+        .and_then(|mesh| {
+            let topography = world.get::<TopographyMesh>(topography)?;
+            ContourLines::from_topography(mesh, topography, state.interval, state.major_every, state.label_spacing)
+        })
+        .ok_or("The topography has no triangles to contour")?;
+    if contours.contours.is_empty() {
+        return Err("No contour at that interval crosses the topography".into());
+    }
+
+    let previous = world.get::<Children>(topography)
+        .map(|children| children.iter().copied().filter(|child| world.get::<ContourLines>(*child).is_some()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    for child in previous {
+        world.entity_mut(child).despawn_recursive();
+    }
+
+    let name = format!("Contours {} m", contours.interval);
+    state.contours = Some(spawn_contours(world, topography, contours, &name));
+    Ok(())
+}
+
+fn export_contours(world: &mut World, state: &ContoursWindowState) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(contours) = state.contours.and_then(|entity| world.get::<ContourLines>(entity)) else {
+        return Err("Generate the contours to export first".into());
+    };
+
+    if let Some(path) = rfd::FileDialog::new().add_filter("Contours (dxf)", &["dxf"]).save_file() {
+        write_contours_dxf(contours, &path.display().to_string())?;
+    }
+    Ok(())
+}
+
+/// Draws the elevation labels of the visible contours, skipping the ones that would overlap.
+fn labels_ui(world: &mut World, ui: &mut egui::Ui) {
+    let viewport = ui.clip_rect();
+    let Ok((camera, camera_transform)) = world
+        .query_filtered::<(&Camera, &GlobalTransform), With<ActiveEditorCamera>>()
+        .get_single(world)
+        .map(|(camera, transform)| (camera.clone(), *transform))
+    else {
+        return;
+    };
+
+    let painter = ui.painter_at(viewport);
+    let mut placed: Vec<Rect> = vec![];
+    let mut query = world.query::<(&ContourLines, &GlobalTransform, &ComputedVisibility)>();
+    for (contours, transform, visibility) in query.iter(world) {
+        if !contours.labels || !visibility.is_visible_in_hierarchy() {
+            continue;
+        }
+
+        for (point, elevation) in contours.label_points.iter() {
+            let Some(position) = camera.world_to_viewport(&camera_transform, transform.transform_point(*point))
+                .map(|position| viewport.left_top() + egui::vec2(position.x, position.y))
+                .filter(|position| viewport.contains(*position))
+            else {
+                continue;
+            };
+
+            let text = contours.label(*elevation);
+            let galley = painter.layout_no_wrap(text, FontId::proportional(contours.font_size), Color32::WHITE);
+            let rect = Rect::from_center_size(position, galley.size()).expand(2.0);
+            if placed.iter().any(|other| other.intersects(rect)) {
+                continue;
+            }
+            painter.rect_filled(rect, 2.0, Color32::from_black_alpha(140));
+            painter.galley(rect.min + egui::vec2(2.0, 2.0), galley);
+            placed.push(rect);
+        }
+    }
+}
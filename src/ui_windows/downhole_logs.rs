@@ -0,0 +1,252 @@
+use std::error::Error;
+
+use bevy::prelude::{App, Entity, Name, Parent, Update, World};
+use bevy_editor_pls_core::editor_window::{EditorWindow, EditorWindowContext, MenuBarWindow};
+use bevy_inspector_egui::egui::*;
+use egui::RichText;
+
+use crate::custom_meshes::color_legend::Colormap;
+use crate::custom_meshes::downhole_log_mesh::{spawn_downhole_log, update_downhole_logs, DownholeLog, LogStyle};
+use crate::custom_meshes::drill_holes_mesh::{DrillHoleCollar, DrillHolesData};
+use crate::files_manager::files_porperties::FileProperties;
+use crate::files_manager::las_parser::{LasFile, LasLog};
+
+struct LasEntry {
+    file: LasFile,
+    log: Result<LasLog, String>,
+    hole: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct DownholeLogsWindowState {
+    drill_holes: Option<Entity>,
+    files: Vec<LasEntry>,
+    load_result: Option<Result<(), Box<dyn Error + Send + Sync>>>,
+}
+
+pub struct DownholeLogsWindow;
+
+impl EditorWindow for DownholeLogsWindow {
+    type State = DownholeLogsWindowState;
+    const NAME: &'static str = "Load Downhole Logs";
+    const DEFAULT_SIZE: (f32, f32) = (450.0, 350.0);
+    const MENU_BAR: MenuBarWindow = MenuBarWindow::File;
+
+    fn ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let state = cx.state_mut::<DownholeLogsWindow>().unwrap();
+
+        ui.label("Drill holes the logs belong to: ");
+        let mut query = world.query::<(Entity, &Name, &DrillHolesData)>();
+        let mut changed = false;
+        for (entity, name, _) in query.iter(world) {
+            let selected = state.drill_holes == Some(entity);
+            if ui.selectable_label(selected, name.as_str()).clicked() {
+                state.drill_holes = Some(entity);
+                changed = true;
+            }
+        }
+
+        let hole_ids = state.drill_holes
+            .and_then(|entity| world.get::<DrillHolesData>(entity))
+            .map(|data| data.holes.iter().map(|hole| hole.hole_id.clone()).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        if ui.button("Add LAS files").clicked() {
+            if let Some(paths) = rfd::FileDialog::new().add_filter("LAS 2.0", &["las", "LAS"]).pick_files() {
+                for path in paths {
+                    let file = LasFile { path: path.display().to_string() };
+                    let log = file.read().map_err(|error| error.to_string());
+                    state.files.push(LasEntry { file, log, hole: None });
+                }
+                changed = true;
+            }
+        }
+
+        if changed {
+            let hole_ids = hole_ids.iter().map(|hole_id| hole_id.as_str()).collect::<Vec<_>>();
+            for entry in state.files.iter_mut() {
+                if let Ok(log) = &entry.log {
+                    entry.hole = entry.file.match_hole(log, &hole_ids);
+                }
+            }
+        }
+
+        ui.separator();
+
+        let mut remove = None;
+        egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+            egui::Grid::new("las_files").striped(true).show(ui, |ui| {
+                ui.label(RichText::new("File").strong());
+                ui.label(RichText::new("Well").strong());
+                ui.label(RichText::new("Hole").strong());
+                ui.label(RichText::new("Curves").strong());
+                ui.end_row();
+
+                for (i, entry) in state.files.iter_mut().enumerate() {
+                    ui.label(entry.file.name_with_extension().unwrap_or_default());
+                    match &entry.log {
+                        Ok(log) => {
+                            ui.label(&log.well);
+                            let selected = entry.hole.and_then(|hole| hole_ids.get(hole)).cloned().unwrap_or_else(|| "Not matched".to_string());
+                            egui::ComboBox::from_id_source(("las_hole", i))
+                                .selected_text(selected)
+                                .show_ui(ui, |ui| {
+                                    for (hole, hole_id) in hole_ids.iter().enumerate() {
+                                        ui.selectable_value(&mut entry.hole, Some(hole), hole_id);
+                                    }
+                                });
+                            ui.label(log.curves.iter().map(|curve| curve.mnemonic.as_str()).collect::<Vec<_>>().join(", "));
+                        }
+                        Err(error) => {
+                            ui.label("");
+                            ui.label("");
+                            ui.label(RichText::new(error).color(egui::Color32::RED));
+                        }
+                    }
+                    if ui.small_button("\u{1F5D1}").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        if let Some(i) = remove {
+            state.files.remove(i);
+        }
+
+        ui.separator();
+
+        if ui.button("Load").clicked() {
+            state.load_result = Some(load_logs(world, state));
+        }
+
+        if let Some(status) = &state.load_result {
+            match status {
+                Ok(()) => {
+                    ui.label(RichText::new("Logs Loaded!").color(egui::Color32::GREEN));
+                }
+                Err(error) => {
+                    ui.label(RichText::new(error.to_string()).color(egui::Color32::RED));
+                }
+            }
+        }
+    }
+
+    fn app_setup(app: &mut App) {
+        app.add_systems(Update, update_downhole_logs);
+    }
+}
+
+/// Spawns every matched log under the entity of its hole, or under the drill holes when they are merged.
+fn load_logs(world: &mut World, state: &DownholeLogsWindowState) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(drill_holes) = state.drill_holes.filter(|entity| world.get::<DrillHolesData>(*entity).is_some()) else {
+        return Err("Select the drill holes the logs belong to".into());
+    };
+
+    let mut hole_entities = world.query::<(Entity, &DrillHoleCollar, &Parent)>()
+        .iter(world)
+        .filter(|(_, _, parent)| parent.get() == drill_holes)
+        .map(|(entity, collar, _)| (collar.hole, entity))
+        .collect::<std::collections::HashMap<_, _>>();
+
+    let mut loaded = 0;
+    for entry in state.files.iter() {
+        let (Ok(log), Some(hole)) = (&entry.log, entry.hole) else {
+            continue;
+        };
+        let parent = *hole_entities.entry(hole).or_insert(drill_holes);
+        let name = format!("{} log", entry.file.name().unwrap_or_else(|| log.well.clone()));
+        spawn_downhole_log(world, parent, DownholeLog::new(hole, log.clone()), &name);
+        loaded += 1;
+    }
+
+    if loaded == 0 {
+        return Err("No log is matched to a hole".into());
+    }
+    Ok(())
+}
+
+/// Inspector section of a downhole log entity.
+pub fn log_ui(world: &mut World, entity: Entity, ui: &mut egui::Ui) {
+    let Some(log) = world.get::<DownholeLog>(entity) else {
+        return;
+    };
+
+    let mut curve = log.curve;
+    let mut style = log.style;
+    let mut offset = log.offset;
+    let mut width = log.width;
+    let mut legend = log.legend.clone();
+    let mut reset_legend = false;
+
+    ui.separator();
+    ui.heading("Downhole log");
+    egui::Grid::new("downhole_log").show(ui, |ui| {
+        ui.label("Curve");
+        egui::ComboBox::from_id_source("downhole_log_curve")
+            .selected_text(log.log.curves.get(curve).map(|curve| curve.name()).unwrap_or_default())
+            .show_ui(ui, |ui| {
+                for (i, option) in log.log.curves.iter().enumerate() {
+                    ui.selectable_value(&mut curve, i, option.name())
+                        .on_hover_text(&option.description);
+                }
+            });
+        ui.end_row();
+
+        ui.label("Style");
+        egui::ComboBox::from_id_source("downhole_log_style")
+            .selected_text(style.name())
+            .show_ui(ui, |ui| {
+                for option in LogStyle::all() {
+                    ui.selectable_value(&mut style, option, option.name());
+                }
+            });
+        ui.end_row();
+
+        ui.label("Offset");
+        ui.add(egui::DragValue::new(&mut offset).clamp_range(0.0..=100.0).speed(0.1).suffix(" m"));
+        ui.end_row();
+
+        ui.label("Width");
+        ui.add(egui::DragValue::new(&mut width).clamp_range(0.1..=200.0).speed(0.1).suffix(" m"));
+        ui.end_row();
+
+        ui.label("Colormap");
+        egui::ComboBox::from_id_source("downhole_log_colormap")
+            .selected_text(legend.colormap.name())
+            .show_ui(ui, |ui| {
+                for colormap in Colormap::all() {
+                    ui.selectable_value(&mut legend.colormap, colormap, colormap.name());
+                }
+            });
+        ui.end_row();
+
+        ui.label("Min");
+        ui.add(egui::DragValue::new(&mut legend.min).speed(0.01));
+        ui.end_row();
+
+        ui.label("Max");
+        ui.add(egui::DragValue::new(&mut legend.max).speed(0.01));
+        ui.end_row();
+    });
+    if ui.button("Reset range to P25 - P75").clicked() {
+        reset_legend = true;
+    }
+
+    let log = world.get::<DownholeLog>(entity).unwrap();
+    let changed = curve != log.curve || style != log.style || offset != log.offset || width != log.width || legend != log.legend;
+    if !(changed || reset_legend) {
+        return;
+    }
+
+    let mut log = world.get_mut::<DownholeLog>(entity).unwrap();
+    let curve_changed = curve != log.curve;
+    log.curve = curve;
+    log.style = style;
+    log.offset = offset;
+    log.width = width;
+    log.legend = legend;
+    if curve_changed || reset_legend {
+        log.reset_legend();
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::{Camera, Component, ComputedVisibility, Entity, GlobalTransform, Parent, Vec3, With, World};
+use bevy_inspector_egui::egui;
+use egui::{Color32, FontId, Pos2, Rect};
+
+use crate::custom_meshes::drill_holes_mesh::{DrillHoleCollar, DrillHolesData};
+use crate::ui_windows::cameras::ActiveEditorCamera;
+
+/// Hole-id labels and collar symbols drawn over the viewport for the holes of the entity.
+#[derive(Component)]
+pub struct DrillHolesLabels {
+    pub hole_ids: bool,
+    pub end_depths: bool,
+    pub collars: bool,
+    /// Holes further from the camera are not labelled
+    pub max_distance: f32,
+    pub font_size: f32,
+}
+
+impl Default for DrillHolesLabels {
+    fn default() -> Self {
+        Self {
+            hole_ids: true,
+            end_depths: false,
+            collars: true,
+            max_distance: 2000.0,
+            font_size: 12.0,
+        }
+    }
+}
+
+struct Label {
+    distance: f32,
+    position: Pos2,
+    text: String,
+    font_size: f32,
+    collar: bool,
+}
+
+/// Draws the labels of every visible drill-hole entity, nearest holes first.
+/// Labels that would overlap one already drawn are skipped.
+pub fn viewport_ui(world: &mut World, ui: &mut egui::Ui) {
+    let viewport = ui.clip_rect();
+    let Ok((camera, camera_transform)) = world
+        .query_filtered::<(&Camera, &GlobalTransform), With<ActiveEditorCamera>>()
+        .get_single(world)
+        .map(|(camera, transform)| (camera.clone(), *transform))
+    else {
+        return;
+    };
+    let camera_position = camera_transform.translation();
+    let project = |point: Vec3| {
+        camera.world_to_viewport(&camera_transform, point)
+            .map(|position| viewport.left_top() + egui::vec2(position.x, position.y))
+            .filter(|position| viewport.contains(*position))
+    };
+
+    // Holes with their own entity are only labelled while it is visible
+    let mut hole_entities: HashMap<Entity, HashSet<usize>> = HashMap::new();
+    let mut holes = world.query::<(&DrillHoleCollar, &Parent, &ComputedVisibility)>();
+    for (collar, parent, visibility) in holes.iter(world) {
+        let visible = hole_entities.entry(parent.get()).or_default();
+        if visibility.is_visible_in_hierarchy() {
+            visible.insert(collar.hole);
+        }
+    }
+
+    let mut labels = vec![];
+    let mut query = world.query::<(Entity, &DrillHolesData, &DrillHolesLabels, &GlobalTransform, &ComputedVisibility)>();
+    for (entity, data, settings, transform, visibility) in query.iter(world) {
+        if !visibility.is_visible_in_hierarchy() {
+            continue;
+        }
+        let visible_holes = hole_entities.get(&entity);
+
+        for (i, hole) in data.holes.iter().enumerate() {
+            if visible_holes.map_or(false, |visible| !visible.contains(&i)) {
+                continue;
+            }
+            let collar = transform.transform_point(hole.trace.collar());
+            let distance = collar.distance(camera_position);
+            if distance > settings.max_distance {
+                continue;
+            }
+
+            if let Some(position) = project(collar) {
+                labels.push(Label {
+                    distance,
+                    position,
+                    text: if settings.hole_ids { hole.hole_id.clone() } else { String::new() },
+                    font_size: settings.font_size,
+                    collar: settings.collars,
+                });
+            }
+
+            if settings.end_depths {
+                let end = transform.transform_point(hole.trace.point_at(hole.trace.length()));
+                if let Some(position) = project(end) {
+                    labels.push(Label {
+                        distance: end.distance(camera_position),
+                        position,
+                        text: format!("{:.1} m", hole.trace.length()),
+                        font_size: settings.font_size * 0.85,
+                        collar: false,
+                    });
+                }
+            }
+        }
+    }
+
+    labels.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+    let painter = ui.painter_at(viewport);
+    let mut placed: Vec<Rect> = vec![];
+    for label in labels {
+        if label.collar {
+            painter.circle(label.position, 3.5, Color32::WHITE, egui::Stroke::new(1.0, Color32::BLACK));
+        }
+        if label.text.is_empty() {
+            continue;
+        }
+
+        let galley = painter.layout_no_wrap(label.text, FontId::proportional(label.font_size), Color32::WHITE);
+        let rect = Rect::from_min_size(label.position + egui::vec2(6.0, -galley.size().y - 2.0), galley.size()).expand(2.0);
+        if placed.iter().any(|other| other.intersects(rect)) {
+            continue;
+        }
+        painter.rect_filled(rect, 2.0, Color32::from_black_alpha(160));
+        painter.galley(rect.min + egui::vec2(2.0, 2.0), galley);
+        placed.push(rect);
+    }
+}
+
+/// Inspector section of the label settings of a drill-hole entity.
+pub fn labels_ui(world: &mut World, entity: Entity, ui: &mut egui::Ui) {
+    let Some(mut settings) = world.get_mut::<DrillHolesLabels>(entity) else {
+        return;
+    };
+
+    ui.separator();
+    ui.heading("Labels");
+    egui::Grid::new("drill_holes_labels").show(ui, |ui| {
+        ui.label("Hole ids");
+        ui.checkbox(&mut settings.hole_ids, "");
+        ui.end_row();
+
+        ui.label("End of hole depth");
+        ui.checkbox(&mut settings.end_depths, "");
+        ui.end_row();
+
+        ui.label("Collar symbols");
+        ui.checkbox(&mut settings.collars, "");
+        ui.end_row();
+
+        ui.label("Hide beyond");
+        ui.add(egui::DragValue::new(&mut settings.max_distance).clamp_range(10.0..=100000.0).speed(10.0).suffix(" m"));
+        ui.end_row();
+
+        ui.label("Font size");
+        ui.add(egui::DragValue::new(&mut settings.font_size).clamp_range(6.0..=32.0).speed(0.2));
+        ui.end_row();
+    });
+}
@@ -0,0 +1,168 @@
+use bevy::prelude::{Entity, Name, World};
+use bevy_inspector_egui::egui;
+
+use crate::custom_meshes::drill_holes_mesh::{drill_holes_root, DrillHoleCollar, DrillHolesData, DrillHolesFilter, DrillHolesLayer, DrillHolesVariable, FilterDisplay};
+use crate::files_manager::filter_expression::FilterExpression;
+
+/// Inspector section of a drill-hole layer entity, switching the displayed variable
+/// rebuilds the mesh from the data already in memory.
+/// On per hole layers the change is applied to the same layer of every hole.
+pub fn layer_ui(world: &mut World, entity: Entity, ui: &mut egui::Ui) {
+    let (Some(layer), Some(root)) = (world.get::<DrillHolesLayer>(entity), drill_holes_root(world, entity)) else {
+        return;
+    };
+    let Some(data) = world.get::<DrillHolesData>(root) else {
+        return;
+    };
+
+    let mut options = data.variables.iter()
+        .map(|variable| DrillHolesVariable::Assay(variable.clone()))
+        .collect::<Vec<_>>();
+    if !data.lithology_intervals.is_empty() {
+        options.push(DrillHolesVariable::Lithology);
+    }
+
+    let mut variable = layer.variable.clone();
+    let mut radius = layer.radius;
+
+    ui.separator();
+    ui.heading("Drill holes");
+    egui::Grid::new("drill_holes_layer").show(ui, |ui| {
+        ui.label("Variable");
+        egui::ComboBox::from_id_source("drill_holes_layer_variable")
+            .selected_text(variable.name())
+            .show_ui(ui, |ui| {
+                for option in options {
+                    let name = option.name();
+                    ui.selectable_value(&mut variable, option, name);
+                }
+            });
+        ui.end_row();
+
+        ui.label("Radius");
+        ui.add(egui::DragValue::new(&mut radius).clamp_range(0.1..=100.0).speed(0.1));
+        ui.end_row();
+    });
+
+    let layer = world.get::<DrillHolesLayer>(entity).unwrap();
+    if variable == layer.variable && radius == layer.radius {
+        return;
+    }
+
+    let targets = if layer.hole.is_some() {
+        let previous = layer.variable.clone();
+        world.query::<(Entity, &DrillHolesLayer)>()
+            .iter(world)
+            .filter(|(_, other)| other.hole.is_some() && other.variable == previous)
+            .map(|(other, _)| other)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .filter(|other| drill_holes_root(world, *other) == Some(root))
+            .collect()
+    } else {
+        vec![entity]
+    };
+
+    let name = variable.name();
+    for target in targets {
+        let mut layer = world.get_mut::<DrillHolesLayer>(target).unwrap();
+        layer.variable = variable.clone();
+        layer.radius = radius;
+
+        if let Some(mut entity_name) = world.get_mut::<Name>(target) {
+            entity_name.set(name.clone());
+        }
+    }
+}
+
+/// Inspector section of a hole entity.
+pub fn hole_ui(world: &mut World, entity: Entity, ui: &mut egui::Ui) {
+    let Some(collar) = world.get::<DrillHoleCollar>(entity) else {
+        return;
+    };
+
+    ui.separator();
+    ui.heading("Drill hole");
+    egui::Grid::new("drill_hole_collar").show(ui, |ui| {
+        ui.label("Hole id");
+        ui.label(&collar.hole_id);
+        ui.end_row();
+
+        for (axis, value) in ["X", "Y", "Z"].iter().zip(collar.collar) {
+            ui.label(format!("Collar {}", axis));
+            ui.label(format!("{:.2}", value));
+            ui.end_row();
+        }
+
+        ui.label("Length");
+        ui.label(format!("{:.2}", collar.length));
+        ui.end_row();
+    });
+}
+
+/// Inspector section of the interval filter of a drill-hole entity.
+/// The layers are rebuilt as soon as the typed expression parses.
+pub fn filter_ui(world: &mut World, entity: Entity, ui: &mut egui::Ui) {
+    let Some(data) = world.get::<DrillHolesData>(entity) else {
+        return;
+    };
+    let mut columns = data.assay.get_column_names().into_iter()
+        .chain(data.lithology.get_column_names())
+        .chain(data.header.get_column_names())
+        .map(|column| column.to_string())
+        .collect::<Vec<_>>();
+    if !data.lithology_intervals.is_empty() {
+        columns.push("rock".to_string());
+    }
+    columns.sort();
+    columns.dedup();
+
+    let Some(mut filter) = world.get_mut::<DrillHolesFilter>(entity) else {
+        return;
+    };
+
+    ui.separator();
+    ui.heading("Filter");
+
+    let mut text = filter.text.clone();
+    ui.add(egui::TextEdit::singleline(&mut text).hint_text("cu > 0.3 and rock == 4").desired_width(f32::INFINITY))
+        .on_hover_text(format!("Compare columns with ==, !=, <, <=, > and >=, join them with and, or, not.\nQuote texts as 'RC'.\n\nColumns: {}", columns.join(", ")));
+
+    if text != filter.text {
+        filter.bypass_change_detection().text = text.clone();
+        if text.trim().is_empty() {
+            filter.expression = None;
+            filter.error = None;
+        } else {
+            match FilterExpression::parse(&text) {
+                Ok(expression) => filter.expression = Some(expression),
+                Err(error) => filter.bypass_change_detection().error = Some(error.to_string()),
+            }
+        }
+    }
+
+    let mut display = filter.display;
+    ui.horizontal(|ui| {
+        ui.label("Failing intervals");
+        egui::ComboBox::from_id_source("drill_holes_filter_display")
+            .selected_text(display.name())
+            .show_ui(ui, |ui| {
+                for option in FilterDisplay::all() {
+                    ui.selectable_value(&mut display, option, option.name());
+                }
+            });
+    });
+    if display != filter.display {
+        filter.display = display;
+    }
+
+    if let Some(error) = &filter.error {
+        ui.label(egui::RichText::new(error).color(egui::Color32::RED));
+    }
+    for (name, passed) in [("Assay", &filter.assay_passed), ("Lithology", &filter.lithology_passed)] {
+        if let Some(passed) = passed {
+            let count = passed.iter().filter(|passed| **passed).count();
+            ui.label(format!("{}: {} of {} rows pass", name, count, passed.len()));
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::error::Error;
+
+use bevy::prelude::{Entity, Name, World};
+use bevy_editor_pls_core::editor_window::{EditorWindow, EditorWindowContext, MenuBarWindow};
+use bevy_inspector_egui::egui::*;
+use egui::RichText;
+
+use crate::custom_meshes::drill_holes_mesh::DrillHolesData;
+use crate::files_manager::column_mapping::DrillTable;
+use crate::files_manager::csv_parser::write_dataframe;
+use crate::files_manager::drill_holes_export::{interval_table, write_dxf, write_tables};
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum DrillsExport {
+    /// Collar, survey, assay and lithology CSV files
+    #[default]
+    Tables,
+    /// One CSV row per interval with its midpoint
+    Intervals,
+    /// 3D polylines of the traces
+    Dxf,
+}
+
+impl DrillsExport {
+    pub fn name(self) -> &'static str {
+        match self {
+            DrillsExport::Tables => "Collar, survey, assay and lithology CSV",
+            DrillsExport::Intervals => "Intervals with XYZ midpoints CSV",
+            DrillsExport::Dxf => "Traces as DXF 3D polylines",
+        }
+    }
+
+    pub fn all() -> [DrillsExport; 3] {
+        [DrillsExport::Tables, DrillsExport::Intervals, DrillsExport::Dxf]
+    }
+}
+
+#[derive(Default)]
+pub struct ExportDrillsWindowState {
+    drill_holes: Option<Entity>,
+    export: DrillsExport,
+    lithology_intervals: bool,
+    export_result: Option<Result<(), Box<dyn Error + Send + Sync>>>,
+}
+
+pub struct ExportDrillsWindow;
+
+impl EditorWindow for ExportDrillsWindow {
+    type State = ExportDrillsWindowState;
+    const NAME: &'static str = "Export Drill Holes";
+    const DEFAULT_SIZE: (f32, f32) = (350.0, 250.0);
+    const MENU_BAR: MenuBarWindow = MenuBarWindow::File;
+
+    fn ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let state = cx.state_mut::<ExportDrillsWindow>().unwrap();
+
+        ui.label("Drill holes to export: ");
+        let mut query = world.query::<(Entity, &Name, &DrillHolesData)>();
+        for (entity, name, _) in query.iter(world) {
+            let selected = state.drill_holes == Some(entity);
+            if ui.selectable_label(selected, name.as_str()).clicked() {
+                state.drill_holes = Some(entity);
+            }
+        }
+
+        ui.separator();
+
+        for export in DrillsExport::all() {
+            ui.radio_value(&mut state.export, export, export.name());
+        }
+        if state.export == DrillsExport::Intervals {
+            ui.checkbox(&mut state.lithology_intervals, "Lithology intervals instead of assays");
+        }
+
+        ui.separator();
+
+        if ui.button("Export").clicked() {
+            state.export_result = Some(export(world, state));
+        }
+
+        if let Some(status) = &state.export_result {
+            match status {
+                Ok(()) => {
+                    ui.label(RichText::new("Success!").color(egui::Color32::GREEN));
+                }
+                Err(error) => {
+                    ui.label(RichText::new(error.to_string()).color(egui::Color32::RED));
+                }
+            }
+        }
+    }
+}
+
+/// Writes the desurveyed holes as the editor shows them, with the collars in mine grid coordinates.
+fn export(world: &mut World, state: &ExportDrillsWindowState) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(data) = state.drill_holes.and_then(|entity| world.get::<DrillHolesData>(entity)) else {
+        return Err("Select the drill holes to export".into());
+    };
+
+    match state.export {
+        DrillsExport::Tables => {
+            if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                write_tables(data, &folder)?;
+            }
+        }
+        DrillsExport::Intervals => {
+            let table = if state.lithology_intervals { DrillTable::Lithology } else { DrillTable::Assay };
+            if data.interval_rows(table).map_or(true, |(_, intervals)| intervals.is_empty()) {
+                return Err("The drill holes have no intervals in that table".into());
+            }
+            if let Some(path) = rfd::FileDialog::new().add_filter("Intervals (csv)", &["csv"]).save_file() {
+                write_dataframe(&interval_table(data, table)?, &path.display().to_string())?;
+            }
+        }
+        DrillsExport::Dxf => {
+            if let Some(path) = rfd::FileDialog::new().add_filter("Traces (dxf)", &["dxf"]).save_file() {
+                write_dxf(data, &path.display().to_string())?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use std::error::Error;
+
+use bevy::prelude::*;
+use bevy::render::mesh::PrimitiveTopology;
+use bevy_editor_pls_core::editor_window::{EditorWindow, EditorWindowContext, MenuBarWindow};
+use bevy_inspector_egui::egui;
+use egui::RichText;
+
+use crate::custom_meshes::topography_lod::LodTile;
+use crate::custom_meshes::topography_mesh::TopographyMesh;
+use crate::files_manager::surface_export::{SurfaceExport, SurfaceFormat};
+
+#[derive(Default)]
+pub struct ExportSurfaceWindowState {
+    mesh: Option<Entity>,
+    format: SurfaceFormat,
+    export_result: Option<Result<(), Box<dyn Error + Send + Sync>>>,
+}
+
+pub struct ExportSurfaceWindow;
+
+impl EditorWindow for ExportSurfaceWindow {
+    type State = ExportSurfaceWindowState;
+    const NAME: &'static str = "Export Surface";
+    const DEFAULT_SIZE: (f32, f32) = (350.0, 300.0);
+    const MENU_BAR: MenuBarWindow = MenuBarWindow::File;
+
+    fn ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let state = cx.state_mut::<ExportSurfaceWindow>().unwrap();
+
+        ui.label("Mesh to export: ");
+        // Level of detail tiles are pieces of their topography, which is exported whole
+        let mut query = world.query_filtered::<(Entity, &Name, &Handle<Mesh>), Without<LodTile>>();
+        let meshes = query.iter(world)
+            .filter(|(_, _, handle)| {
+                world.resource::<Assets<Mesh>>().get(handle)
+                    .map_or(false, |mesh| mesh.primitive_topology() == PrimitiveTopology::TriangleList)
+            })
+            .map(|(entity, name, _)| (entity, name.to_string(), world.get::<TopographyMesh>(entity).is_some()))
+            .collect::<Vec<_>>();
+        egui::ScrollArea::vertical().max_height(150.0).show(ui, |ui| {
+            for (entity, name, topography) in meshes {
+                let label = if topography { format!("\u{1F5FA} {}", name) } else { name };
+                if ui.selectable_label(state.mesh == Some(entity), label).clicked() {
+                    state.mesh = Some(entity);
+                }
+            }
+        });
+
+        ui.separator();
+
+        for format in SurfaceFormat::all() {
+            ui.radio_value(&mut state.format, format, format.name());
+        }
+
+        ui.separator();
+
+        if ui.button("Export").clicked() {
+            state.export_result = Some(export(world, state));
+        }
+
+        if let Some(status) = &state.export_result {
+            match status {
+                Ok(()) => {
+                    ui.label(RichText::new("Success!").color(egui::Color32::GREEN));
+                }
+                Err(error) => {
+                    ui.label(RichText::new(error.to_string()).color(egui::Color32::RED));
+                }
+            }
+        }
+    }
+}
+
+/// Offsets of the closest topography among the entity and its ancestors, none for other meshes.
+fn offset(world: &World, entity: Entity) -> [f64; 3] {
+    let mut current = Some(entity);
+    while let Some(entity) = current {
+        if let Some(topography) = world.get::<TopographyMesh>(entity) {
+            return [topography.offset_x, topography.offset_y, topography.offset_z];
+        }
+        current = world.get::<Parent>(entity).map(|parent| parent.get());
+    }
+    [0.0; 3]
+}
+
+fn export(world: &mut World, state: &ExportSurfaceWindowState) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(entity) = state.mesh.filter(|entity| world.get::<Handle<Mesh>>(*entity).is_some()) else {
+        return Err("Select the mesh to export".into());
+    };
+
+    let name = world.get::<Name>(entity).map(|name| name.to_string()).unwrap_or_default();
+    let transform = world.get::<GlobalTransform>(entity).copied().unwrap_or_default();
+    let surface = world.get::<Handle<Mesh>>(entity)
+        .and_then(|handle| world.resource::<Assets<Mesh>>().get(handle))
+        .and_then(|mesh| SurfaceExport::from_mesh(&name, mesh, &transform, offset(world, entity)))
+        .ok_or("Only triangle meshes can be exported")?;
+
+    if let Some(path) = rfd::FileDialog::new()
+        .add_filter(state.format.name(), &[state.format.extension()])
+        .set_file_name(format!("{}.{}", name, state.format.extension()))
+        .save_file()
+    {
+        surface.write(state.format, &path.display().to_string())?;
+    }
+    Ok(())
+}
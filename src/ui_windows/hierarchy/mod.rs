@@ -1,4 +1,4 @@
-// pub mod picking;
+pub mod picking;
 
 use bevy::ecs::entity::Entities;
 use bevy::pbr::wireframe::Wireframe;
@@ -62,8 +62,17 @@ impl EditorWindow for HierarchyWindow {
             });
     }
 
+    fn viewport_ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let Some((hierarchy_state, inspector_state)) = cx.state_mut_pair::<HierarchyWindow, InspectorWindow>() else {
+            return;
+        };
+        if picking::viewport_ui(world, &mut hierarchy_state.selected, ui) {
+            inspector_state.selected = InspectorSelection::Entities;
+        }
+    }
+
     fn app_setup(app: &mut bevy::prelude::App) {
-        // picking::setup(app);
+        picking::setup(app);
         app.add_systems(PostUpdate, clear_removed_entites);
         // .add_system(handle_events);
 
@@ -1,32 +1,166 @@
-use bevy::{prelude::*, render::render_resource::PrimitiveTopology};
-use bevy_mod_picking::prelude::{PickRaycastTarget, PickableBundle};
+use bevy::math::Ray;
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_inspector::hierarchy::SelectedEntities;
+use bevy_inspector_egui::egui;
 
-pub struct EditorPickingSet;
+use crate::custom_meshes::drill_holes_mesh::{drill_holes_root, DrillHolesData, DrillHolesFilter, DrillHolesLayer, DrillHolesVariable, DrillInterval};
+use crate::math::analytic_geometry::ray_segment_closest;
+use crate::ui_windows::cameras::ActiveEditorCamera;
 
-/// Prevents the entity from being selectable in the editor window.
-#[derive(Component)]
-pub struct NoEditorPicking;
+/// Interval of a drill-hole layer found under the pointer.
+#[derive(Clone, Copy)]
+pub struct IntervalPick {
+    pub layer: Entity,
+    pub data: Entity,
+    pub interval: DrillInterval,
+    /// Downhole depth of the point that was hit
+    pub depth: f32,
+}
+
+/// Drill-hole intervals currently hovered and clicked in the viewport.
+/// `combine_meshes` merges every interval of a layer, so they are found against the traces.
+#[derive(Resource, Default)]
+pub struct DrillHolesPicking {
+    pub hovered: Option<IntervalPick>,
+    pub selected: Option<IntervalPick>,
+    last_pointer: Option<egui::Pos2>,
+}
 
 pub fn setup(app: &mut App) {
-    app.add_plugins(bevy_mod_picking::plugins::DefaultPickingPlugins)
-        .add_system(auto_add_editor_picking_set);
+    app.init_resource::<DrillHolesPicking>();
 }
 
-fn auto_add_editor_picking_set(
-    mut commands: Commands,
-    meshes: Res<Assets<Mesh>>,
-    meshes_query: Query<
-        (Entity, &Handle<Mesh>),
-        (Without<PickRaycastTarget>, Without<NoEditorPicking>),
-    >,
-) {
-    for (entity, handle) in meshes_query.iter() {
-        if let Some(mesh) = meshes.get(handle) {
-            if let PrimitiveTopology::TriangleList = mesh.primitive_topology() {
-                commands
-                    .entity(entity)
-                    .insert((PickableBundle::default(), PickRaycastTarget::default()));
+/// Closest interval of the visible drill-hole layers hit by the ray, in world space.
+pub fn pick_interval(world: &mut World, ray: Ray) -> Option<IntervalPick> {
+    let mut closest: Option<(f32, IntervalPick)> = None;
+
+    let mut layers = world.query::<(Entity, &DrillHolesLayer, &GlobalTransform, &ComputedVisibility)>();
+    for (entity, layer, transform, visibility) in layers.iter(world) {
+        if !visibility.is_visible() {
+            continue;
+        }
+        let Some(root) = drill_holes_root(world, entity) else {
+            continue;
+        };
+        let Some(data) = world.get::<DrillHolesData>(root) else {
+            continue;
+        };
+
+        let inverse = transform.affine().inverse();
+        let origin = inverse.transform_point3(ray.origin);
+        let direction = inverse.transform_vector3(ray.direction);
+
+        let filter = world.get::<DrillHolesFilter>(root);
+
+        let intervals = data.intervals(&layer.variable).iter()
+            .filter(|interval| layer.hole.map_or(true, |hole| interval.hole == hole))
+            .filter(|interval| !filter.map_or(false, |filter| filter.hides(&layer.variable, interval.row)));
+        for interval in intervals {
+            let points = data.holes[interval.hole].trace.segment(interval.from, interval.to);
+            let mut depth = interval.from;
+            for pair in points.windows(2) {
+                let length = pair[0].distance(pair[1]);
+                let (along, t, distance) = ray_segment_closest(origin, direction, pair[0], pair[1]);
+                if distance <= layer.radius && closest.map_or(true, |(best, _)| along < best) {
+                    let pick = IntervalPick {
+                        layer: entity,
+                        data: root,
+                        interval: *interval,
+                        depth: (depth + length * t).min(interval.to),
+                    };
+                    closest = Some((along, pick));
+                }
+                depth += length;
             }
         }
     }
+
+    closest.map(|(_, pick)| pick)
+}
+
+/// Hover tooltip and click selection of drill-hole intervals, drawn over the viewport.
+pub fn viewport_ui(world: &mut World, selected: &mut SelectedEntities, ui: &mut egui::Ui) -> bool {
+    let viewport = ui.clip_rect();
+    let pointer = ui.ctx().pointer_hover_pos()
+        .filter(|pointer| viewport.contains(*pointer))
+        .filter(|pointer| ui.ctx().layer_id_at(*pointer).map_or(true, |layer| layer.order == egui::Order::Background));
+
+    let Some(pointer) = pointer else {
+        let mut picking = world.resource_mut::<DrillHolesPicking>();
+        picking.hovered = None;
+        picking.last_pointer = None;
+        return false;
+    };
+
+    if world.resource::<DrillHolesPicking>().last_pointer != Some(pointer) {
+        let ray = world
+            .query_filtered::<(&Camera, &GlobalTransform), With<ActiveEditorCamera>>()
+            .get_single(world)
+            .ok()
+            .and_then(|(camera, transform)| {
+                let position = pointer - viewport.left_top();
+                camera.viewport_to_world(transform, Vec2::new(position.x, position.y))
+            });
+        let hovered = ray.and_then(|ray| pick_interval(world, ray));
+
+        let mut picking = world.resource_mut::<DrillHolesPicking>();
+        picking.hovered = hovered;
+        picking.last_pointer = Some(pointer);
+    }
+
+    let Some(hovered) = world.resource::<DrillHolesPicking>().hovered else {
+        return false;
+    };
+
+    if let (Some(data), Some(layer)) = (world.get::<DrillHolesData>(hovered.data), world.get::<DrillHolesLayer>(hovered.layer)) {
+        let hole_id = &data.holes[hovered.interval.hole].hole_id;
+        let column = match &layer.variable {
+            DrillHolesVariable::Assay(variable) => variable.as_str(),
+            DrillHolesVariable::Lithology => "rock",
+        };
+        let value = data.interval_attributes(&layer.variable, &hovered.interval).into_iter()
+            .find(|(name, _)| name == column)
+            .map(|(_, value)| value)
+            .unwrap_or_default();
+
+        egui::show_tooltip_at_pointer(ui.ctx(), egui::Id::new("drill_hole_tooltip"), |ui| {
+            ui.strong(hole_id);
+            ui.label(format!("{:.2} - {:.2} m", hovered.interval.from, hovered.interval.to));
+            ui.label(format!("{}: {}", layer.variable.name(), value));
+        });
+    }
+
+    if ui.input(|input| input.pointer.primary_clicked()) {
+        world.resource_mut::<DrillHolesPicking>().selected = Some(hovered);
+        selected.select_replace(hovered.layer);
+        return true;
+    }
+
+    false
+}
+
+/// Inspector section with every attribute of the interval clicked on the layer.
+pub fn interval_ui(world: &mut World, entity: Entity, ui: &mut egui::Ui) {
+    let Some(pick) = world.resource::<DrillHolesPicking>().selected else {
+        return;
+    };
+    if pick.layer != entity {
+        return;
+    }
+    let (Some(data), Some(layer)) = (world.get::<DrillHolesData>(pick.data), world.get::<DrillHolesLayer>(pick.layer)) else {
+        return;
+    };
+
+    ui.separator();
+    ui.heading("Picked interval");
+    egui::Grid::new("picked_interval").striped(true).show(ui, |ui| {
+        for (name, value) in data.interval_attributes(&layer.variable, &pick.interval) {
+            ui.label(name);
+            ui.label(value);
+            ui.end_row();
+        }
+        ui.label("picked depth");
+        ui.label(format!("{:.2}", pick.depth));
+        ui.end_row();
+    });
 }
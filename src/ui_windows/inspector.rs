@@ -1,7 +1,8 @@
 use std::any::TypeId;
 
 use super::add::{AddWindow, AddWindowState};
-use super::hierarchy::HierarchyWindow;
+use super::{downhole_logs, drill_holes_labels, drill_holes_layer, nodes_creator};
+use super::hierarchy::{picking, HierarchyWindow};
 use bevy::asset::HandleId;
 use bevy::prelude::{AppTypeRegistry, Entity, World};
 use bevy::reflect::TypeRegistryInternal;
@@ -67,6 +68,14 @@ fn inspector(
             }
             &[entity] => {
                 bevy_inspector::ui_for_entity(world, entity, ui);
+                drill_holes_layer::hole_ui(world, entity, ui);
+                drill_holes_layer::layer_ui(world, entity, ui);
+                drill_holes_labels::labels_ui(world, entity, ui);
+                drill_holes_layer::filter_ui(world, entity, ui);
+                downhole_logs::log_ui(world, entity, ui);
+                picking::interval_ui(world, entity, ui);
+                nodes_creator::shading_ui(world, entity, ui);
+                nodes_creator::lod_ui(world, entity, ui);
                 add_ui(ui, &[entity], world, add_window_state);
             }
             entities => {
@@ -0,0 +1,280 @@
+use std::error::Error;
+
+use bevy::prelude::*;
+use bevy_editor_pls_core::editor_window::{EditorWindow, EditorWindowContext, MenuBarWindow};
+use bevy_inspector_egui::egui;
+use egui::RichText;
+use polars::prelude::{DataFrame, NamedFrom, Series};
+
+use crate::custom_meshes::drill_holes_mesh::{DrillHolesData, HoleIntercept};
+use crate::files_manager::csv_parser::write_dataframe;
+use crate::math::intercepts::{Intercept, InterceptSettings};
+
+const INTERCEPT_COLOR: [f32; 4] = [1.0, 0.85, 0.0, 1.0];
+const INCLUDE_COLOR: [f32; 4] = [1.0, 0.1, 0.1, 1.0];
+
+/// Highlight radii, larger than the layers so the intercepts are drawn around them.
+const INTERCEPT_RADIUS: f32 = 5.0;
+const INCLUDE_RADIUS: f32 = 6.0;
+
+/// Entity drawing the last intercepts found on a drill-hole entity, child of it.
+#[derive(Component)]
+pub struct InterceptsHighlight;
+
+pub struct InterceptsWindowState {
+    drill_holes: Option<Entity>,
+    variable: Option<String>,
+    settings: InterceptSettings,
+    unit: String,
+    intercepts: Vec<HoleIntercept>,
+    /// Unit and variable of the intercepts, as "g/t Au"
+    grade_label: String,
+    intercepts_result: Option<Result<(), Box<dyn Error + Send + Sync>>>,
+}
+
+impl Default for InterceptsWindowState {
+    fn default() -> Self {
+        Self {
+            drill_holes: None,
+            variable: None,
+            settings: InterceptSettings::default(),
+            unit: "g/t".to_string(),
+            intercepts: vec![],
+            grade_label: String::new(),
+            intercepts_result: None,
+        }
+    }
+}
+
+pub struct InterceptsWindow;
+
+impl EditorWindow for InterceptsWindow {
+    type State = InterceptsWindowState;
+    const NAME: &'static str = "Significant Intercepts";
+    const DEFAULT_SIZE: (f32, f32) = (480.0, 520.0);
+    const MENU_BAR: MenuBarWindow = MenuBarWindow::Edit;
+
+    fn ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let state = cx.state_mut::<InterceptsWindow>().unwrap();
+
+        ui.label("Drill holes: ");
+        let mut query = world.query::<(Entity, &Name, &DrillHolesData)>();
+        for (entity, name, _) in query.iter(world) {
+            let selected = state.drill_holes == Some(entity);
+            if ui.selectable_label(selected, name.as_str()).clicked() {
+                state.drill_holes = Some(entity);
+            }
+        }
+
+        let variables = state.drill_holes
+            .and_then(|entity| world.get::<DrillHolesData>(entity))
+            .map(|data| data.variables.clone())
+            .unwrap_or_default();
+        if state.variable.as_ref().map_or(true, |variable| !variables.contains(variable)) {
+            state.variable = variables.first().cloned();
+        }
+
+        ui.separator();
+
+        let settings = &mut state.settings;
+        egui::Grid::new("intercepts_settings").show(ui, |ui| {
+            ui.label("Variable");
+            egui::ComboBox::from_id_source("intercepts_variable")
+                .selected_text(state.variable.clone().unwrap_or_default())
+                .show_ui(ui, |ui| {
+                    for variable in variables.iter() {
+                        ui.selectable_value(&mut state.variable, Some(variable.clone()), variable);
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Unit");
+            ui.add(egui::TextEdit::singleline(&mut state.unit).desired_width(60.0));
+            ui.end_row();
+
+            ui.label("Cut-off grade");
+            ui.add(egui::DragValue::new(&mut settings.cutoff).clamp_range(0.0..=f32::MAX).speed(0.01));
+            ui.end_row();
+
+            ui.label("Minimum length");
+            ui.add(egui::DragValue::new(&mut settings.min_length).clamp_range(0.0..=1000.0).speed(0.1).suffix(" m"));
+            ui.end_row();
+
+            ui.label("Maximum internal dilution");
+            ui.add(egui::DragValue::new(&mut settings.max_dilution).clamp_range(0.0..=1000.0).speed(0.1).suffix(" m"));
+            ui.end_row();
+
+            ui.label("High grade includes");
+            ui.checkbox(&mut settings.include, "");
+            ui.end_row();
+
+            if settings.include {
+                ui.label("Include cut-off grade");
+                ui.add(egui::DragValue::new(&mut settings.include_cutoff).clamp_range(0.0..=f32::MAX).speed(0.01));
+                ui.end_row();
+
+                ui.label("Include minimum length");
+                ui.add(egui::DragValue::new(&mut settings.include_min_length).clamp_range(0.0..=1000.0).speed(0.1).suffix(" m"));
+                ui.end_row();
+            }
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if ui.button("Calculate").clicked() {
+                state.intercepts_result = Some(calculate(world, state));
+            }
+            if ui.button("Export CSV").clicked() {
+                state.intercepts_result = Some(export_csv(&state.intercepts, &state.grade_label));
+            }
+            if ui.button("Clear highlight").clicked() {
+                clear_highlight(world);
+            }
+        });
+
+        if let Some(status) = &state.intercepts_result {
+            match status {
+                Ok(()) => {
+                    ui.label(RichText::new("Success!").color(egui::Color32::GREEN));
+                }
+                Err(error) => {
+                    ui.label(RichText::new(error.to_string()).color(egui::Color32::RED));
+                }
+            }
+        }
+
+        ui.separator();
+        ui.label(format!("{} intercepts", state.intercepts.len()));
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for intercept in state.intercepts.iter() {
+                ui.label(summary(intercept, &state.grade_label));
+            }
+        });
+    }
+}
+
+fn calculate(world: &mut World, state: &mut InterceptsWindowState) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(drill_holes) = state.drill_holes else {
+        return Err("Select the drill holes".into());
+    };
+    let Some(data) = world.get::<DrillHolesData>(drill_holes) else {
+        return Err("The selected drill holes no longer exist".into());
+    };
+    let Some(variable) = state.variable.clone() else {
+        return Err("The drill holes have no assay variables".into());
+    };
+
+    state.intercepts = data.significant_intercepts(&variable, &state.settings);
+    state.grade_label = format!("{} {}", state.unit, element_name(&variable)).trim().to_string();
+
+    let runs = state.intercepts.iter()
+        .flat_map(|found| {
+            let intercept = &found.intercept;
+            std::iter::once((found.hole, intercept.from, intercept.to, INTERCEPT_RADIUS, INTERCEPT_COLOR))
+                .chain(intercept.includes.iter().map(move |include| (found.hole, include.from, include.to, INCLUDE_RADIUS, INCLUDE_COLOR)))
+        })
+        .collect::<Vec<_>>();
+    let mesh = data.runs_mesh(&runs);
+
+    clear_highlight(world);
+
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(mesh);
+    let material = world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial::default());
+    let highlight = world.spawn((
+        PbrBundle {
+            mesh,
+            material,
+            ..Default::default()
+        },
+        Name::new("Significant intercepts"),
+        InterceptsHighlight,
+    )).id();
+    world.entity_mut(drill_holes).add_child(highlight);
+
+    if state.intercepts.is_empty() {
+        return Err("No intercept above the cut-off".into());
+    }
+    Ok(())
+}
+
+fn clear_highlight(world: &mut World) {
+    let highlights = world.query_filtered::<Entity, With<InterceptsHighlight>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    for highlight in highlights {
+        world.entity_mut(highlight).despawn_recursive();
+    }
+}
+
+/// "au" as "Au", the way assay columns are usually named in reports.
+fn element_name(variable: &str) -> String {
+    let mut chars = variable.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn intercept_text(intercept: &Intercept, grade_label: &str) -> String {
+    format!("{:.1} m @ {:.2} {}", intercept.length(), intercept.grade, grade_label)
+}
+
+/// Intercept as reported in press releases,
+/// "HOLE-12: 34.0 m @ 1.20 g/t Au from 120.0 m incl. 6.0 m @ 4.50 g/t Au".
+fn summary(found: &HoleIntercept, grade_label: &str) -> String {
+    let mut text = format!(
+        "{}: {} from {:.1} m",
+        found.hole_id,
+        intercept_text(&found.intercept, grade_label),
+        found.intercept.from,
+    );
+    for (i, include) in found.intercept.includes.iter().enumerate() {
+        text.push_str(if i == 0 { " incl. " } else { " and " });
+        text.push_str(&format!("{} from {:.1} m", intercept_text(include, grade_label), include.from));
+    }
+    text
+}
+
+/// One row per intercept and include, includes follow the intercept they belong to.
+fn export_csv(intercepts: &[HoleIntercept], grade_label: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if intercepts.is_empty() {
+        return Err("Calculate the intercepts first".into());
+    }
+
+    let mut hole_ids = vec![];
+    let mut kinds = vec![];
+    let mut from = vec![];
+    let mut to = vec![];
+    let mut length = vec![];
+    let mut grade = vec![];
+    let mut summaries = vec![];
+    for found in intercepts {
+        let rows = std::iter::once(("intercept", &found.intercept))
+            .chain(found.intercept.includes.iter().map(|include| ("include", include)));
+        for (kind, intercept) in rows {
+            hole_ids.push(found.hole_id.clone());
+            kinds.push(kind);
+            from.push(intercept.from);
+            to.push(intercept.to);
+            length.push(intercept.length());
+            grade.push(intercept.grade);
+            summaries.push(if kind == "intercept" { summary(found, grade_label) } else { String::new() });
+        }
+    }
+
+    let df = DataFrame::new(vec![
+        Series::new("hole-id", hole_ids),
+        Series::new("type", kinds),
+        Series::new("from", from),
+        Series::new("to", to),
+        Series::new("length", length),
+        Series::new("grade", grade),
+        Series::new("summary", summaries),
+    ])?;
+
+    if let Some(path) = rfd::FileDialog::new().add_filter("Intercepts (csv)", &["csv"]).save_file() {
+        write_dataframe(&df, &path.display().to_string())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use bevy::prelude::{App, World};
+use bevy_editor_pls_core::editor_window::{EditorWindow, EditorWindowContext, MenuBarWindow};
+use bevy_inspector_egui::egui::*;
+use egui::RichText;
+
+use crate::custom_meshes::lithology_legend::{LithologyLegend, DEFAULT_LEGEND_FILENAME};
+
+#[derive(Default)]
+pub struct LithologyLegendWindowState {
+    new_code: String,
+    legend_file_result: Option<Result<(), Box<dyn std::error::Error + Send + Sync>>>,
+}
+
+pub struct LithologyLegendWindow;
+
+impl EditorWindow for LithologyLegendWindow {
+    type State = LithologyLegendWindowState;
+    const NAME: &'static str = "Lithology Legend";
+    const DEFAULT_SIZE: (f32, f32) = (300.0, 400.0);
+    const MENU_BAR: MenuBarWindow = MenuBarWindow::Edit;
+
+    fn ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let state = cx.state_mut::<LithologyLegendWindow>().unwrap();
+        let mut legend = world.resource_mut::<LithologyLegend>();
+
+        let mut remove = None;
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                egui::Grid::new("lithology_legend").striped(true).show(ui, |ui| {
+                    // Only flag the resource as changed when a colour is actually edited
+                    let mut colors = legend.bypass_change_detection().colors.clone();
+                    for (code, color) in colors.iter_mut() {
+                        ui.label(code);
+                        if ui.color_edit_button_rgb(color).changed() {
+                            legend.colors.insert(code.clone(), *color);
+                        }
+                        if ui.small_button("\u{1F5D1}").clicked() {
+                            remove = Some(code.clone());
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+
+        if let Some(code) = remove {
+            legend.colors.shift_remove(&code);
+        }
+
+        ui.horizontal(|ui| {
+            egui::TextEdit::singleline(&mut state.new_code)
+                .hint_text("Rock code")
+                .desired_width(120.0)
+                .show(ui);
+            if ui.button("Add").clicked() && !state.new_code.is_empty() {
+                legend.color(&state.new_code);
+                state.new_code.clear();
+            }
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                state.legend_file_result = Some(legend.save(DEFAULT_LEGEND_FILENAME));
+            }
+            if ui.button("Reload").clicked() {
+                state.legend_file_result = Some(
+                    LithologyLegend::load(DEFAULT_LEGEND_FILENAME).map(|loaded| *legend = loaded),
+                );
+            }
+        });
+
+        if let Some(status) = &state.legend_file_result {
+            match status {
+                Ok(()) => {
+                    ui.label(RichText::new("Success!").color(egui::Color32::GREEN));
+                }
+                Err(error) => {
+                    ui.label(RichText::new(error.to_string()).color(egui::Color32::RED));
+                }
+            }
+        }
+    }
+
+    fn app_setup(app: &mut App) {
+        let legend = LithologyLegend::load(DEFAULT_LEGEND_FILENAME).unwrap_or_default();
+        app.insert_resource(legend);
+    }
+}
@@ -1,17 +1,23 @@
 
 use std::error::Error;
+use std::path::Path;
 
-use bevy::prelude::{Entity, With, Mesh, World, Name, Assets, StandardMaterial, PbrBundle, BuildWorldChildren};
+use bevy::prelude::{App, Assets, Entity, Handle, Mesh, With, World, Name, BuildWorldChildren, Update};
 use bevy_editor_pls_core::editor_window::{EditorWindow, EditorWindowContext, MenuBarWindow};
 use bevy_inspector_egui::egui::*;
 use egui::{RichText};
 
 use crate::custom_meshes::topography_mesh::TopographyMesh;
-use crate::custom_meshes::drill_holes_mesh::DrillHolesMesh;
+use crate::custom_meshes::drill_holes_mesh::{spawn_drill_holes, update_drill_holes_layers, CollarDelta, DrillHolesMesh};
+use crate::custom_meshes::lithology_legend::LithologyLegend;
+use crate::files_manager::column_mapping::{ColumnMapping, DrillTable};
 use crate::files_manager::csv_parser::CsvFile;
+use crate::files_manager::drill_holes_validation::ValidationReport;
+use crate::math::desurvey::DesurveyMethod;
+use crate::math::surface::SurfaceGrid;
+use crate::ui_windows::drill_holes_labels::{self, DrillHolesLabels};
 
 
-#[derive(Default)]
 pub struct LoadDrillsWindowState{
     assays: String,
     assays_headers: bool,
@@ -22,9 +28,86 @@ pub struct LoadDrillsWindowState{
     survey: String,
     survey_headers: bool,
     topography_mesh: Option<Entity>,
+    desurvey_method: DesurveyMethod,
+    columns: [TableColumns; 4],
+    skip_bad_records: bool,
+    merge_holes: bool,
+    validation_report: Option<ValidationReport>,
+    collar_elevation: CollarElevation,
+    collar_tolerance: f32,
+    collar_deltas: Vec<CollarDelta>,
     load_files_result: Option<Result<(), Box<dyn Error + Send + Sync>>>,
 }
 
+/// How collar elevations are checked against the linked topography when loading.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollarElevation {
+    #[default]
+    Keep,
+    Report,
+    Snap,
+}
+
+impl CollarElevation {
+    pub fn name(self) -> &'static str {
+        match self {
+            CollarElevation::Keep => "Keep surveyed Z",
+            CollarElevation::Report => "Report differences",
+            CollarElevation::Snap => "Snap to topography",
+        }
+    }
+
+    pub fn all() -> [CollarElevation; 3] {
+        [CollarElevation::Keep, CollarElevation::Report, CollarElevation::Snap]
+    }
+}
+
+/// Header preview and column mapping of one of the four csv files.
+pub struct TableColumns{
+    source: (String, bool),
+    headers: Vec<String>,
+    preview: Vec<Vec<String>>,
+    preview_error: Option<String>,
+    mapping: ColumnMapping,
+}
+
+impl TableColumns{
+    fn new(table: DrillTable) -> Self{
+        Self{
+            source: (String::new(), false),
+            headers: vec![],
+            preview: vec![],
+            preview_error: None,
+            mapping: ColumnMapping::new(table),
+        }
+    }
+}
+
+impl Default for LoadDrillsWindowState{
+    fn default() -> Self{
+        Self{
+            assays: String::new(),
+            assays_headers: false,
+            header: String::new(),
+            header_headers: false,
+            lithography: String::new(),
+            lithography_headers: false,
+            survey: String::new(),
+            survey_headers: false,
+            topography_mesh: None,
+            desurvey_method: DesurveyMethod::default(),
+            columns: DrillTable::all().map(TableColumns::new),
+            skip_bad_records: false,
+            merge_holes: false,
+            validation_report: None,
+            collar_elevation: CollarElevation::default(),
+            collar_tolerance: 0.5,
+            collar_deltas: vec![],
+            load_files_result: None,
+        }
+    }
+}
+
 pub struct LoadDrills;
 
 impl EditorWindow for LoadDrills {
@@ -56,6 +139,8 @@ impl EditorWindow for LoadDrills {
                 }
             });
 
+            columns_ui(ui, DrillTable::Assay, &state.assays, state.assays_headers, &mut state.columns[0]);
+
             ui.horizontal(|ui|{
                 egui::TextEdit::singleline(&mut state.header)
                     .hint_text("HOLE-ID, X, Y, Z, LENGTH")
@@ -70,6 +155,8 @@ impl EditorWindow for LoadDrills {
                 }
             });
 
+            columns_ui(ui, DrillTable::Header, &state.header, state.header_headers, &mut state.columns[1]);
+
             ui.horizontal(|ui|{
                 egui::TextEdit::singleline(&mut state.lithography)
                     .hint_text("HOLE-ID, FROM, TO, ROCK")
@@ -84,6 +171,8 @@ impl EditorWindow for LoadDrills {
                 }
             });
 
+            columns_ui(ui, DrillTable::Lithology, &state.lithography, state.lithography_headers, &mut state.columns[2]);
+
             ui.horizontal(|ui|{
                 egui::TextEdit::singleline(&mut state.survey)
                     .hint_text("HOLE-ID, FROM, TO, AZIMUTH, DIP")
@@ -97,6 +186,19 @@ impl EditorWindow for LoadDrills {
                     }
                 }
             });
+            columns_ui(ui, DrillTable::Survey, &state.survey, state.survey_headers, &mut state.columns[3]);
+
+            ui.horizontal(|ui|{
+                ui.label("Desurvey method: ");
+                egui::ComboBox::from_id_source("desurvey_method")
+                    .selected_text(state.desurvey_method.name())
+                    .show_ui(ui, |ui|{
+                        for method in DesurveyMethod::all(){
+                            ui.selectable_value(&mut state.desurvey_method, method, method.name());
+                        }
+                    });
+            });
+
             ui.label("Select Topography that will be linked to the drill holes: ");
             ui.horizontal(|ui|{
                 let mut filtered_query = world
@@ -112,6 +214,23 @@ impl EditorWindow for LoadDrills {
 
             });
 
+            ui.horizontal(|ui|{
+                ui.label("Collar elevation: ");
+                egui::ComboBox::from_id_source("collar_elevation")
+                    .selected_text(state.collar_elevation.name())
+                    .show_ui(ui, |ui|{
+                        for option in CollarElevation::all(){
+                            ui.selectable_value(&mut state.collar_elevation, option, option.name());
+                        }
+                    });
+                if state.collar_elevation != CollarElevation::Keep {
+                    ui.label("Tolerance: ");
+                    ui.add(egui::DragValue::new(&mut state.collar_tolerance).clamp_range(0.0..=100.0).speed(0.05).suffix(" m"));
+                }
+            });
+
+            ui.checkbox(&mut state.merge_holes, "Merge holes in one mesh per variable (large campaigns)");
+
             let enter_pressed = ui.input(|input| input.key_pressed(egui::Key::Enter));
 
             if state.topography_mesh == None {
@@ -120,12 +239,28 @@ impl EditorWindow for LoadDrills {
 
             ui.separator();
 
-            if ui.button("Load Files").clicked() || enter_pressed {
-                state.load_files_result = Some(load_files(world, state));
-            }
+            ui.horizontal(|ui|{
+                if ui.button("Validate").clicked() {
+                    state.load_files_result = Some(validate_files(world, state));
+                }
+
+                if ui.button("Load Files").clicked() || enter_pressed {
+                    state.load_files_result = Some(load_files(world, state));
+                }
+
+                ui.checkbox(&mut state.skip_bad_records, "Skip bad records");
+            });
 
         });
 
+        if let Some(report) = &state.validation_report {
+            validation_report_ui(ui, report);
+        }
+
+        if !state.collar_deltas.is_empty() {
+            collar_deltas_ui(ui, &state.collar_deltas, state.collar_tolerance, &mut state.load_files_result);
+        }
+
         if let Some(status) = &state.load_files_result {
             match status {
                 Ok(()) => {
@@ -137,13 +272,21 @@ impl EditorWindow for LoadDrills {
             }
         }
     }
+
+    fn viewport_ui(world: &mut World, _cx: EditorWindowContext, ui: &mut egui::Ui) {
+        drill_holes_labels::viewport_ui(world, ui);
+    }
+
+    fn app_setup(app: &mut App) {
+        app.add_systems(Update, update_drill_holes_layers);
+    }
 }
 
-fn load_files(
+/// Builds the loader from the window fields.
+fn drill_holes_from_state(
     world: &mut World,
-    state: &mut LoadDrillsWindowState
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-
+    state: &LoadDrillsWindowState
+) -> Result<DrillHolesMesh, Box<dyn Error + Send + Sync>> {
 
     let assays_contents = CsvFile{
         path: state.assays.to_string(),
@@ -166,11 +309,23 @@ fn load_files(
         sep: b',',
     };
 
+    for columns in state.columns.iter(){
+        let missing = columns.mapping.missing_roles();
+        if !missing.is_empty(){
+            let missing = missing.iter().map(|role| role.name()).collect::<Vec<_>>().join(", ");
+            return Err(format!("Missing {} columns: {}", columns.mapping.table.name(), missing).into());
+        }
+    }
+
+    let mappings = DrillTable::all().map(|table| state.columns[table as usize].mapping.clone());
+
     let mut drill_holes = DrillHolesMesh{
         files: [assays_contents, header_contents, lithography_contents, survey_contents],
+        mappings,
         offset_x: None,
         offset_y: None,
         offset_z: None,
+        desurvey_method: state.desurvey_method,
     };
 
     if state.topography_mesh!= None {
@@ -188,31 +343,296 @@ fn load_files(
         }
     }
 
-    let final_meshes = DrillHolesMesh::from_csv(drill_holes);
+    Ok(drill_holes)
+}
+
+fn validate_files(
+    world: &mut World,
+    state: &mut LoadDrillsWindowState
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let drill_holes = drill_holes_from_state(world, state)?;
+    let [df_assay, df_header, df_lithography, df_survey] = drill_holes.read_tables()?;
+    let variables = drill_holes.mappings[0].variables.iter()
+        .map(|variable| variable.to_lowercase())
+        .collect::<Vec<_>>();
+    let report = ValidationReport::validate(&df_header, &df_survey, &df_assay, &df_lithography, &variables)?;
+    state.validation_report = Some(report);
+    Ok(())
+}
+
+fn load_files(
+    world: &mut World,
+    state: &mut LoadDrillsWindowState
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(topography_mesh) = state.topography_mesh else {
+        return Err("Select the topography the drill holes will be linked to".into());
+    };
+
+    let drill_holes = drill_holes_from_state(world, state)?;
+    let import = DrillHolesMesh::from_csv(drill_holes, state.skip_bad_records)?;
+    let errors = import.report.errors();
+    state.validation_report = Some(import.report);
+
+    let Some(mut data) = import.data else {
+        return Err(format!("{} invalid records, fix them or skip them to load the rest", errors).into());
+    };
+
+    state.collar_deltas.clear();
+    if state.collar_elevation != CollarElevation::Keep {
+        let surface = world.get::<Handle<Mesh>>(topography_mesh)
+            .and_then(|handle| world.resource::<Assets<Mesh>>().get(handle))
+            .and_then(SurfaceGrid::from_mesh)
+            .ok_or("The topography has no triangles to compare the collars with")?;
+
+        let deltas = data.collar_deltas(&surface);
+        if state.collar_elevation == CollarElevation::Snap {
+            // Collars within the tolerance are taken as right and kept where they are
+            let off = deltas.iter()
+                .filter(|delta| delta.delta().map_or(false, |dz| dz.abs() > state.collar_tolerance))
+                .cloned()
+                .collect::<Vec<_>>();
+            data.snap_collars(&off)?;
+        }
+        state.collar_deltas = deltas;
+    }
+
+    let mut legend = world.resource_mut::<LithologyLegend>();
+    for code in data.rock_codes(){
+        legend.color(&code);
+    }
+
+    let drill_holes_id = spawn_drill_holes(world, data, "Drill Holes", !state.merge_holes);
+    world.entity_mut(drill_holes_id).insert(DrillHolesLabels::default());
+    world.entity_mut(topography_mesh).add_child(drill_holes_id);
+
+    // Only a mapping that loaded is remembered in the project folder
+    if let Some(project_dir) = Path::new(&state.header).parent(){
+        let mappings = DrillTable::all().map(|table| state.columns[table as usize].mapping.clone());
+        ColumnMapping::save_project(project_dir, &mappings)?;
+    }
+
+    Ok(())
+}
+
+/// Previews the file header and lets the user assign a column to every role of the table.
+/// The mapping is reloaded from the project folder, or guessed, whenever the file changes.
+fn columns_ui(ui: &mut egui::Ui, table: DrillTable, path: &str, has_headers: bool, columns: &mut TableColumns){
+    let source = (path.to_string(), has_headers);
+    if columns.source != source {
+        columns.source = source;
+        refresh_columns(table, columns);
+    }
+
+    if columns.headers.is_empty() && columns.preview_error.is_none() {
+        return;
+    }
+
+    egui::CollapsingHeader::new(format!("Columns ({})", table.name()))
+        .id_source(("drill_columns", table.name()))
+        .show(ui, |ui|{
+            if let Some(error) = &columns.preview_error {
+                ui.label(RichText::new(error).color(egui::Color32::RED));
+                return;
+            }
+
+            egui::Grid::new(("drill_columns_roles", table.name())).show(ui, |ui|{
+                for role in table.roles(){
+                    if role.required() && !columns.mapping.columns.contains_key(role) {
+                        ui.label(RichText::new(role.name()).color(egui::Color32::RED));
+                    } else {
+                        ui.label(role.name());
+                    }
+
+                    let selected = columns.mapping.columns.get(role).cloned();
+                    let mut new_selected = selected.clone();
+                    egui::ComboBox::from_id_source(("drill_column_role", table.name(), role.column_name()))
+                        .selected_text(selected.clone().unwrap_or("-".to_string()))
+                        .show_ui(ui, |ui|{
+                            ui.selectable_value(&mut new_selected, None, "-");
+                            for header in columns.headers.iter(){
+                                ui.selectable_value(&mut new_selected, Some(header.clone()), header);
+                            }
+                        });
+
+                    if new_selected != selected {
+                        match new_selected {
+                            Some(header) => { columns.mapping.columns.insert(*role, header); }
+                            None => { columns.mapping.columns.shift_remove(role); }
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+
+            if table == DrillTable::Assay {
+                ui.label("Variables:");
+                ui.horizontal_wrapped(|ui|{
+                    for header in columns.mapping.unassigned(&columns.headers){
+                        let mut checked = columns.mapping.variables.contains(header);
+                        if ui.checkbox(&mut checked, header).changed() {
+                            if checked {
+                                columns.mapping.variables.push(header.clone());
+                            } else {
+                                columns.mapping.variables.retain(|variable| variable != header);
+                            }
+                        }
+                    }
+                });
+            }
+
+            if ui.button("Guess from headers").clicked() {
+                columns.mapping = ColumnMapping::guess(table, &columns.headers, &columns.preview);
+            }
+
+            egui::ScrollArea::horizontal()
+                .id_source(("drill_columns_preview", table.name()))
+                .show(ui, |ui|{
+                    egui::Grid::new(("drill_columns_preview_grid", table.name())).striped(true).show(ui, |ui|{
+                        for header in columns.headers.iter(){
+                            ui.label(RichText::new(header).strong());
+                        }
+                        ui.end_row();
+                        for row in columns.preview.iter(){
+                            for field in row.iter(){
+                                ui.label(field);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+        });
+}
+
+fn refresh_columns(table: DrillTable, columns: &mut TableColumns){
+    let (path, has_headers) = &columns.source;
+    let csv = CsvFile{
+        path: path.clone(),
+        header: *has_headers,
+        sep: b',',
+    };
 
-    for final_mesh in final_meshes{
-        let mut meshes = world.get_resource_mut::<Assets<Mesh>>().unwrap();
-        let mesh = meshes.add(final_mesh);
+    match csv.preview(5) {
+        Ok((headers, preview)) => {
+            columns.headers = headers;
+            columns.preview = preview;
+            columns.preview_error = None;
+        }
+        Err(error) => {
+            columns.headers = vec![];
+            columns.preview = vec![];
+            columns.preview_error = if path.is_empty() { None } else { Some(error.to_string()) };
+        }
+    }
 
-        let mut materials = world
-            .get_resource_mut::<Assets<StandardMaterial>>()
-            .unwrap();
-        let material = materials.add(
-            StandardMaterial::default()
-        );
+    let saved = Path::new(path).parent()
+        .and_then(|dir| ColumnMapping::load_project(dir).ok())
+        .map(|mut mappings| std::mem::replace(&mut mappings[table as usize], ColumnMapping::new(table)))
+        .filter(|mapping| mapping.columns.values().all(|column| columns.headers.contains(column)) && !mapping.columns.is_empty());
 
-        let drill_holes_id = world.spawn((PbrBundle {
-            mesh,
-            material,
-            ..Default::default()
-        },
-                                          Name::new("Drill Holes")
-        )).id();
+    columns.mapping = saved.unwrap_or_else(|| ColumnMapping::guess(table, &columns.headers, &columns.preview));
+}
 
-        world.entity_mut(state.topography_mesh.unwrap()).add_child(drill_holes_id);
+fn validation_report_ui(ui: &mut egui::Ui, report: &ValidationReport){
+    ui.separator();
+    if report.issues.is_empty() {
+        ui.label(RichText::new("No problems found").color(egui::Color32::GREEN));
+        return;
     }
 
-    //TODO
+    ui.label(format!("{} errors, {} warnings", report.errors(), report.warnings()));
+    egui::ScrollArea::both()
+        .id_source("drill_holes_validation")
+        .max_height(200.0)
+        .show(ui, |ui|{
+            egui::Grid::new("drill_holes_validation_grid").striped(true).show(ui, |ui|{
+                for title in ["", "Table", "Row", "Hole", "Issue", "Detail"]{
+                    ui.label(RichText::new(title).strong());
+                }
+                ui.end_row();
 
+                for issue in report.issues.iter(){
+                    if issue.kind.is_error() {
+                        ui.label(RichText::new("\u{26D4}").color(egui::Color32::RED));
+                    } else {
+                        ui.label(RichText::new("\u{26A0}").color(egui::Color32::YELLOW));
+                    }
+                    ui.label(issue.table.name());
+                    ui.label((issue.row + 1).to_string());
+                    ui.label(&issue.hole_id);
+                    ui.label(issue.kind.name());
+                    ui.label(&issue.message);
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+/// Collars further than `tolerance` from the topography, largest differences first.
+fn collar_deltas_ui(
+    ui: &mut egui::Ui,
+    deltas: &[CollarDelta],
+    tolerance: f32,
+    result: &mut Option<Result<(), Box<dyn Error + Send + Sync>>>
+){
+    let mut off = deltas.iter()
+        .filter(|delta| delta.delta().map_or(true, |dz| dz.abs() > tolerance))
+        .collect::<Vec<_>>();
+    off.sort_by(|a, b| {
+        let a = a.delta().map_or(f32::INFINITY, f32::abs);
+        let b = b.delta().map_or(f32::INFINITY, f32::abs);
+        b.total_cmp(&a)
+    });
+
+    ui.separator();
+    ui.horizontal(|ui|{
+        ui.label(format!("{} of {} collars off the topography by more than {} m", off.len(), deltas.len(), tolerance));
+        if ui.button("Export CSV").clicked() {
+            if let Some(path) = rfd::FileDialog::new().add_filter("Collar elevations (csv)", &["csv"]).save_file() {
+                *result = Some(save_collar_deltas(&path.display().to_string(), deltas));
+            }
+        }
+    });
+
+    egui::ScrollArea::vertical()
+        .id_source("collar_deltas")
+        .max_height(200.0)
+        .show(ui, |ui|{
+            egui::Grid::new("collar_deltas_grid").striped(true).show(ui, |ui|{
+                for title in ["Hole", "Collar Z", "Topography Z", "Delta"]{
+                    ui.label(RichText::new(title).strong());
+                }
+                ui.end_row();
+
+                for delta in off {
+                    ui.label(&delta.hole_id);
+                    ui.label(format!("{:.2}", delta.collar_z));
+                    match (delta.surface_z, delta.delta()) {
+                        (Some(surface_z), Some(dz)) => {
+                            ui.label(format!("{:.2}", surface_z));
+                            ui.label(RichText::new(format!("{:+.2}", dz)).color(egui::Color32::YELLOW));
+                        }
+                        _ => {
+                            ui.label("-");
+                            ui.label(RichText::new("outside topography").color(egui::Color32::RED));
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+fn save_collar_deltas(path: &str, deltas: &[CollarDelta]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut writer = csv::WriterBuilder::new().from_path(path)?;
+    writer.write_record(["hole-id", "collar_z", "topography_z", "delta"])?;
+    for delta in deltas {
+        writer.write_record([
+            delta.hole_id.clone(),
+            delta.collar_z.to_string(),
+            delta.surface_z.map(|z| z.to_string()).unwrap_or_default(),
+            delta.delta().map(|dz| dz.to_string()).unwrap_or_default(),
+        ])?;
+    }
+    writer.flush()?;
     Ok(())
 }
@@ -10,4 +10,17 @@ pub mod renderer;
 pub mod resources;
 pub mod scenes;
 pub mod nodes_creator;
-pub mod load_drills;
\ No newline at end of file
+pub mod load_drills;
+pub mod lithology_legend;
+pub mod drill_holes_layer;
+pub mod composite_drills;
+pub mod drill_holes_labels;
+pub mod color_legend;
+pub mod intercepts;
+pub mod downhole_logs;
+pub mod export_drills;
+pub mod plan_holes;
+pub mod contours;
+pub mod surface_volumes;
+pub mod orthophoto;
+pub mod export_surface;
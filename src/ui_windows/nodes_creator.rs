@@ -1,19 +1,131 @@
-use bevy::prelude::{Assets, Color, StandardMaterial, World, Mesh, PbrBundle, Name};
+use bevy::prelude::{App, Assets, Children, Entity, Handle, Mut, StandardMaterial, Update, World, Mesh, PbrBundle, Name};
 use bevy_inspector_egui::egui::*;
 use bevy_editor_pls_core::editor_window::{EditorWindow, EditorWindowContext, MenuBarWindow};
-use crate::custom_meshes::topography_mesh::TopographyMesh;
+use crate::custom_meshes::topography_lod::{lod_of, refresh_lod_tiles, remove_lod, spawn_lod, update_lod_visibility, LodTile, TopographyLod, DEFAULT_TILE_SIZE};
+use crate::custom_meshes::topography_mesh::{update_topography_shading, ElevationDifference, TopographyMesh, TopographyShading, TOPOGRAPHY_COLOR};
 use crate::files_manager::csv_parser::CsvFile;
+use crate::files_manager::dem_parser::DemFile;
 use crate::files_manager::dxf_parser::DxfFile;
 use crate::files_manager::files_porperties::FileProperties;
+use crate::math::decimation::DecimationTarget;
+use crate::math::surface_trim::SurfaceTrim;
+use crate::ui_windows::color_legend::legend_editor_ui;
 use crate::ui_windows::load_drills::LoadDrills;
 use crate::ui_windows::scenes::SceneWindow;
 
 #[derive(Default)]
 pub struct NodesCreatorState{
     search: String,
+    trim: TrimOptions,
+    grid: GridOptions,
+    decimation: DecimationOptions,
     load_node_result: Option<Result<(), Box<dyn std::error::Error + Send + Sync>>>
 }
 
+/// Trimming of the next topography created.
+pub struct TrimOptions{
+    /// DXF layer of the closed polylines bounding the surface
+    boundary_layer: String,
+    /// DXF layer of the closed polylines cut out of the surface
+    holes_layer: String,
+    trim_edges: bool,
+    max_edge_length: f64,
+    trim_alpha: bool,
+    alpha: f64,
+}
+
+impl Default for TrimOptions{
+    fn default() -> Self {
+        Self{
+            boundary_layer: String::new(),
+            holes_layer: String::new(),
+            trim_edges: false,
+            max_edge_length: 100.0,
+            trim_alpha: false,
+            alpha: 100.0,
+        }
+    }
+}
+
+impl TrimOptions{
+    /// Polygon layers of the DXF that are not part of the surface.
+    fn layers(&self) -> Vec<&str>{
+        [self.boundary_layer.trim(), self.holes_layer.trim()].into_iter()
+            .filter(|layer| !layer.is_empty())
+            .collect()
+    }
+
+    /// Trimming with the closed polylines of the layers of the DXF. A layer without any is an
+    /// error rather than a surface left untrimmed.
+    fn surface_trim(&self, dxf: Option<&DxfFile>) -> Result<SurfaceTrim, Box<dyn std::error::Error + Send + Sync>>{
+        let polygons = |name: &str, layer: &str| -> Result<Vec<Vec<[f64;2]>>, Box<dyn std::error::Error + Send + Sync>> {
+            let layer = layer.trim();
+            let Some(dxf) = dxf.filter(|_| !layer.is_empty()) else {
+                return Ok(vec![]);
+            };
+            let polygons = dxf.get_polygons(Some(layer))?;
+            if polygons.is_empty() {
+                return Err(format!("The {} layer '{}' has no closed polylines", name, layer).into());
+            }
+            Ok(polygons)
+        };
+        Ok(SurfaceTrim{
+            boundaries: polygons("boundary", &self.boundary_layer)?,
+            holes: polygons("holes", &self.holes_layer)?,
+            max_edge_length: self.trim_edges.then_some(self.max_edge_length),
+            alpha: self.trim_alpha.then_some(self.alpha),
+        })
+    }
+}
+
+/// Reading of the next gridded topography created.
+pub struct GridOptions{
+    /// Keeps one of every `step` columns and rows
+    step: usize,
+    /// Whether XYZ points at `nodata` are left out, ESRI grids have it in the header
+    use_nodata: bool,
+    nodata: f64,
+}
+
+impl Default for GridOptions{
+    fn default() -> Self {
+        Self{
+            step: 1,
+            use_nodata: true,
+            nodata: -9999.0,
+        }
+    }
+}
+
+/// Simplification of the next topography created, none when both targets are off.
+pub struct DecimationOptions{
+    by_triangles: bool,
+    triangles: usize,
+    by_tolerance: bool,
+    /// Metres
+    tolerance: f32,
+}
+
+impl Default for DecimationOptions{
+    fn default() -> Self {
+        Self{
+            by_triangles: false,
+            triangles: 500_000,
+            by_tolerance: false,
+            tolerance: 0.1,
+        }
+    }
+}
+
+impl DecimationOptions{
+    fn target(&self) -> DecimationTarget{
+        DecimationTarget{
+            triangles: self.by_triangles.then_some(self.triangles),
+            vertical_tolerance: self.by_tolerance.then_some(self.tolerance),
+        }
+    }
+}
+
 pub struct NodesCreator;
 
 impl EditorWindow for NodesCreator{
@@ -27,8 +139,123 @@ impl EditorWindow for NodesCreator{
     fn ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut Ui) {
         make_ui(world, &mut cx, ui);
     }
+
+    fn app_setup(app: &mut App) {
+        app.add_systems(Update, (update_topography_shading, update_lod_visibility, refresh_lod_tiles));
+    }
 }
 
+/// Inspector section of the topography shading.
+pub fn shading_ui(world: &mut World, entity: Entity, ui: &mut Ui) {
+    let Some(topography) = world.get::<TopographyMesh>(entity) else {
+        return;
+    };
+
+    let mut shading = topography.shading;
+    let mut hillshade = topography.hillshade;
+    let mut design_slope = topography.design_slope;
+    let mut legend = topography.legend.clone();
+    let mut reset_legend = false;
+
+    ui.separator();
+    ui.heading("Shading");
+    Grid::new("topography_shading").show(ui, |ui| {
+        ui.label("Colour by");
+        ComboBox::from_id_source("topography_shading_mode")
+            .selected_text(shading.name())
+            .show_ui(ui, |ui| {
+                for option in TopographyShading::all() {
+                    ui.selectable_value(&mut shading, option, option.name());
+                }
+            });
+        ui.end_row();
+
+        ui.label("Hillshade");
+        ui.checkbox(&mut hillshade, "");
+        ui.end_row();
+
+        if shading == TopographyShading::Slope {
+            ui.label("Design slope");
+            ui.add(DragValue::new(&mut design_slope).clamp_range(1.0..=90.0).speed(0.1).suffix("°"));
+            ui.end_row();
+        }
+    });
+    if shading != TopographyShading::Flat {
+        legend_editor_ui(ui, "topography_shading_legend", &mut legend);
+        if ui.button("Reset range").clicked() {
+            reset_legend = true;
+        }
+    }
+    if let Some(difference) = world.get::<ElevationDifference>(entity) {
+        ui.label(RichText::new(format!("Hidden by the difference map to {}", difference.base)).weak());
+    }
+
+    let topography = world.get::<TopographyMesh>(entity).unwrap();
+    let changed = shading != topography.shading || hillshade != topography.hillshade
+        || design_slope != topography.design_slope || legend != topography.legend;
+    if !(changed || reset_legend) {
+        return;
+    }
+
+    let handle = world.get::<Handle<Mesh>>(entity).cloned();
+    world.resource_scope(|world, meshes: Mut<Assets<Mesh>>| {
+        let mut topography = world.get_mut::<TopographyMesh>(entity).unwrap();
+        // The slope ramp runs up to the design angle
+        let shading_changed = shading != topography.shading || design_slope != topography.design_slope;
+        topography.shading = shading;
+        topography.hillshade = hillshade;
+        topography.design_slope = design_slope;
+        topography.legend = legend;
+        if let Some(mesh) = handle.and_then(|handle| meshes.get(&handle)).filter(|_| shading_changed || reset_legend) {
+            topography.reset_legend(mesh);
+        }
+    });
+}
+
+
+/// Inspector section of the tiled level of detail of the topography.
+pub fn lod_ui(world: &mut World, entity: Entity, ui: &mut Ui) {
+    if world.get::<TopographyMesh>(entity).is_none() {
+        return;
+    }
+
+    ui.separator();
+    ui.heading("Level of detail");
+    let Some(lod) = lod_of(world, entity) else {
+        if ui.button("Build tiles").clicked() {
+            spawn_lod(world, entity, DEFAULT_TILE_SIZE, DEFAULT_TILE_SIZE * 2.0);
+        }
+        return;
+    };
+
+    let tiles = world.get::<Children>(lod)
+        .map_or(0, |children| children.iter().filter(|child| world.get::<LodTile>(**child).map_or(false, |tile| tile.level == 0)).count());
+    ui.label(format!("{} tiles", tiles));
+
+    let mut rebuild = false;
+    let mut remove = false;
+    let mut settings = world.get_mut::<TopographyLod>(lod).unwrap();
+    Grid::new("topography_lod").show(ui, |ui| {
+        ui.label("Tile size");
+        ui.add(DragValue::new(&mut settings.tile_size).clamp_range(10.0..=10000.0).speed(1.0).suffix(" m"));
+        ui.end_row();
+
+        ui.label("Simplify from");
+        ui.add(DragValue::new(&mut settings.switch_distance).clamp_range(1.0..=100000.0).speed(1.0).suffix(" m"));
+        ui.end_row();
+    });
+    let (tile_size, switch_distance) = (settings.tile_size, settings.switch_distance);
+    ui.horizontal(|ui| {
+        rebuild = ui.button("Rebuild tiles").clicked();
+        remove = ui.button("Remove").clicked();
+    });
+
+    if rebuild {
+        spawn_lod(world, entity, tile_size, switch_distance);
+    } else if remove {
+        remove_lod(world, entity);
+    }
+}
 
 fn make_ui(world: &mut World,
            cx: &mut EditorWindowContext,
@@ -64,7 +291,8 @@ fn make_ui(world: &mut World,
                                             let dxf = DxfFile{
                                                 path: Some(path.display().to_string()).unwrap()
                                             };
-                                            result = Option::from(generate_topography_mesh_from_dxf(&dxf, world));
+                                            let state = cx.state::<NodesCreator>().unwrap();
+                                            result = Option::from(generate_topography_mesh_from_dxf(&dxf, &state.trim, &state.decimation.target(), world));
                                             let state = cx.state_mut::<NodesCreator>().unwrap();
                                             state.load_node_result=result;
                                         }
@@ -77,11 +305,78 @@ fn make_ui(world: &mut World,
                                                 header: true,
                                                 sep: b',',
                                             };
-                                            result = Option::from(generate_topography_mesh_from_csv(csv, world));
+                                            let state = cx.state::<NodesCreator>().unwrap();
+                                            result = Option::from(state.trim.surface_trim(None)
+                                                .and_then(|surface_trim| generate_topography_mesh_from_csv(csv, &surface_trim, &state.decimation.target(), world)));
                                             let state = cx.state_mut::<NodesCreator>().unwrap();
                                             state.load_node_result=result;
                                         }
                                     }
+
+                                    if ui.selectable_label(false ,"\u{1F5B9} From grid file (asc, xyz)").clicked(){
+                                        if let Some(path) = rfd::FileDialog::new().add_filter("Elevation grids (asc, xyz)", &["asc", "grd", "xyz", "txt", "csv"]).pick_file() {
+                                            let dem = DemFile{
+                                                path: path.display().to_string(),
+                                            };
+                                            let state = cx.state::<NodesCreator>().unwrap();
+                                            result = Option::from(generate_topography_mesh_from_grid(dem, &state.grid, &state.decimation.target(), world));
+                                            let state = cx.state_mut::<NodesCreator>().unwrap();
+                                            state.load_node_result=result;
+                                        }
+                                    }
+
+                                    egui::CollapsingHeader::new("\u{25A6} Grid")
+                                        .default_open(false)
+                                        .show(ui, |ui|{
+                                            let grid = &mut cx.state_mut::<NodesCreator>().unwrap().grid;
+                                            egui::Grid::new("topography_grid").show(ui, |ui|{
+                                                ui.label("Downsample");
+                                                ui.add(egui::DragValue::new(&mut grid.step).clamp_range(1..=100).prefix("1 of every "));
+                                                ui.end_row();
+
+                                                ui.checkbox(&mut grid.use_nodata, "NODATA (xyz)");
+                                                ui.add_enabled(grid.use_nodata, egui::DragValue::new(&mut grid.nodata).speed(1.0));
+                                                ui.end_row();
+                                            });
+                                        });
+
+                                    egui::CollapsingHeader::new("\u{25BC} Decimation")
+                                        .default_open(false)
+                                        .show(ui, |ui|{
+                                            let decimation = &mut cx.state_mut::<NodesCreator>().unwrap().decimation;
+                                            egui::Grid::new("topography_decimation").show(ui, |ui|{
+                                                ui.checkbox(&mut decimation.by_triangles, "Target triangles");
+                                                ui.add_enabled(decimation.by_triangles, egui::DragValue::new(&mut decimation.triangles).clamp_range(100..=50_000_000).speed(1000.0));
+                                                ui.end_row();
+
+                                                ui.checkbox(&mut decimation.by_tolerance, "Vertical tolerance");
+                                                ui.add_enabled(decimation.by_tolerance, egui::DragValue::new(&mut decimation.tolerance).clamp_range(0.001..=100.0).speed(0.01).suffix(" m"));
+                                                ui.end_row();
+                                            });
+                                        });
+
+                                    egui::CollapsingHeader::new("\u{2702} Trimming")
+                                        .default_open(false)
+                                        .show(ui, |ui|{
+                                            let trim = &mut cx.state_mut::<NodesCreator>().unwrap().trim;
+                                            egui::Grid::new("topography_trim").show(ui, |ui|{
+                                                ui.label("Boundary layer (dxf)");
+                                                ui.text_edit_singleline(&mut trim.boundary_layer);
+                                                ui.end_row();
+
+                                                ui.label("Holes layer (dxf)");
+                                                ui.text_edit_singleline(&mut trim.holes_layer);
+                                                ui.end_row();
+
+                                                ui.checkbox(&mut trim.trim_edges, "Max edge length");
+                                                ui.add_enabled(trim.trim_edges, egui::DragValue::new(&mut trim.max_edge_length).clamp_range(0.1..=100000.0).suffix(" m"));
+                                                ui.end_row();
+
+                                                ui.checkbox(&mut trim.trim_alpha, "Alpha shape radius");
+                                                ui.add_enabled(trim.trim_alpha, egui::DragValue::new(&mut trim.alpha).clamp_range(0.1..=100000.0).suffix(" m"));
+                                                ui.end_row();
+                                            });
+                                        });
                                 });
                             if ui.selectable_label(false,"\u{1F4A2} Drill Holes").clicked(){
                                 cx.open_floating_window::<LoadDrills>();
@@ -108,11 +403,15 @@ fn make_ui(world: &mut World,
 
 
 
-fn generate_topography_mesh_from_dxf(dxf: &DxfFile, world: &mut World) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+fn generate_topography_mesh_from_dxf(dxf: &DxfFile, trim: &TrimOptions, decimation: &DecimationTarget, world: &mut World) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
-
-    let _points: Vec<[f64;3]> = dxf.get_points();
-    let (topography_mesh, topography) = TopographyMesh::from_points(_points);
+    let surface_trim = trim.surface_trim(Some(dxf))?;
+    let (_points, breaklines) = dxf.get_breaklines(&trim.layers());
+    if _points.is_empty() {
+        return Err("The DXF has no points outside of the boundary and holes layers".into());
+    }
+    let (topography_mesh, topography) = TopographyMesh::from_points(_points, &breaklines, &surface_trim);
+    let topography_mesh = decimated(topography_mesh, decimation);
 
     let mut meshes = world.get_resource_mut::<Assets<Mesh>>().unwrap();
     let mesh = meshes.add(topography_mesh);
@@ -122,7 +421,7 @@ fn generate_topography_mesh_from_dxf(dxf: &DxfFile, world: &mut World) -> Result
         .unwrap();
     let material = materials.add(
         StandardMaterial{
-            base_color: Color::rgb(135.0/255.0,135.0/255.0,73.0/255.0),
+            base_color: TOPOGRAPHY_COLOR,
             cull_mode: None,
             ..Default::default()
         }
@@ -137,8 +436,9 @@ fn generate_topography_mesh_from_dxf(dxf: &DxfFile, world: &mut World) -> Result
     Ok(())
 }
 
-fn generate_topography_mesh_from_csv(csv: CsvFile, world: &mut World) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let (topography_mesh, topography) = TopographyMesh::from_csv(&csv).unwrap();
+fn generate_topography_mesh_from_csv(csv: CsvFile, trim: &SurfaceTrim, decimation: &DecimationTarget, world: &mut World) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (topography_mesh, topography) = TopographyMesh::from_csv(&csv, trim).unwrap();
+    let topography_mesh = decimated(topography_mesh, decimation);
 
     let mut meshes = world.get_resource_mut::<Assets<Mesh>>().unwrap();
     let mesh = meshes.add(topography_mesh);
@@ -148,7 +448,7 @@ fn generate_topography_mesh_from_csv(csv: CsvFile, world: &mut World) -> Result<
         .unwrap();
     let material = materials.add(
         StandardMaterial{
-            base_color: Color::rgb(135.0/255.0,135.0/255.0,73.0/255.0),
+            base_color: TOPOGRAPHY_COLOR,
             cull_mode: None,
             ..Default::default()
         }
@@ -162,3 +462,42 @@ fn generate_topography_mesh_from_csv(csv: CsvFile, world: &mut World) -> Result<
 
     Ok(())
 }
+
+fn generate_topography_mesh_from_grid(dem: DemFile, options: &GridOptions, decimation: &DecimationTarget, world: &mut World) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut grid = dem.read(options.use_nodata.then_some(options.nodata))?;
+    if options.step > 1 {
+        grid = grid.downsample(options.step);
+    }
+    let (topography_mesh, topography) = TopographyMesh::from_grid(&grid)?;
+    let topography_mesh = decimated(topography_mesh, decimation);
+
+    let mut meshes = world.get_resource_mut::<Assets<Mesh>>().unwrap();
+    let mesh = meshes.add(topography_mesh);
+
+    let mut materials = world
+        .get_resource_mut::<Assets<StandardMaterial>>()
+        .unwrap();
+    let material = materials.add(
+        StandardMaterial{
+            base_color: TOPOGRAPHY_COLOR,
+            cull_mode: None,
+            ..Default::default()
+        }
+    );
+
+    world.spawn((PbrBundle {
+        mesh,
+        material,
+        ..Default::default()
+    }, topography, dem.clone(), Name::new(dem.name().unwrap())));
+
+    Ok(())
+}
+
+/// The mesh simplified to the target, as it is when there is none.
+fn decimated(mesh: Mesh, target: &DecimationTarget) -> Mesh {
+    if target.is_empty() {
+        return mesh;
+    }
+    TopographyMesh::decimate(&mesh, target).unwrap_or(mesh)
+}
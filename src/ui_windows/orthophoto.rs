@@ -0,0 +1,129 @@
+use std::error::Error;
+
+use bevy::prelude::*;
+use bevy_editor_pls_core::editor_window::{EditorWindow, EditorWindowContext, MenuBarWindow};
+use bevy_inspector_egui::egui;
+use egui::RichText;
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+use crate::custom_meshes::topography_mesh::TopographyMesh;
+use crate::files_manager::files_porperties::FileProperties;
+use crate::files_manager::orthophoto::ImageFile;
+
+/// Largest side of the texture, larger images are scaled down to fit the GPU limits.
+const MAX_TEXTURE_SIZE: u32 = 8192;
+
+#[derive(Default)]
+pub struct OrthophotoWindowState {
+    topography: Option<Entity>,
+    orthophoto_result: Option<Result<(), Box<dyn Error + Send + Sync>>>,
+}
+
+pub struct OrthophotoWindow;
+
+impl EditorWindow for OrthophotoWindow {
+    type State = OrthophotoWindowState;
+    const NAME: &'static str = "Drape Orthophoto";
+    const DEFAULT_SIZE: (f32, f32) = (350.0, 200.0);
+    const MENU_BAR: MenuBarWindow = MenuBarWindow::Edit;
+
+    fn ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let state = cx.state_mut::<OrthophotoWindow>().unwrap();
+
+        ui.label("Topography: ");
+        ui.horizontal(|ui| {
+            let mut query = world.query_filtered::<(Entity, &Name), With<TopographyMesh>>();
+            for (entity, name) in query.iter(world) {
+                let selected = state.topography == Some(entity);
+                if ui.selectable_label(selected, name.as_str()).clicked() {
+                    state.topography = Some(entity);
+                }
+            }
+        });
+
+        ui.separator();
+
+        let draped = state.topography
+            .and_then(|entity| world.get::<TopographyMesh>(entity))
+            .and_then(|topography| topography.orthophoto.clone());
+        ui.label(format!("Orthophoto: {}", draped.as_deref().unwrap_or("none")));
+
+        ui.horizontal(|ui| {
+            if ui.button("Load image").clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Georeferenced images (tif, png, jpg)", &["tif", "tiff", "png", "jpg", "jpeg"])
+                    .pick_file()
+                {
+                    let file = ImageFile { path: path.display().to_string() };
+                    state.orthophoto_result = Some(drape_orthophoto(world, state.topography, &file));
+                }
+            }
+            if draped.is_some() && ui.button("Remove").clicked() {
+                remove_orthophoto(world, state.topography.unwrap());
+                state.orthophoto_result = None;
+            }
+        });
+        ui.label(RichText::new("PNG and JPG images need a world file beside them").weak());
+
+        if let Some(status) = &state.orthophoto_result {
+            match status {
+                Ok(()) => {
+                    ui.label(RichText::new("Success!").color(egui::Color32::GREEN));
+                }
+                Err(error) => {
+                    ui.label(RichText::new(error.to_string()).color(egui::Color32::RED));
+                }
+            }
+        }
+    }
+}
+
+/// Drapes the image as the base colour texture of the topography, replacing the one it had.
+fn drape_orthophoto(world: &mut World, topography: Option<Entity>, file: &ImageFile) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(topography) = topography.filter(|entity| world.get::<TopographyMesh>(*entity).is_some()) else {
+        return Err("Select the topography to drape the image on".into());
+    };
+    let orthophoto = file.read()?;
+    let (width, height) = (orthophoto.image.width(), orthophoto.image.height());
+
+    let mesh_handle = world.get::<Handle<Mesh>>(topography).cloned().ok_or("The topography has no mesh")?;
+    let uvs = world.resource::<Assets<Mesh>>().get(&mesh_handle)
+        .map(|mesh| world.get::<TopographyMesh>(topography).unwrap().planar_uvs(mesh, &orthophoto.world_file, width, height))
+        .ok_or("The topography has no mesh")?;
+    let inside = |value: f32| (0.0..=1.0).contains(&value);
+    if !uvs.iter().any(|uv| inside(uv[0]) && inside(uv[1])) {
+        return Err("The image does not cover the topography, check its coordinates".into());
+    }
+    if let Some(mesh) = world.resource_mut::<Assets<Mesh>>().get_mut(&mesh_handle) {
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    }
+
+    // The UVs are relative to the image size, so scaling it down keeps them valid
+    let mut image = orthophoto.image;
+    if width.max(height) > MAX_TEXTURE_SIZE {
+        image = image.resize(MAX_TEXTURE_SIZE, MAX_TEXTURE_SIZE, FilterType::Triangle);
+    }
+    let texture = Image::from_dynamic(DynamicImage::ImageRgba8(image.to_rgba8()), true);
+    let texture = world.resource_mut::<Assets<Image>>().add(texture);
+
+    if let Some(handle) = world.get::<Handle<StandardMaterial>>(topography).cloned() {
+        if let Some(material) = world.resource_mut::<Assets<StandardMaterial>>().get_mut(&handle) {
+            material.base_color_texture = Some(texture);
+        }
+    }
+    // The shading system sets the base colour to show the texture as it is
+    world.get_mut::<TopographyMesh>(topography).unwrap().orthophoto = file.name_with_extension();
+    Ok(())
+}
+
+fn remove_orthophoto(world: &mut World, topography: Entity) {
+    if let Some(handle) = world.get::<Handle<StandardMaterial>>(topography).cloned() {
+        if let Some(material) = world.resource_mut::<Assets<StandardMaterial>>().get_mut(&handle) {
+            material.base_color_texture = None;
+        }
+    }
+    if let Some(mut topography) = world.get_mut::<TopographyMesh>(topography) {
+        topography.orthophoto = None;
+    }
+}
@@ -0,0 +1,277 @@
+use std::error::Error;
+
+use bevy::prelude::*;
+use bevy_editor_pls_core::editor_window::{EditorWindow, EditorWindowContext, MenuBarWindow};
+use bevy_inspector_egui::egui;
+use egui::RichText;
+
+use crate::custom_meshes::drill_holes_mesh::DrillHolesData;
+use crate::custom_meshes::planned_holes_mesh::{spawn_planned_holes, update_planned_holes, PlannedHole, PlannedHoles};
+use crate::custom_meshes::topography_mesh::TopographyMesh;
+use crate::files_manager::drill_holes_export::write_tables;
+use crate::math::surface::SurfaceGrid;
+use crate::ui_windows::cameras::ActiveEditorCamera;
+use crate::ui_windows::drill_holes_labels::DrillHolesLabels;
+
+#[derive(Default)]
+pub struct PlanHolesWindowState {
+    topography: Option<Entity>,
+    design: Option<Entity>,
+    /// Planned hole whose collar is placed by the next click on the topography
+    picking: Option<usize>,
+    plan_result: Option<Result<(), Box<dyn Error + Send + Sync>>>,
+}
+
+pub struct PlanHolesWindow;
+
+impl EditorWindow for PlanHolesWindow {
+    type State = PlanHolesWindowState;
+    const NAME: &'static str = "Plan Drill Holes";
+    const DEFAULT_SIZE: (f32, f32) = (620.0, 420.0);
+    const MENU_BAR: MenuBarWindow = MenuBarWindow::Edit;
+
+    fn ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let state = cx.state_mut::<PlanHolesWindow>().unwrap();
+
+        ui.label("Topography the holes are collared on: ");
+        ui.horizontal(|ui| {
+            let mut query = world.query_filtered::<(Entity, &Name), With<TopographyMesh>>();
+            for (entity, name) in query.iter(world) {
+                let selected = state.topography == Some(entity);
+                if ui.selectable_label(selected, name.as_str()).clicked() {
+                    state.topography = Some(entity);
+                }
+            }
+        });
+
+        ui.label("Design: ");
+        ui.horizontal(|ui| {
+            let mut query = world.query_filtered::<(Entity, &Name), With<PlannedHoles>>();
+            for (entity, name) in query.iter(world) {
+                let selected = state.design == Some(entity);
+                if ui.selectable_label(selected, name.as_str()).clicked() {
+                    state.design = Some(entity);
+                    state.picking = None;
+                }
+            }
+            if ui.button("New design").clicked() {
+                state.plan_result = Some(new_design(world, state));
+            }
+        });
+
+        ui.separator();
+
+        let Some((offset, planned)) = state.design
+            .and_then(|entity| Some((world.get::<DrillHolesData>(entity)?.offset, world.get::<PlannedHoles>(entity)?)))
+        else {
+            state.design = None;
+            status_ui(ui, state);
+            return;
+        };
+
+        let mut holes = planned.holes.clone();
+        let mut radius = planned.radius;
+        let mut remove = None;
+
+        ui.horizontal(|ui| {
+            ui.label("Trace radius: ");
+            ui.add(egui::DragValue::new(&mut radius).clamp_range(0.1..=50.0).speed(0.1).suffix(" m"));
+        });
+
+        egui::ScrollArea::vertical().max_height(220.0).show(ui, |ui| {
+            egui::Grid::new("planned_holes").striped(true).show(ui, |ui| {
+                for header in ["Hole", "X", "Y", "Z", "Azimuth", "Dip", "Length", "", ""] {
+                    ui.label(RichText::new(header).strong());
+                }
+                ui.end_row();
+
+                for (i, hole) in holes.iter_mut().enumerate() {
+                    ui.add(egui::TextEdit::singleline(&mut hole.hole_id).desired_width(70.0));
+                    for (axis, value) in hole.collar.iter_mut().enumerate() {
+                        // Shown in mine grid coordinates, stored without the offsets
+                        let mut real = *value + offset[axis];
+                        if ui.add(egui::DragValue::new(&mut real).speed(0.5).max_decimals(2)).changed() {
+                            *value = real - offset[axis];
+                        }
+                    }
+                    ui.add(egui::DragValue::new(&mut hole.azimuth).clamp_range(0.0..=360.0).speed(0.5).suffix("°"));
+                    ui.add(egui::DragValue::new(&mut hole.dip).clamp_range(-90.0..=90.0).speed(0.5).suffix("°"));
+                    ui.add(egui::DragValue::new(&mut hole.length).clamp_range(1.0..=5000.0).speed(1.0).suffix(" m"));
+
+                    let picking = state.picking == Some(i);
+                    if ui.selectable_label(picking, "Pick collar").clicked() {
+                        state.picking = if picking { None } else { Some(i) };
+                    }
+                    if ui.small_button("\u{1F5D1}").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+
+        if let Some(i) = remove {
+            holes.remove(i);
+            state.picking = None;
+        }
+
+        let mut drape = false;
+        ui.horizontal(|ui| {
+            if ui.button("Add hole").clicked() {
+                let collar = holes.last().map(|hole| hole.collar).unwrap_or_default();
+                holes.push(PlannedHole::new(format!("PH-{:03}", holes.len() + 1), collar));
+                state.picking = Some(holes.len() - 1);
+            }
+            drape = ui.button("Drape collars to topography").clicked();
+            if ui.button("Export collar and survey CSV").clicked() {
+                state.plan_result = Some(export_design(world, state));
+            }
+        });
+
+        if let Some(i) = state.picking {
+            let hole_id = holes.get(i).map(|hole| hole.hole_id.as_str()).unwrap_or_default();
+            ui.label(format!("Click on the topography to place the collar of {}, Escape to cancel", hole_id));
+        }
+
+        if drape {
+            state.plan_result = Some(drape_collars(world, state, &mut holes));
+        }
+
+        let design = state.design.unwrap();
+        let planned = world.get::<PlannedHoles>(design).unwrap();
+        if holes != planned.holes || radius != planned.radius {
+            let mut planned = world.get_mut::<PlannedHoles>(design).unwrap();
+            planned.holes = holes;
+            planned.radius = radius;
+        }
+
+        status_ui(ui, state);
+    }
+
+    fn viewport_ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let Some(state) = cx.state_mut::<PlanHolesWindow>() else {
+            return;
+        };
+        let (Some(design), Some(hole)) = (state.design, state.picking) else {
+            return;
+        };
+        if ui.input(|input| input.key_pressed(egui::Key::Escape)) {
+            state.picking = None;
+            return;
+        }
+
+        let viewport = ui.clip_rect();
+        let pointer = ui.ctx().pointer_interact_pos()
+            .filter(|pointer| viewport.contains(*pointer))
+            .filter(|pointer| ui.ctx().layer_id_at(*pointer).map_or(true, |layer| layer.order == egui::Order::Background));
+        let Some(pointer) = pointer else {
+            return;
+        };
+        if !ui.input(|input| input.pointer.primary_clicked()) {
+            return;
+        }
+
+        if let Some(collar) = pick_collar(world, design, pointer - viewport.left_top()) {
+            if let Some(mut planned) = world.get_mut::<PlannedHoles>(design) {
+                if let Some(hole) = planned.holes.get_mut(hole) {
+                    hole.collar = collar;
+                }
+            }
+            state.picking = None;
+        }
+    }
+
+    fn app_setup(app: &mut App) {
+        app.add_systems(Update, update_planned_holes);
+    }
+}
+
+fn status_ui(ui: &mut egui::Ui, state: &PlanHolesWindowState) {
+    if let Some(status) = &state.plan_result {
+        match status {
+            Ok(()) => {
+                ui.label(RichText::new("Success!").color(egui::Color32::GREEN));
+            }
+            Err(error) => {
+                ui.label(RichText::new(error.to_string()).color(egui::Color32::RED));
+            }
+        }
+    }
+}
+
+/// Spawns an empty design under the selected topography, with its offsets.
+fn new_design(world: &mut World, state: &mut PlanHolesWindowState) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some((topography, offset)) = state.topography
+        .and_then(|entity| world.get::<TopographyMesh>(entity).map(|topography| (entity, topography)))
+        .map(|(entity, topography)| (entity, [topography.offset_x as f32, topography.offset_y as f32, topography.offset_z as f32]))
+    else {
+        return Err("Select the topography the holes are collared on".into());
+    };
+
+    let count = world.query::<&PlannedHoles>().iter(world).count();
+    let design = spawn_planned_holes(world, topography, offset, &format!("Planned Holes {}", count + 1));
+    world.entity_mut(design).insert(DrillHolesLabels::default());
+    state.design = Some(design);
+    state.picking = None;
+    Ok(())
+}
+
+/// Surface of the topography the design is spawned under, in the local space of both.
+fn design_surface(world: &World, design: Entity) -> Option<SurfaceGrid> {
+    let topography = world.get::<Parent>(design)?.get();
+    let handle = world.get::<Handle<Mesh>>(topography)?;
+    world.resource::<Assets<Mesh>>().get(handle).and_then(SurfaceGrid::from_mesh)
+}
+
+/// Collar of the point of the topography under the viewport position, `None` when it is missed.
+fn pick_collar(world: &mut World, design: Entity, position: egui::Vec2) -> Option<[f32; 3]> {
+    let ray = world
+        .query_filtered::<(&Camera, &GlobalTransform), With<ActiveEditorCamera>>()
+        .get_single(world)
+        .ok()
+        .and_then(|(camera, transform)| camera.viewport_to_world(transform, Vec2::new(position.x, position.y)))?;
+
+    let transform = world.get::<GlobalTransform>(design)?;
+    let inverse = transform.affine().inverse();
+    let origin = inverse.transform_point3(ray.origin);
+    let direction = inverse.transform_vector3(ray.direction);
+
+    let point = design_surface(world, design)?.raycast(origin, direction)?;
+    Some([point.x, point.z, point.y])
+}
+
+/// Moves every collar to the elevation of the topography under it.
+fn drape_collars(world: &World, state: &PlanHolesWindowState, holes: &mut [PlannedHole]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let surface = state.design
+        .and_then(|design| design_surface(world, design))
+        .ok_or("The topography has no triangles to drape the collars on")?;
+
+    let mut outside = vec![];
+    for hole in holes.iter_mut() {
+        match surface.elevation(hole.collar[0], hole.collar[1]) {
+            Some(elevation) => hole.collar[2] = elevation,
+            None => outside.push(hole.hole_id.clone()),
+        }
+    }
+
+    if !outside.is_empty() {
+        return Err(format!("Collars outside of the topography: {}", outside.join(", ")).into());
+    }
+    Ok(())
+}
+
+/// Writes `collar.csv` and `survey.csv` of the design in a folder, in mine grid coordinates,
+/// with empty `assay.csv` and `lithology.csv` so the Load Drills dialog takes the folder as is.
+fn export_design(world: &World, state: &PlanHolesWindowState) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(data) = state.design.and_then(|design| world.get::<DrillHolesData>(design)) else {
+        return Err("Select the design to export".into());
+    };
+    if data.holes.is_empty() {
+        return Err("The design has no holes".into());
+    }
+
+    if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+        write_tables(data, &folder)?;
+    }
+    Ok(())
+}
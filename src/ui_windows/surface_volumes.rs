@@ -0,0 +1,283 @@
+use std::error::Error;
+
+use bevy::prelude::*;
+use bevy_editor_pls_core::editor_window::{EditorWindow, EditorWindowContext, MenuBarWindow};
+use bevy_inspector_egui::egui;
+use egui::RichText;
+
+use crate::custom_meshes::color_legend::Colormap;
+use crate::custom_meshes::topography_mesh::{ElevationDifference, TopographyMesh, TOPOGRAPHY_COLOR};
+use crate::files_manager::dxf_parser::DxfFile;
+use crate::files_manager::files_porperties::FileProperties;
+use crate::math::surface::SurfaceGrid;
+use crate::math::volumes::{elevation_differences, surface_volumes, PlacedSurface, VolumeReport, MAX_VOLUME_CELLS};
+use crate::ui_windows::color_legend::grade_legend_ui;
+
+pub struct SurfaceVolumesWindowState {
+    /// Older surface
+    base: Option<Entity>,
+    /// Newer surface, the difference map is shaded onto it
+    top: Option<Entity>,
+    cell_size: f64,
+    boundary_file: Option<DxfFile>,
+    boundary: Vec<Vec<[f64; 2]>>,
+    report: Option<VolumeReport>,
+    volumes_result: Option<Result<(), Box<dyn Error + Send + Sync>>>,
+}
+
+impl Default for SurfaceVolumesWindowState {
+    fn default() -> Self {
+        Self {
+            base: None,
+            top: None,
+            cell_size: 1.0,
+            boundary_file: None,
+            boundary: vec![],
+            report: None,
+            volumes_result: None,
+        }
+    }
+}
+
+pub struct SurfaceVolumesWindow;
+
+impl EditorWindow for SurfaceVolumesWindow {
+    type State = SurfaceVolumesWindowState;
+    const NAME: &'static str = "Surface Volumes";
+    const DEFAULT_SIZE: (f32, f32) = (400.0, 420.0);
+    const MENU_BAR: MenuBarWindow = MenuBarWindow::Edit;
+
+    fn ui(world: &mut World, mut cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let state = cx.state_mut::<SurfaceVolumesWindow>().unwrap();
+
+        let surfaces = world.query_filtered::<(Entity, &Name), With<TopographyMesh>>()
+            .iter(world)
+            .map(|(entity, name)| (entity, name.to_string()))
+            .collect::<Vec<_>>();
+        let name_of = |entity: Option<Entity>| {
+            surfaces.iter().find(|(other, _)| Some(*other) == entity).map(|(_, name)| name.clone()).unwrap_or_default()
+        };
+
+        egui::Grid::new("surface_volumes").show(ui, |ui| {
+            ui.label("Base (older) surface");
+            egui::ComboBox::from_id_source("volumes_base")
+                .selected_text(name_of(state.base))
+                .show_ui(ui, |ui| {
+                    for (entity, name) in surfaces.iter() {
+                        ui.selectable_value(&mut state.base, Some(*entity), name);
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Top (newer) surface");
+            egui::ComboBox::from_id_source("volumes_top")
+                .selected_text(name_of(state.top))
+                .show_ui(ui, |ui| {
+                    for (entity, name) in surfaces.iter() {
+                        ui.selectable_value(&mut state.top, Some(*entity), name);
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Cell size");
+            ui.add(egui::DragValue::new(&mut state.cell_size).clamp_range(0.1..=100.0).speed(0.05).suffix(" m"));
+            ui.end_row();
+
+            ui.label("Boundary");
+            ui.horizontal(|ui| {
+                let boundary_name = state.boundary_file.as_ref()
+                    .map(|file| format!("{} ({} polygons)", file.name_with_extension().unwrap_or_default(), state.boundary.len()))
+                    .unwrap_or_else(|| "Whole overlap".to_string());
+                ui.label(boundary_name);
+                if ui.button("From DXF").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().add_filter("CAD files (dxf)", &["dxf"]).pick_file() {
+                        let file = DxfFile { path: path.display().to_string() };
+                        match file.get_polygons(None) {
+                            Ok(boundary) => {
+                                state.boundary = boundary;
+                                state.boundary_file = Some(file);
+                            }
+                            Err(error) => state.volumes_result = Some(Err(error)),
+                        }
+                    }
+                }
+                if state.boundary_file.is_some() && ui.button("Clear").clicked() {
+                    state.boundary_file = None;
+                    state.boundary.clear();
+                }
+            });
+            ui.end_row();
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Calculate").clicked() {
+                state.volumes_result = Some(calculate(world, state));
+            }
+            let shaded = state.top.map_or(false, |top| world.get::<ElevationDifference>(top).is_some());
+            if shaded && ui.button("Clear difference map").clicked() {
+                clear_difference(world, state.top.unwrap());
+            }
+        });
+
+        if let Some(report) = &state.report {
+            ui.separator();
+            egui::Grid::new("volume_report").striped(true).show(ui, |ui| {
+                for (name, value, unit) in [
+                    ("Cut", report.cut, "m³"),
+                    ("Fill", report.fill, "m³"),
+                    ("Net", report.net(), "m³"),
+                    ("Area", report.area, "m²"),
+                ] {
+                    ui.label(RichText::new(name).strong());
+                    ui.label(format!("{:.1} {}", value, unit));
+                    ui.end_row();
+                }
+            });
+            if report.cell_size > state.cell_size {
+                ui.label(RichText::new(format!(
+                    "Cells of {:.2} m, raised from {:.2} m to stay under {} cells",
+                    report.cell_size, state.cell_size, MAX_VOLUME_CELLS,
+                )).color(egui::Color32::YELLOW));
+            }
+        }
+
+        let difference = state.top
+            .and_then(|top| world.get::<ElevationDifference>(top))
+            .map(|difference| (difference.base.clone(), difference.legend.clone()));
+        if let Some((base, current)) = difference {
+            ui.separator();
+            ui.label(format!("Difference map to {}", base));
+            let mut legend = current.clone();
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("difference_colormap")
+                    .selected_text(legend.colormap.name())
+                    .show_ui(ui, |ui| {
+                        for colormap in Colormap::all() {
+                            ui.selectable_value(&mut legend.colormap, colormap, colormap.name());
+                        }
+                    });
+                ui.label("Range ±");
+                let mut range = legend.max;
+                if ui.add(egui::DragValue::new(&mut range).clamp_range(0.01..=1000.0).speed(0.05).suffix(" m")).changed() {
+                    legend.min = -range;
+                    legend.max = range;
+                }
+            });
+
+            if legend != current {
+                let top = state.top.unwrap();
+                let mut difference = world.get_mut::<ElevationDifference>(top).unwrap();
+                difference.legend = legend;
+                let colors = difference.colors();
+                set_vertex_colors(world, top, Some(colors));
+            }
+        }
+
+        if let Some(status) = &state.volumes_result {
+            match status {
+                Ok(()) => {
+                    ui.label(RichText::new("Success!").color(egui::Color32::GREEN));
+                }
+                Err(error) => {
+                    ui.label(RichText::new(error.to_string()).color(egui::Color32::RED));
+                }
+            }
+        }
+    }
+
+    fn viewport_ui(world: &mut World, cx: EditorWindowContext, ui: &mut egui::Ui) {
+        let Some(state) = cx.state::<SurfaceVolumesWindow>() else {
+            return;
+        };
+        let Some(difference) = state.top.and_then(|top| world.get::<ElevationDifference>(top)) else {
+            return;
+        };
+
+        let viewport = ui.clip_rect();
+        egui::Area::new("difference_legend_viewport")
+            .fixed_pos(viewport.right_bottom() + egui::vec2(-10.0, -10.0))
+            .pivot(egui::Align2::RIGHT_BOTTOM)
+            .interactable(false)
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(RichText::new("Elevation difference (m)").strong());
+                    grade_legend_ui(ui, &difference.legend);
+                });
+            });
+    }
+}
+
+fn surface_grid(world: &World, entity: Entity) -> Option<SurfaceGrid> {
+    let handle = world.get::<Handle<Mesh>>(entity)?;
+    world.resource::<Assets<Mesh>>().get(handle).and_then(SurfaceGrid::from_mesh)
+}
+
+fn offset(world: &World, entity: Entity) -> Option<[f64; 3]> {
+    let topography = world.get::<TopographyMesh>(entity)?;
+    Some([topography.offset_x, topography.offset_y, topography.offset_z])
+}
+
+/// Reports the volumes between both surfaces and shades the differences onto the top one.
+fn calculate(world: &mut World, state: &mut SurfaceVolumesWindowState) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (Some(base), Some(top)) = (state.base, state.top) else {
+        return Err("Select the base and top surfaces".into());
+    };
+    if base == top {
+        return Err("The base and top surfaces are the same".into());
+    }
+    let (Some(base_offset), Some(top_offset)) = (offset(world, base), offset(world, top)) else {
+        return Err("Select the base and top surfaces".into());
+    };
+    let (Some(base_grid), Some(top_grid)) = (surface_grid(world, base), surface_grid(world, top)) else {
+        return Err("The surfaces have no triangles".into());
+    };
+
+    let base_surface = PlacedSurface { grid: &base_grid, offset: base_offset };
+    let top_surface = PlacedSurface { grid: &top_grid, offset: top_offset };
+    let report = surface_volumes(&base_surface, &top_surface, &state.boundary, state.cell_size);
+    state.report = Some(report);
+    if report.area <= 0.0 {
+        return Err("The surfaces do not overlap inside the boundary".into());
+    }
+
+    let positions = world.get::<Handle<Mesh>>(top)
+        .and_then(|handle| world.resource::<Assets<Mesh>>().get(handle))
+        .and_then(|mesh| mesh.attribute(Mesh::ATTRIBUTE_POSITION))
+        .and_then(|positions| positions.as_float3())
+        .map(|positions| positions.to_vec())
+        .unwrap_or_default();
+    let values = elevation_differences(&base_surface, &positions, top_offset, &state.boundary);
+
+    let base_name = world.get::<Name>(base).map(|name| name.to_string()).unwrap_or_default();
+    let difference = ElevationDifference::new(base_name, values);
+    set_vertex_colors(world, top, Some(difference.colors()));
+    world.entity_mut(top).insert(difference);
+    Ok(())
+}
+
+fn clear_difference(world: &mut World, top: Entity) {
+    world.entity_mut(top).remove::<ElevationDifference>();
+    set_vertex_colors(world, top, None);
+}
+
+/// Sets the vertex colours of the topography mesh, white material included so they show
+/// as they are. `None` goes back to the plain topography colour or its orthophoto.
+fn set_vertex_colors(world: &mut World, entity: Entity, colors: Option<Vec<[f32; 4]>>) {
+    let base_color = world.get::<TopographyMesh>(entity)
+        .map_or(TOPOGRAPHY_COLOR, |topography| topography.base_color(colors.is_some()));
+    if let Some(handle) = world.get::<Handle<Mesh>>(entity).cloned() {
+        if let Some(mesh) = world.resource_mut::<Assets<Mesh>>().get_mut(&handle) {
+            match &colors {
+                Some(colors) => mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors.clone()),
+                None => {
+                    mesh.remove_attribute(Mesh::ATTRIBUTE_COLOR);
+                }
+            }
+        }
+    }
+    if let Some(handle) = world.get::<Handle<StandardMaterial>>(entity).cloned() {
+        if let Some(material) = world.resource_mut::<Assets<StandardMaterial>>().get_mut(&handle) {
+            material.base_color = base_color;
+        }
+    }
+}